@@ -10,9 +10,23 @@ fn main() {
 
     if std::env::var("CARGO_FEATURE_PAYLOAD").is_ok() {
         let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
-        println!("cargo:rustc-link-arg=-T{}/payload/linker.ld", manifest_dir);
         println!("cargo:rustc-link-arg=-nostdlib");
         println!("cargo:rustc-link-arg=-static");
+        // `cargo xtask build --pie` sets this, along with a PIE relocation
+        // model, to link static PIEs the kernel relocates at load time.
+        println!("cargo:rerun-if-env-changed=LAZYMAPPING_PIE");
+        if std::env::var_os("LAZYMAPPING_PIE").is_some() {
+            println!("cargo:rustc-link-arg=-T{}/payload/pie.ld", manifest_dir);
+            println!("cargo:rustc-link-arg=-pie");
+            println!("cargo:rustc-link-arg=--no-dynamic-linker");
+        } else {
+            println!("cargo:rustc-link-arg=-T{}/payload/linker.ld", manifest_dir);
+            // x86_64-unknown-none links static PIEs by default.
+            println!("cargo:rustc-link-arg=-no-pie");
+        }
+        // The kernel maps segments with 4K pages. With the 64K default of
+        // some targets, the ELF headers would end up loaded at address 0.
+        println!("cargo:rustc-link-arg=-zmax-page-size=4096");
         return;
     }
 
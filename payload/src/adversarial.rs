@@ -0,0 +1,73 @@
+//! Minimal user-space binary that feeds the kernel bad syscall arguments.
+//!
+//! Every call below passes pointers that wrap around or hit no region,
+//! lengths that overflow once rounded to pages, or unsupported flags. Each
+//! must fail with an errno (or, for SYS_BRK, leave the break untouched)
+//! rather than panic the kernel. Calls SYS_EXIT with 0 if they all do, or 1
+//! otherwise.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{
+    CLONE_VM, MADV_DONTNEED, MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, PROT_READ, SYS_BRK,
+    SYS_CLOCK_GETTIME, SYS_CLONE, SYS_GETRANDOM, SYS_MADVISE, SYS_MMAP, SYS_MUNMAP, SYS_NANOSLEEP,
+    SYS_RT_SIGACTION, SYS_RT_SIGRETURN, SYS_WRITE, exit, is_err, syscall1, syscall4,
+};
+
+const CLOCK_MONOTONIC: usize = 1;
+const SIGSEGV: usize = 11;
+
+/// Address outside every region of the task.
+const WILD: usize = 0xdead_0000;
+
+/// Page in the kernel's mmap window.
+const MMAP_ADDR: usize = 0x10_0000_0000;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    exit(if check_adversarial() { 0 } else { 1 })
+}
+
+fn check_adversarial() -> bool {
+    let brk = syscall1(SYS_BRK, 0);
+    let top = usize::MAX - 3;
+    let calls = [
+        syscall4(SYS_WRITE, 1, top, 16, 0),
+        syscall4(SYS_WRITE, 1, WILD, 8, 0),
+        syscall4(
+            SYS_MMAP,
+            0,
+            usize::MAX,
+            PROT_READ,
+            MAP_PRIVATE | MAP_ANONYMOUS,
+        ),
+        syscall4(
+            SYS_MMAP,
+            MMAP_ADDR,
+            usize::MAX - 0x1000,
+            PROT_READ,
+            MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+        ),
+        syscall4(SYS_MUNMAP, usize::MAX & !0xfff, 0x2000, 0, 0),
+        syscall4(SYS_MADVISE, 0, usize::MAX, MADV_DONTNEED, 0),
+        syscall4(SYS_CLOCK_GETTIME, CLOCK_MONOTONIC, top, 0, 0),
+        syscall4(SYS_RT_SIGACTION, SIGSEGV, top, 0, 0),
+        syscall4(SYS_RT_SIGACTION, SIGSEGV, 0, WILD, 0),
+        syscall4(SYS_NANOSLEEP, top, 0, 0, 0),
+        syscall4(SYS_GETRANDOM, top, 16, 0, 0),
+        syscall4(SYS_GETRANDOM, brk, 16, 0x80, 0),
+        syscall4(SYS_CLONE, CLONE_VM, 0x1000, 0, 0),
+        syscall1(SYS_RT_SIGRETURN, 0),
+    ];
+    calls.iter().all(|&ret| is_err(ret)) && syscall1(SYS_BRK, usize::MAX) == brk
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
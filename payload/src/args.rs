@@ -0,0 +1,70 @@
+//! Minimal user-space binary that reads the arguments the kernel laid out on
+//! its initial stack.
+//!
+//! At entry, the stack pointer points at `argc`, followed by the `argv` and
+//! `envp` arrays, each ending with a null pointer, and by the auxiliary
+//! vector, as by the System V ABI. It checks that layout, then calls SYS_EXIT
+//! with `argc`, or with 255 if the layout is wrong.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::exit;
+
+/// Most environment variables walked before giving up on a missing null
+/// pointer.
+const MAX_ENV: usize = 64;
+
+/// Passes the initial stack pointer to [`start`].
+#[unsafe(naked)]
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    #[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+    core::arch::naked_asm!("mv a0, sp", "tail {start}", start = sym start);
+
+    #[cfg(target_arch = "aarch64")]
+    core::arch::naked_asm!("mov x0, sp", "b {start}", start = sym start);
+
+    // The call pushes a return address, as for any function entry.
+    #[cfg(target_arch = "x86_64")]
+    core::arch::naked_asm!(
+        "mov rdi, rsp",
+        "and rsp, -16",
+        "call {start}",
+        start = sym start,
+    );
+
+    #[cfg(target_arch = "loongarch64")]
+    core::arch::naked_asm!("move $a0, $sp", "b {start}", start = sym start);
+}
+
+unsafe extern "C" fn start(sp: *const usize) -> ! {
+    exit(unsafe { check_args(sp) }.unwrap_or(255))
+}
+
+/// Returns `argc` if the initial stack at `sp` is laid out as expected.
+unsafe fn check_args(sp: *const usize) -> Option<usize> {
+    if !(sp as usize).is_multiple_of(16) {
+        return None;
+    }
+    let word = |i: usize| unsafe { sp.add(i).read_volatile() };
+    let argc = word(0);
+    // Every argument is a non-empty string.
+    let args_ok = (1..=argc).all(|i| word(i) != 0 && unsafe { (word(i) as *const u8).read() } != 0);
+    if !args_ok || word(argc + 1) != 0 {
+        return None;
+    }
+    let envp = argc + 2;
+    let envc = (0..MAX_ENV).find(|&i| word(envp + i) == 0)?;
+    // The auxiliary vector holds at least its AT_NULL terminator.
+    (word(envp + envc + 1) == 0).then_some(argc)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(255)
+}
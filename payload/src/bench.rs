@@ -0,0 +1,38 @@
+//! Minimal user-space binary that touches many heap pages.
+//!
+//! It grows the heap by `PAGES` pages with SYS_BRK and writes one byte to
+//! each of them, so that each write takes a fault, then calls SYS_EXIT with
+//! 0, or 1 if the heap cannot grow. Built with the `fault_timing` feature,
+//! the kernel then reports the average time it took to map a page.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{SYS_BRK, exit, syscall1};
+
+const PAGE_SIZE: usize = 0x1000;
+
+/// Number of heap pages touched.
+const PAGES: usize = 1024;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    let base = syscall1(SYS_BRK, 0);
+    let end = base + PAGES * PAGE_SIZE;
+    if syscall1(SYS_BRK, end) != end {
+        exit(1)
+    }
+    for page in (base..end).step_by(PAGE_SIZE) {
+        unsafe { (page as *mut u8).write_volatile(1) };
+    }
+    exit(0)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
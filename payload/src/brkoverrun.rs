@@ -0,0 +1,40 @@
+//! Minimal user-space binary that writes one page past its program break.
+//!
+//! It moves the break with SYS_BRK to 8 bytes into the third page of the
+//! heap, writes a word in each of those 3 pages, then writes one in the page
+//! after them, past the break: the kernel terminates the task there, with a
+//! heap overrun if the heap is guarded. Calls SYS_EXIT with 1 if that write
+//! goes through, or if the break does not move.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{SYS_BRK, exit, syscall1};
+
+const PAGE_SIZE: usize = 0x1000;
+
+const PAGES: usize = 2;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    let base = syscall1(SYS_BRK, 0);
+    let brk = base + PAGES * PAGE_SIZE + 8;
+    if syscall1(SYS_BRK, brk) != brk {
+        exit(1);
+    }
+    // The page of the break is part of the heap, the last write is the
+    // overrun.
+    for page in 0..=PAGES + 1 {
+        unsafe { ((base + page * PAGE_SIZE) as *mut usize).write_volatile(page) };
+    }
+    exit(1)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,64 @@
+//! Minimal user-space binary that echoes a line of console input.
+//!
+//! A zero-length SYS_READ must return 0, and a read into `0xdead0000`,
+//! outside all of its regions, must fail with `-EFAULT` before any input is
+//! waited for. It then grows the heap with SYS_BRK by one page, which it does
+//! not touch, and reads a line from stdin there with SYS_READ: the kernel
+//! faults the page in on the copy. It writes the line back with SYS_WRITE,
+//! prefixed with `> `, and calls SYS_EXIT with 0 if a whole line was read,
+//! and with 1 otherwise.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{SYS_BRK, SYS_READ, SYS_WRITE, exit, syscall1, syscall4};
+
+const PAGE_SIZE: usize = 0x1000;
+
+const EFAULT: usize = -14isize as usize;
+
+const PREFIX: &[u8] = b"> ";
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    exit(if echo_line() { 0 } else { 1 })
+}
+
+fn echo_line() -> bool {
+    if syscall4(SYS_READ, 0, 0xdead0000, 0, 0) != 0
+        || syscall4(SYS_READ, 0, 0xdead0000, 16, 0) != EFAULT
+    {
+        return false;
+    }
+    let base = syscall1(SYS_BRK, 0);
+    let end = base + PAGE_SIZE;
+    if syscall1(SYS_BRK, end) != end {
+        return false;
+    }
+    // The line is read right after the prefix, which is written once the
+    // page is faulted in.
+    let line = base + PREFIX.len();
+    let mut len = 0;
+    while len == 0 || unsafe { ((line + len - 1) as *const u8).read_volatile() } != b'\n' {
+        match syscall4(SYS_READ, 0, line + len, end - line - len, 0) {
+            // End of file, or no room left, before the newline.
+            0 => return false,
+            n if n > end - line - len => return false,
+            n => len += n,
+        }
+    }
+    for (i, &byte) in PREFIX.iter().enumerate() {
+        unsafe { ((base + i) as *mut u8).write_volatile(byte) };
+    }
+    let total = PREFIX.len() + len;
+    syscall4(SYS_WRITE, 1, base, total, 0) == total
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,26 @@
+//! Minimal user-space binary that exits with a code wider than 8 bits.
+//!
+//! It calls SYS_EXIT with 0x142: only the low 8 bits make up the exit
+//! status, so the kernel must report the task exiting with 0x42 (66).
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::exit;
+
+/// The code passed to SYS_EXIT, whose exit status is 0x42.
+const EXIT_CODE: usize = 0x142;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    exit(EXIT_CODE)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
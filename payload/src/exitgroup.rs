@@ -0,0 +1,81 @@
+//! Minimal user-space binary that exits its thread group while a thread of
+//! it spins.
+//!
+//! It maps a control page and a stack for a thread at fixed addresses, then
+//! creates the thread with SYS_CLONE. The thread counts itself started in
+//! the control page and spins there forever, never making a syscall. Once
+//! the thread has started, the main thread calls SYS_EXIT_GROUP with 7,
+//! which must take the spinning thread down too for the task to exit. Calls
+//! SYS_EXIT with 1 if the thread never starts or a syscall fails.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use sys::{
+    CLONE_THREAD, CLONE_VM, MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, PROT_READ, PROT_WRITE, SYS_MMAP,
+    SYS_SCHED_YIELD, clone, exit, exit_group, is_err, syscall1, syscall4,
+};
+
+const PAGE_SIZE: usize = 0x1000;
+
+/// Page holding the started flag of the thread, then the count of its
+/// spins, inside the kernel's mmap window.
+const CTRL_ADDR: usize = 0x10_0070_0000;
+/// Stack of the thread, right above the control page.
+const STACK_ADDR: usize = CTRL_ADDR + PAGE_SIZE;
+const STACK_SIZE: usize = 4 * PAGE_SIZE;
+
+/// Number of times the main thread yields before giving up on the thread.
+const MAX_YIELDS: usize = 10_000;
+
+/// Exit code of the thread group.
+const GROUP_EXIT_CODE: usize = 7;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    let prot = PROT_READ | PROT_WRITE;
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED;
+    for (addr, size) in [(CTRL_ADDR, PAGE_SIZE), (STACK_ADDR, STACK_SIZE)] {
+        if syscall4(SYS_MMAP, addr, size, prot, flags) != addr {
+            exit(1);
+        }
+    }
+    let tid = clone(
+        CLONE_VM | CLONE_THREAD,
+        STACK_ADDR + STACK_SIZE,
+        thread_main,
+    );
+    if tid == 0 || is_err(tid) {
+        exit(1);
+    }
+    for _ in 0..MAX_YIELDS {
+        if counter(0).load(Ordering::Acquire) != 0 {
+            exit_group(GROUP_EXIT_CODE);
+        }
+        syscall1(SYS_SCHED_YIELD, 0);
+    }
+    exit(1)
+}
+
+/// Returns the counter `index` of the control page.
+fn counter(index: usize) -> &'static AtomicUsize {
+    unsafe { AtomicUsize::from_ptr((CTRL_ADDR as *mut usize).add(index)) }
+}
+
+/// Entry point of the thread, on its own stack.
+extern "C" fn thread_main() -> ! {
+    counter(0).store(1, Ordering::Release);
+    loop {
+        counter(1).fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,134 @@
+//! Minimal user-space binary that keeps a floating-point value in a register
+//! across a page fault and a context switch.
+//!
+//! It grows the heap with SYS_BRK by one page, which it does not touch, then
+//! converts its pid to a double in an FP register, writes to the new page,
+//! which faults it in, and yields with SYS_SCHED_YIELD so that the other
+//! tasks run their own copy in between. It then doubles the register and
+//! converts it back, and calls SYS_EXIT with 0 if the result is twice its
+//! pid, and with 1 otherwise.
+//!
+//! The FP registers must be saved and restored by the kernel (feature
+//! `fp-simd`); without it, the first FP instruction traps and the task is
+//! terminated.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{SYS_BRK, SYS_GETPID, SYS_SCHED_YIELD, exit, syscall1};
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    exit(if check_fp() { 0 } else { 1 })
+}
+
+fn check_fp() -> bool {
+    let base = syscall1(SYS_BRK, 0);
+    let end = base + 0x1000;
+    if syscall1(SYS_BRK, end) != end {
+        return false;
+    }
+    let pid = syscall1(SYS_GETPID, 0);
+    unsafe { double_across_fault(pid, base as *mut usize) == 2 * pid }
+}
+
+/// Converts `value` to a double, writes to `page`, yields, then returns twice
+/// the double converted back, all without leaving the FP register file.
+#[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+unsafe fn double_across_fault(value: usize, page: *mut usize) -> usize {
+    let ret;
+    unsafe {
+        core::arch::asm!(
+            "fcvt.d.l fa0, {value}",
+            "sd zero, 0({page})",
+            "ecall",
+            "fadd.d fa0, fa0, fa0",
+            "fcvt.l.d {ret}, fa0, rtz",
+            value = in(reg) value,
+            page = in(reg) page,
+            ret = lateout(reg) ret,
+            inlateout("a7") SYS_SCHED_YIELD => _,
+            lateout("a0") _,
+            out("fa0") _,
+        );
+    }
+    ret
+}
+
+#[cfg(target_arch = "loongarch64")]
+unsafe fn double_across_fault(value: usize, page: *mut usize) -> usize {
+    let ret;
+    unsafe {
+        core::arch::asm!(
+            "movgr2fr.d $fa0, {value}",
+            "ffint.d.l $fa0, $fa0",
+            "st.d $zero, {page}, 0",
+            "syscall 0",
+            "fadd.d $fa0, $fa0, $fa0",
+            "ftintrz.l.d $fa0, $fa0",
+            "movfr2gr.d {ret}, $fa0",
+            value = in(reg) value,
+            page = in(reg) page,
+            ret = lateout(reg) ret,
+            inlateout("$a7") SYS_SCHED_YIELD => _,
+            lateout("$a0") _,
+            out("$fa0") _,
+        );
+    }
+    ret
+}
+
+// The target is soft-float: the compiler never allocates the SSE registers,
+// so xmm0 is used without being declared as clobbered.
+#[cfg(target_arch = "x86_64")]
+unsafe fn double_across_fault(value: usize, page: *mut usize) -> usize {
+    let ret;
+    unsafe {
+        core::arch::asm!(
+            "cvtsi2sd xmm0, {value}",
+            "mov qword ptr [{page}], 0",
+            "syscall",
+            "addsd xmm0, xmm0",
+            "cvttsd2si {ret}, xmm0",
+            value = in(reg) value,
+            page = in(reg) page,
+            ret = lateout(reg) ret,
+            inlateout("rax") SYS_SCHED_YIELD => _,
+            out("rcx") _,
+            out("r11") _,
+        );
+    }
+    ret
+}
+
+// As on x86_64, the target is soft-float, so d0 is used without being
+// declared as clobbered.
+#[cfg(target_arch = "aarch64")]
+unsafe fn double_across_fault(value: usize, page: *mut usize) -> usize {
+    let ret;
+    unsafe {
+        core::arch::asm!(
+            ".arch_extension fp",
+            "scvtf d0, {value}",
+            "str xzr, [{page}]",
+            "svc #0",
+            "fadd d0, d0, d0",
+            "fcvtzs {ret}, d0",
+            value = in(reg) value,
+            page = in(reg) page,
+            ret = lateout(reg) ret,
+            inlateout("x8") SYS_SCHED_YIELD => _,
+            lateout("x0") _,
+        );
+    }
+    ret
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,44 @@
+//! Minimal user-space binary that checks its stdout with SYS_FSTAT.
+//!
+//! It grows the heap with SYS_BRK by one page, which it does not touch, and
+//! has SYS_FSTAT write the [`Stat`] of fd 1 there: the kernel faults the page
+//! in on the copy. Calls SYS_EXIT with 0 if the mode is that of a character
+//! device and an fd that is not open fails with `-EBADF`, and with 1
+//! otherwise.
+
+#![no_std]
+#![no_main]
+
+#[path = "../../src/stat.rs"]
+mod stat;
+mod sys;
+
+use core::panic::PanicInfo;
+
+use stat::{S_IFCHR, S_IFMT, Stat};
+use sys::{SYS_BRK, SYS_FSTAT, exit, syscall1, syscall4};
+
+const EBADF: usize = 9;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    exit(if check_fstat() { 0 } else { 1 })
+}
+
+fn check_fstat() -> bool {
+    let buf = syscall1(SYS_BRK, 0);
+    let end = buf + 0x1000;
+    if syscall1(SYS_BRK, end) != end {
+        return false;
+    }
+    if syscall4(SYS_FSTAT, 1, buf, 0, 0) != 0 {
+        return false;
+    }
+    let stat = unsafe { (buf as *const Stat).read_volatile() };
+    stat.st_mode & S_IFMT == S_IFCHR && syscall4(SYS_FSTAT, 3, buf, 0, 0) == EBADF.wrapping_neg()
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,44 @@
+//! Minimal user-space binary that has the kernel write to an untouched lazy
+//! page.
+//!
+//! It grows the heap with SYS_BRK by one page, which it does not touch, and
+//! asks SYS_GETRANDOM for 32 bytes in it. It then calls SYS_EXIT with 0 if
+//! all 32 bytes were written, and with 1 otherwise.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{SYS_BRK, SYS_GETRANDOM, exit, syscall1, syscall4};
+
+/// Number of random bytes requested.
+const LEN: usize = 32;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    exit(if check_getrandom() { 0 } else { 1 })
+}
+
+fn check_getrandom() -> bool {
+    let base = syscall1(SYS_BRK, 0);
+    let end = base + 0x1000;
+    if syscall1(SYS_BRK, end) != end {
+        return false;
+    }
+    // The page is faulted in by the kernel, on the copy to user space.
+    if syscall4(SYS_GETRANDOM, base, LEN, 0, 0) != LEN {
+        return false;
+    }
+    let bytes = base as *const u8;
+    let read = |i| unsafe { bytes.add(i).read_volatile() };
+    // 32 zero bytes are as likely as any other value, which is to say never.
+    (0..LEN).any(|i| read(i) != 0) && read(LEN) == 0
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
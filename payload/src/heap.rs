@@ -0,0 +1,52 @@
+//! Minimal user-space binary that exercises demand paging of the heap.
+//!
+//! It grows the heap with SYS_BRK, checks that the new pages read as zeros,
+//! fills them with a pattern and reads it back, then calls SYS_EXIT with 0 on
+//! success or 1 on any mismatch.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{SYS_BRK, exit, syscall1};
+
+/// Number of heap bytes checked: two pages, so two heap faults.
+const HEAP_SIZE: usize = 0x2000;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    exit(if check_heap() { 0 } else { 1 })
+}
+
+fn check_heap() -> bool {
+    let base = syscall1(SYS_BRK, 0);
+    let end = base + HEAP_SIZE;
+    if syscall1(SYS_BRK, end) != end {
+        return false;
+    }
+
+    let words = base as *mut usize;
+    let len = HEAP_SIZE / size_of::<usize>();
+    for i in 0..len {
+        // Each access to a new page faults it in.
+        let word = unsafe { words.add(i) };
+        if unsafe { word.read_volatile() } != 0 {
+            return false;
+        }
+        unsafe { word.write_volatile(pattern(i)) };
+    }
+    (0..len).all(|i| unsafe { words.add(i).read_volatile() } == pattern(i))
+}
+
+/// Value written to the `i`-th heap word.
+fn pattern(i: usize) -> usize {
+    i.wrapping_mul(0x9e37_79b9) ^ 0x5a5a
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,43 @@
+//! Minimal user-space binary that executes an illegal instruction.
+//!
+//! The trap is neither a syscall nor a page fault, so it must terminate the
+//! task, with a report naming the instruction as `_start` plus an offset
+//! when the kernel has the symbols of the app. Calls SYS_EXIT with 1 if the
+//! instruction ever returns.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::exit;
+
+/// Executes an illegal instruction past the first one of the function, so
+/// that the trap is reported at a non-zero offset.
+#[unsafe(naked)]
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    #[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+    core::arch::naked_asm!("nop", "unimp", "tail {fail}", fail = sym fail);
+
+    #[cfg(target_arch = "aarch64")]
+    core::arch::naked_asm!("nop", "udf #0", "b {fail}", fail = sym fail);
+
+    #[cfg(target_arch = "x86_64")]
+    core::arch::naked_asm!("nop", "ud2", "jmp {fail}", fail = sym fail);
+
+    // The all-zero word is a reserved instruction.
+    #[cfg(target_arch = "loongarch64")]
+    core::arch::naked_asm!("nop", ".word 0", "b {fail}", fail = sym fail);
+}
+
+extern "C" fn fail() -> ! {
+    exit(1)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,37 @@
+//! Minimal user-space binary that reads its task id from the shared info
+//! page.
+//!
+//! With feature `info-page`, the kernel passes the address of a read-only
+//! page, laid out as a [`SharedInfo`], in the first argument of the entry;
+//! the first read faults it in. The task id there must match SYS_GETPID, and
+//! the clock must have been stamped. Calls SYS_EXIT with the task id, and with
+//! 255 on a mismatch or without the page.
+
+#![no_std]
+#![no_main]
+
+#[path = "../../src/shared_info.rs"]
+mod shared_info;
+mod sys;
+
+use core::panic::PanicInfo;
+
+use shared_info::SharedInfo;
+use sys::{SYS_GETPID, exit, syscall1};
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start(info: *const SharedInfo) -> ! {
+    if info.is_null() {
+        exit(255)
+    }
+    let info = unsafe { info.read_volatile() };
+    if info.pid as usize != syscall1(SYS_GETPID, 0) || info.clock_ns == 0 {
+        exit(255)
+    }
+    exit(info.pid as usize)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(255)
+}
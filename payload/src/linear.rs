@@ -0,0 +1,54 @@
+//! Minimal user-space binary that reads 12 stack pages, one after the other
+//! downward.
+//!
+//! The pages are all in the initial stack, below the page of the stack top
+//! that holds the frame of [`start`]. Each read of an unmapped page faults it
+//! in, so with stack readahead most of them are mapped by an earlier fault.
+//! Calls SYS_EXIT with 0.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::exit;
+
+/// Passes the initial stack pointer, the stack top, to [`start`].
+#[unsafe(naked)]
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    #[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+    core::arch::naked_asm!("mv a0, sp", "tail {start}", start = sym start);
+
+    #[cfg(target_arch = "aarch64")]
+    core::arch::naked_asm!("mov x0, sp", "b {start}", start = sym start);
+
+    // The call pushes a return address, as for any function entry.
+    #[cfg(target_arch = "x86_64")]
+    core::arch::naked_asm!(
+        "mov rdi, rsp",
+        "and rsp, -16",
+        "call {start}",
+        start = sym start,
+    );
+
+    #[cfg(target_arch = "loongarch64")]
+    core::arch::naked_asm!("move $a0, $sp", "b {start}", start = sym start);
+}
+
+const PAGE_SIZE: usize = 0x1000;
+const PAGES: usize = 12;
+
+unsafe extern "C" fn start(top: *const u8) -> ! {
+    for page in 1..=PAGES {
+        unsafe { top.sub(page * PAGE_SIZE + 1).read_volatile() };
+    }
+    exit(0)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,68 @@
+//! Minimal user-space binary that maps memory at a fixed address.
+//!
+//! It maps two pages with SYS_MMAP and MAP_FIXED, checks that the mapping is
+//! placed exactly where requested, and writes to both pages. It then maps
+//! the second page again with MAP_FIXED, which must replace it with a fresh
+//! zero page, and checks that a misaligned fixed address fails with
+//! `-EINVAL`. Calls SYS_EXIT with 0 on success or 1 on any mismatch.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, PROT_READ, PROT_WRITE, SYS_MMAP, exit, syscall4};
+
+const PAGE_SIZE: usize = 0x1000;
+
+const EINVAL: usize = 22;
+
+/// Page-aligned address inside the kernel's mmap window, away from its base
+/// where unhinted mappings go.
+const FIXED_ADDR: usize = 0x10_0010_0000;
+
+const MARK: usize = 0x5a5a_5a5a;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    exit(if check_fixed() { 0 } else { 1 })
+}
+
+fn mmap_fixed(addr: usize, len: usize) -> usize {
+    syscall4(
+        SYS_MMAP,
+        addr,
+        len,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+    )
+}
+
+fn check_fixed() -> bool {
+    if mmap_fixed(FIXED_ADDR, 2 * PAGE_SIZE) != FIXED_ADDR {
+        return false;
+    }
+    let first = FIXED_ADDR as *mut usize;
+    let second = (FIXED_ADDR + PAGE_SIZE) as *mut usize;
+    unsafe {
+        first.write_volatile(MARK);
+        second.write_volatile(MARK);
+    }
+
+    // Replacing the second page drops its contents, but not the first's.
+    if mmap_fixed(FIXED_ADDR + PAGE_SIZE, PAGE_SIZE) != FIXED_ADDR + PAGE_SIZE {
+        return false;
+    }
+    if unsafe { first.read_volatile() } != MARK || unsafe { second.read_volatile() } != 0 {
+        return false;
+    }
+
+    mmap_fixed(FIXED_ADDR + 1, PAGE_SIZE) == EINVAL.wrapping_neg()
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
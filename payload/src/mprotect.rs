@@ -0,0 +1,72 @@
+//! Minimal user-space binary that changes the protection of its pages.
+//!
+//! It writes to a fresh page mapped with SYS_MMAP, makes it read-only with
+//! SYS_MPROTECT and checks that it still holds the write. It also makes a
+//! read-only page writable and writes to it, and checks that an unmapped
+//! range fails with `-ENOMEM` and a misaligned one with `-EINVAL`. The last
+//! write, to the page made read-only, must then be a permission fault that
+//! terminates the task. Calls SYS_EXIT with 1 on any mismatch, or with 2 if
+//! the last write returns.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{
+    MAP_ANONYMOUS, MAP_PRIVATE, PROT_READ, PROT_WRITE, SYS_MMAP, SYS_MPROTECT, exit, syscall4,
+};
+
+const PAGE_SIZE: usize = 0x1000;
+
+const ENOMEM: usize = 12;
+const EINVAL: usize = 22;
+
+/// Page-aligned address in no region.
+const UNMAPPED_ADDR: usize = 0xdead_0000;
+
+const MARK: usize = 0x5a5a_5a5a;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    let Some(page) = protect_pages() else { exit(1) };
+    unsafe { page.write_volatile(MARK) };
+    exit(2)
+}
+
+fn mmap(len: usize, prot: usize) -> usize {
+    syscall4(SYS_MMAP, 0, len, prot, MAP_PRIVATE | MAP_ANONYMOUS)
+}
+
+fn mprotect(addr: usize, len: usize, prot: usize) -> usize {
+    syscall4(SYS_MPROTECT, addr, len, prot, 0)
+}
+
+/// Runs the checks, and returns the page made read-only once written to.
+fn protect_pages() -> Option<*mut usize> {
+    let rw = mmap(PAGE_SIZE, PROT_READ | PROT_WRITE);
+    let ro = mmap(PAGE_SIZE, PROT_READ);
+    if !rw.is_multiple_of(PAGE_SIZE) || !ro.is_multiple_of(PAGE_SIZE) {
+        return None;
+    }
+    let (rw, ro) = (rw as *mut usize, ro as *mut usize);
+    unsafe { rw.write_volatile(MARK) };
+    if mprotect(rw as usize, PAGE_SIZE, PROT_READ) != 0 || unsafe { rw.read_volatile() } != MARK {
+        return None;
+    }
+    if mprotect(ro as usize, PAGE_SIZE, PROT_READ | PROT_WRITE) != 0 {
+        return None;
+    }
+    unsafe { ro.write_volatile(MARK) };
+    let written = unsafe { ro.read_volatile() } == MARK;
+    let unmapped = mprotect(UNMAPPED_ADDR, PAGE_SIZE, PROT_READ) == ENOMEM.wrapping_neg();
+    let misaligned = mprotect(rw as usize + 1, PAGE_SIZE, PROT_READ) == EINVAL.wrapping_neg();
+    (written && unmapped && misaligned).then_some(rw)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,30 @@
+//! Minimal user-space binary that issues an unimplemented syscall.
+//!
+//! The kernel must fail the syscall with `-ENOSYS` and resume the task, which
+//! then calls SYS_EXIT with 0, or with 1 if the syscall returned anything
+//! else.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{exit, syscall1};
+
+/// A syscall number the kernel does not implement.
+const SYS_UNKNOWN: usize = 1000;
+
+const ENOSYS: isize = 38;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    let ret = syscall1(SYS_UNKNOWN, 0) as isize;
+    exit(if ret == -ENOSYS { 0 } else { 1 })
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,56 @@
+//! Minimal user-space binary that overruns its stack downward.
+//!
+//! It reads one byte per page below the stack top, each read growing the
+//! stack by a page, until it runs past the growth limit into the guard page.
+//! The fault there must terminate the task as a stack overflow. Calls
+//! SYS_EXIT with 1 if it reads more pages than the stack may grow to.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::exit;
+
+/// Passes the initial stack pointer, the stack top, to [`start`].
+#[unsafe(naked)]
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    #[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+    core::arch::naked_asm!("mv a0, sp", "tail {start}", start = sym start);
+
+    #[cfg(target_arch = "aarch64")]
+    core::arch::naked_asm!("mov x0, sp", "b {start}", start = sym start);
+
+    // The call pushes a return address, as for any function entry.
+    #[cfg(target_arch = "x86_64")]
+    core::arch::naked_asm!(
+        "mov rdi, rsp",
+        "and rsp, -16",
+        "call {start}",
+        start = sym start,
+    );
+
+    #[cfg(target_arch = "loongarch64")]
+    core::arch::naked_asm!("move $a0, $sp", "b {start}", start = sym start);
+}
+
+const PAGE_SIZE: usize = 0x1000;
+
+/// Pages the stack may grow to in the demo kernel, plus the guard page, plus
+/// one more in case the fault did not stop the task.
+const PAGES: usize = 0x10_0000 / PAGE_SIZE + 2;
+
+unsafe extern "C" fn start(top: *const u8) -> ! {
+    for page in 1..=PAGES {
+        unsafe { top.sub(page * PAGE_SIZE).read_volatile() };
+    }
+    exit(1)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,46 @@
+//! Minimal user-space binary that writes one page past the end of its
+//! anonymous mapping.
+//!
+//! It maps `PAGES` pages with SYS_MMAP, writes a word in each of them, then
+//! writes one in the page right after the mapping, which is not part of it:
+//! the kernel terminates the task there, with a heap overrun if the mapping
+//! has a guard page. Calls SYS_EXIT with 1 if that write goes through, or if
+//! the mapping fails.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{MAP_ANONYMOUS, MAP_PRIVATE, PROT_READ, PROT_WRITE, SYS_MMAP, exit, is_err, syscall4};
+
+const PAGE_SIZE: usize = 0x1000;
+
+const PAGES: usize = 2;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    let prot = PROT_READ | PROT_WRITE;
+    let base = syscall4(
+        SYS_MMAP,
+        0,
+        PAGES * PAGE_SIZE,
+        prot,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+    );
+    if is_err(base) {
+        exit(1);
+    }
+    // The last write is the overrun.
+    for page in 0..=PAGES {
+        unsafe { ((base + page * PAGE_SIZE) as *mut usize).write_volatile(page) };
+    }
+    exit(1)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
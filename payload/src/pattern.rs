@@ -0,0 +1,71 @@
+//! Minimal user-space binary that checks that a faulted-in page is normal,
+//! cached memory, coherent with the kernel's view of the same frame.
+//!
+//! It grows the heap with SYS_BRK by one page and fills it with a pattern,
+//! each word derived from its address, ending with a line of text. It reads
+//! the pattern back, yields to the other tasks, and reads it back once more.
+//! Then it passes the line to SYS_WRITE, which the kernel reads through its
+//! own (cached) mapping of the frame: a page mapped with another memory type
+//! would show stale bytes there. Calls SYS_EXIT with 0 on success or 1 on any
+//! mismatch.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{SYS_BRK, SYS_SCHED_YIELD, SYS_WRITE, exit, syscall1, syscall4};
+
+const PAGE_SIZE: usize = 0x1000;
+
+const LINE: &[u8] = b"coherent page pattern OK\n";
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    exit(if check_pattern() { 0 } else { 1 })
+}
+
+/// Returns the word of the pattern at `addr`.
+fn pattern(addr: usize) -> usize {
+    addr.rotate_left(13) ^ 0x5a5a_5a5a
+}
+
+fn check_pattern() -> bool {
+    let base = syscall1(SYS_BRK, 0);
+    let end = base + PAGE_SIZE;
+    if syscall1(SYS_BRK, end) != end {
+        return false;
+    }
+
+    let words = base as *mut usize;
+    let len = (PAGE_SIZE - LINE.len()) / size_of::<usize>();
+    for i in 0..len {
+        let word = unsafe { words.add(i) };
+        unsafe { word.write_volatile(pattern(word as usize)) };
+    }
+    let line = (end - LINE.len()) as *mut u8;
+    for (i, &byte) in LINE.iter().enumerate() {
+        unsafe { line.add(i).write_volatile(byte) };
+    }
+    let intact = || {
+        (0..len).all(|i| {
+            let word = unsafe { words.add(i) };
+            pattern(word as usize) == unsafe { word.read_volatile() }
+        })
+    };
+    if !intact() {
+        return false;
+    }
+    syscall1(SYS_SCHED_YIELD, 0);
+    if !intact() {
+        return false;
+    }
+    syscall4(SYS_WRITE, 1, line as usize, LINE.len(), 0) == LINE.len()
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
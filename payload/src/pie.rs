@@ -0,0 +1,56 @@
+//! Minimal user-space binary that needs load-time relocations when linked as
+//! a static PIE (`cargo xtask run --pie`).
+//!
+//! A table in its `.data.rel.ro` holds the address of a function and of a
+//! string, which the linker cannot know in a PIE: the kernel patches them
+//! with `R_*_RELATIVE` relocations for the base it loads the app at. The app
+//! calls the function through the table, and writes the string with
+//! SYS_WRITE. Calls SYS_EXIT with the result of the function, 0, if it
+//! matches the address of the function taken in code, and with 1 otherwise.
+//!
+//! Linked as a regular executable, the table needs no relocation, and the
+//! app behaves the same.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{SYS_WRITE, exit, syscall4};
+
+const MESSAGE: &str = "relocated table OK\n";
+
+/// A function and a string, both by address.
+struct Table {
+    check: fn() -> usize,
+    message: &'static str,
+}
+
+static TABLE: Table = Table {
+    check,
+    message: MESSAGE,
+};
+
+fn check() -> usize {
+    0
+}
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    // Read through a volatile pointer, so that the table is not folded into
+    // the code.
+    let table = unsafe { core::ptr::read_volatile(&&TABLE) };
+    if table.check as *const () != check as *const () {
+        exit(1);
+    }
+    let msg = table.message;
+    syscall4(SYS_WRITE, 1, msg.as_ptr() as usize, msg.len(), 0);
+    exit((table.check)())
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
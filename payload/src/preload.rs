@@ -0,0 +1,52 @@
+//! Minimal user-space binary that reads a word the kernel wrote on its stack
+//! before it started.
+//!
+//! With feature `preload-stack`, the kernel writes 42 at the base of the
+//! 64K initial stack, whose top is aligned to 64K, and maps its page up
+//! front. The word is found below the initial stack pointer, which starts at
+//! the top, or just below it with arguments. Calls SYS_EXIT with the word.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::exit;
+
+/// Size of the initial stack, to which its top is aligned.
+const STACK_SIZE: usize = 0x10000;
+
+/// Passes the initial stack pointer to [`start`].
+#[unsafe(naked)]
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    #[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+    core::arch::naked_asm!("mv a0, sp", "tail {start}", start = sym start);
+
+    #[cfg(target_arch = "aarch64")]
+    core::arch::naked_asm!("mov x0, sp", "b {start}", start = sym start);
+
+    // The call pushes a return address, as for any function entry.
+    #[cfg(target_arch = "x86_64")]
+    core::arch::naked_asm!(
+        "mov rdi, rsp",
+        "and rsp, -16",
+        "call {start}",
+        start = sym start,
+    );
+
+    #[cfg(target_arch = "loongarch64")]
+    core::arch::naked_asm!("move $a0, $sp", "b {start}", start = sym start);
+}
+
+unsafe extern "C" fn start(sp: usize) -> ! {
+    let base = (sp - 1) & !(STACK_SIZE - 1);
+    exit(unsafe { (base as *const usize).read_volatile() })
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
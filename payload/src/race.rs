@@ -0,0 +1,118 @@
+//! Minimal user-space binary whose threads fault in the same lazy pages at
+//! once.
+//!
+//! It maps a data area of `PAGES` pages, a control page and a stack for each
+//! of `THREADS` threads at fixed addresses, none of them touched, then
+//! creates the threads with SYS_CLONE. Each thread takes an index from a
+//! counter of the control page, then writes a word of its own in every page
+//! of the data area, in the same order as the others and yielding after each
+//! page, so that the threads fault on the same pages in turn, and on their
+//! own stack pages meanwhile. Once every thread has counted itself done, the
+//! main thread checks that every word of every thread is in place: a page
+//! mapped twice, to two frames, would lose some of them. Calls SYS_EXIT with
+//! 0 on success or 1 if a word is missing, a thread never finishes or a
+//! syscall fails.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use sys::{
+    CLONE_THREAD, CLONE_VM, MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, PROT_READ, PROT_WRITE, SYS_MMAP,
+    SYS_SCHED_YIELD, clone, exit, is_err, syscall1, syscall4,
+};
+
+const PAGE_SIZE: usize = 0x1000;
+
+const THREADS: usize = 4;
+const PAGES: usize = 16;
+
+/// Page holding the counters of the threads, inside the kernel's mmap
+/// window: the next thread index at `CTRL_ADDR`, the number of threads done
+/// right after it.
+const CTRL_ADDR: usize = 0x10_0030_0000;
+/// Pages every thread writes to.
+const DATA_ADDR: usize = 0x10_0040_0000;
+/// Stacks of the threads, one after the other.
+const STACKS_ADDR: usize = 0x10_0060_0000;
+const STACK_SIZE: usize = 4 * PAGE_SIZE;
+
+/// Number of times the main thread yields before giving up on the threads.
+const MAX_YIELDS: usize = 10_000;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    exit(if check_race() { 0 } else { 1 })
+}
+
+/// Returns the counter `index` of the control page.
+fn counter(index: usize) -> &'static AtomicUsize {
+    unsafe { AtomicUsize::from_ptr((CTRL_ADDR as *mut usize).add(index)) }
+}
+
+/// Returns the word the thread `thread` writes to the data page `page`.
+fn mark(thread: usize, page: usize) -> usize {
+    0x5a5a_0000 | (page << 8) | thread
+}
+
+/// Returns the address of the word of the thread `thread` in the data page
+/// `page`.
+fn slot(thread: usize, page: usize) -> *mut usize {
+    (DATA_ADDR + page * PAGE_SIZE + thread * size_of::<usize>()) as *mut usize
+}
+
+fn check_race() -> bool {
+    let prot = PROT_READ | PROT_WRITE;
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED;
+    let areas = [
+        (CTRL_ADDR, PAGE_SIZE),
+        (DATA_ADDR, PAGES * PAGE_SIZE),
+        (STACKS_ADDR, THREADS * STACK_SIZE),
+    ];
+    for (addr, size) in areas {
+        if syscall4(SYS_MMAP, addr, size, prot, flags) != addr {
+            return false;
+        }
+    }
+    for i in 0..THREADS {
+        let stack_top = STACKS_ADDR + (i + 1) * STACK_SIZE;
+        let tid = clone(CLONE_VM | CLONE_THREAD, stack_top, thread_main);
+        if tid == 0 || is_err(tid) {
+            return false;
+        }
+    }
+
+    let mut finished = false;
+    for _ in 0..MAX_YIELDS {
+        if counter(1).load(Ordering::Acquire) == THREADS {
+            finished = true;
+            break;
+        }
+        syscall1(SYS_SCHED_YIELD, 0);
+    }
+    finished
+        && (0..PAGES).all(|page| {
+            (0..THREADS)
+                .all(|thread| unsafe { slot(thread, page).read_volatile() } == mark(thread, page))
+        })
+}
+
+/// Entry point of the threads, each on its own stack.
+extern "C" fn thread_main() -> ! {
+    let thread = counter(0).fetch_add(1, Ordering::Relaxed);
+    for page in 0..PAGES {
+        unsafe { slot(thread, page).write_volatile(mark(thread, page)) };
+        syscall1(SYS_SCHED_YIELD, 0);
+    }
+    counter(1).fetch_add(1, Ordering::Release);
+    exit(0)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
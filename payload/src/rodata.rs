@@ -0,0 +1,33 @@
+//! Minimal user-space binary that checks the permissions of its rodata.
+//!
+//! It reads a constant from its read-only segment, which faults the page in
+//! read-only, then writes to it. The write must be caught as a permission
+//! fault, which terminates the task with -1. The payload exits with 1 if the
+//! constant reads wrong, and with 2 if the write unexpectedly succeeds.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::exit;
+
+/// A constant placed in `.rodata`, loaded with read-only permissions.
+static RODATA: usize = 0x5a5a_5a5a;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    let ptr = core::ptr::addr_of!(RODATA).cast_mut();
+    if unsafe { ptr.read_volatile() } != 0x5a5a_5a5a {
+        exit(1);
+    }
+    unsafe { ptr.write_volatile(0) };
+    exit(2)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
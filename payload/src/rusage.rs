@@ -0,0 +1,95 @@
+//! Minimal user-space binary that counts its own page faults.
+//!
+//! It reads its minor fault count with SYS_GETRUSAGE, reads one byte in
+//! each of 5 stack pages it never touched, below the pages its own frames
+//! take, and reads the count again, after checking that a count written to
+//! `0xdead0000` fails with `-EFAULT`. Calls SYS_EXIT with the difference, 5
+//! if each page took one fault, or with 255 if a call fails.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{SYS_GETRUSAGE, exit, syscall4};
+
+const PAGE_SIZE: usize = 0x1000;
+
+/// Number of stack pages touched between the two counts.
+const PAGES: usize = 5;
+
+/// Number of pages below the stack top left to the frames of the payload.
+const FRAME_PAGES: usize = 2;
+
+const RUSAGE_SELF: usize = 0;
+
+const EFAULT: usize = -14isize as usize;
+
+/// Number of words of `struct rusage`: two `struct timeval`, then 14 `long`
+/// counters.
+const RUSAGE_WORDS: usize = 18;
+
+/// Index of `ru_minflt` among the words of `struct rusage`, followed by
+/// `ru_majflt`.
+const MINFLT: usize = 8;
+
+/// Passes the initial stack pointer, the stack top, to [`start`].
+#[unsafe(naked)]
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    #[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+    core::arch::naked_asm!("mv a0, sp", "tail {start}", start = sym start);
+
+    #[cfg(target_arch = "aarch64")]
+    core::arch::naked_asm!("mov x0, sp", "b {start}", start = sym start);
+
+    // The call pushes a return address, as for any function entry.
+    #[cfg(target_arch = "x86_64")]
+    core::arch::naked_asm!(
+        "mov rdi, rsp",
+        "and rsp, -16",
+        "call {start}",
+        start = sym start,
+    );
+
+    #[cfg(target_arch = "loongarch64")]
+    core::arch::naked_asm!("move $a0, $sp", "b {start}", start = sym start);
+}
+
+/// Returns the minor fault count of the task, or `None` if the call fails.
+fn minor_faults() -> Option<usize> {
+    let mut usage = [0usize; RUSAGE_WORDS];
+    let ret = syscall4(
+        SYS_GETRUSAGE,
+        RUSAGE_SELF,
+        usage.as_mut_ptr() as usize,
+        0,
+        0,
+    );
+    (ret == 0 && usage[MINFLT + 1] == 0).then_some(usage[MINFLT])
+}
+
+unsafe extern "C" fn start(top: usize) -> ! {
+    let Some(before) = minor_faults() else {
+        exit(255)
+    };
+    for i in 0..PAGES {
+        let page = top - (FRAME_PAGES + 1 + i) * PAGE_SIZE;
+        unsafe { (page as *const u8).read_volatile() };
+    }
+    // A bad destination must fail cleanly.
+    if syscall4(SYS_GETRUSAGE, RUSAGE_SELF, 0xdead0000, 0, 0) != EFAULT {
+        exit(255)
+    }
+    match minor_faults() {
+        Some(after) => exit(after - before),
+        None => exit(255),
+    }
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(255)
+}
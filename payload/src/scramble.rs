@@ -0,0 +1,73 @@
+//! Minimal user-space binary that writes to 8 stack pages in a scrambled
+//! order.
+//!
+//! The pages are all in the initial stack, below the page of the stack top
+//! that holds the frame of [`start`]. Counting from the page right below
+//! it, each one is written to in the order of [`ORDER`], each the first time
+//! it is touched, so that every write takes a fault of its own and the
+//! faults hit the pages out of sequence. Each page gets a word derived from
+//! its index, and once all of them are written, every word is read back.
+//! Calls SYS_EXIT with 0 if they all hold their word, and with 1 otherwise.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::exit;
+
+/// Passes the initial stack pointer, the stack top, to [`start`].
+#[unsafe(naked)]
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    #[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+    core::arch::naked_asm!("mv a0, sp", "tail {start}", start = sym start);
+
+    #[cfg(target_arch = "aarch64")]
+    core::arch::naked_asm!("mov x0, sp", "b {start}", start = sym start);
+
+    // The call pushes a return address, as for any function entry.
+    #[cfg(target_arch = "x86_64")]
+    core::arch::naked_asm!(
+        "mov rdi, rsp",
+        "and rsp, -16",
+        "call {start}",
+        start = sym start,
+    );
+
+    #[cfg(target_arch = "loongarch64")]
+    core::arch::naked_asm!("move $a0, $sp", "b {start}", start = sym start);
+}
+
+const PAGE_SIZE: usize = 0x1000;
+
+/// Indices of the pages in the order they are written to, each of the 8
+/// pages below the page of the stack top once.
+const ORDER: [usize; 8] = [5, 0, 3, 7, 1, 6, 2, 4];
+
+/// Returns the address of the word written to the page `index`, in the
+/// middle of the page, so that neither of its neighbours is touched.
+fn word(top: usize, index: usize) -> *mut usize {
+    (top - (index + 2) * PAGE_SIZE + PAGE_SIZE / 2) as *mut usize
+}
+
+/// Returns the word written to the page `index`.
+fn value(index: usize) -> usize {
+    0x5c5c_0000 + index
+}
+
+unsafe extern "C" fn start(top: usize) -> ! {
+    for index in ORDER {
+        unsafe { word(top, index).write_volatile(value(index)) };
+    }
+    let intact =
+        (0..ORDER.len()).all(|index| unsafe { word(top, index).read_volatile() } == value(index));
+    exit(if intact { 0 } else { 1 })
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
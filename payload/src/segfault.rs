@@ -0,0 +1,29 @@
+//! Minimal user-space binary that dereferences a wild pointer.
+//!
+//! The address lies far outside every region of the task, so the kernel
+//! reports a segmentation fault and terminates the task with -1 before it
+//! ever reaches SYS_EXIT.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::exit;
+
+/// Address outside the loaded segments, the stack, the heap and the mmap
+/// window.
+const WILD_ADDR: usize = 0xdead_0000;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    unsafe { (WILD_ADDR as *const usize).read_volatile() };
+    exit(0)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,55 @@
+//! Minimal user-space binary that catches its own segmentation fault.
+//!
+//! It installs a `SIGSEGV` handler with SYS_RT_SIGACTION, then dereferences
+//! a wild pointer. The kernel delivers the fault to the handler, which checks
+//! the signal number and faulting address it received and exits with 0 if
+//! they match, or 1 otherwise. Reaching the code after the faulting read, or
+//! being terminated with -1, means the signal was not delivered.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{SYS_RT_SIGACTION, exit, syscall4};
+
+const SIGSEGV: usize = 11;
+
+/// Address outside the loaded segments, the stack, the heap and the mmap
+/// window.
+const WILD_ADDR: usize = 0xdead_0000;
+
+/// Offsets of `si_signo` and `si_addr` in a `siginfo_t`.
+const SI_SIGNO: usize = 0;
+const SI_ADDR: usize = 16;
+
+extern "C" fn on_segv(signo: usize, info: *const u8, _ctx: usize) -> ! {
+    let (si_signo, si_addr) = unsafe {
+        (
+            info.add(SI_SIGNO).cast::<i32>().read(),
+            info.add(SI_ADDR).cast::<usize>().read(),
+        )
+    };
+    if signo == SIGSEGV && si_signo as usize == SIGSEGV && si_addr == WILD_ADDR {
+        exit(0)
+    }
+    exit(1)
+}
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    // struct sigaction { sa_handler, sa_flags, sa_restorer, sa_mask }
+    let act = [on_segv as *const () as usize, 0, 0, 0];
+    if syscall4(SYS_RT_SIGACTION, SIGSEGV, act.as_ptr() as usize, 0, 8) != 0 {
+        exit(1)
+    }
+    unsafe { (WILD_ADDR as *const usize).read_volatile() };
+    exit(1)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
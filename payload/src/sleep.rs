@@ -0,0 +1,68 @@
+//! Minimal user-space binary that sleeps with SYS_NANOSLEEP.
+//!
+//! It writes a mark to its stack, sleeps for 100 ms, and checks with
+//! SYS_CLOCK_GETTIME that at least that much time went by, and that the mark
+//! is still there. A zero sleep must return at once and an invalid
+//! `timespec` fail with `-EINVAL`. Calls SYS_EXIT with 0 on success or 1 on
+//! any mismatch.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{SYS_CLOCK_GETTIME, SYS_NANOSLEEP, exit, syscall4};
+
+const CLOCK_MONOTONIC: usize = 1;
+
+const EINVAL: usize = 22;
+
+const SLEEP_NANOS: usize = 100_000_000;
+
+const MARK: usize = 0x5a5a_5a5a;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    exit(if check_sleep() { 0 } else { 1 })
+}
+
+/// Sleeps for `sec` seconds and `nsec` nanoseconds, and returns the result.
+fn nanosleep(sec: usize, nsec: usize) -> usize {
+    let ts = [sec, nsec];
+    syscall4(SYS_NANOSLEEP, ts.as_ptr() as usize, 0, 0, 0)
+}
+
+/// Returns the monotonic time in nanoseconds.
+fn now() -> usize {
+    let mut ts = [0usize; 2];
+    syscall4(
+        SYS_CLOCK_GETTIME,
+        CLOCK_MONOTONIC,
+        ts.as_mut_ptr() as usize,
+        0,
+        0,
+    );
+    ts[0] * 1_000_000_000 + ts[1]
+}
+
+fn check_sleep() -> bool {
+    let mut mark = MARK;
+    let mark = &raw mut mark;
+    unsafe { mark.write_volatile(MARK) };
+
+    let start = now();
+    if nanosleep(0, SLEEP_NANOS) != 0 || now() - start < SLEEP_NANOS {
+        return false;
+    }
+    if unsafe { mark.read_volatile() } != MARK {
+        return false;
+    }
+    nanosleep(0, 0) == 0 && nanosleep(0, 1_000_000_000) == EINVAL.wrapping_neg()
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,63 @@
+//! Minimal user-space binary that spins in user space across timer ticks.
+//!
+//! It writes a mark to its stack, then busy-loops for 200 ms without
+//! yielding, checking the time with SYS_CLOCK_GETTIME every few thousand
+//! iterations only: with the `preempt` kernel feature, timer interrupts hit
+//! it in user space, and the scheduler may switch to the other tasks in
+//! between. Calls SYS_EXIT with 0 if the mark is still there once the time
+//! is up, or 1 otherwise.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{SYS_CLOCK_GETTIME, exit, syscall4};
+
+const CLOCK_MONOTONIC: usize = 1;
+
+const SPIN_NANOS: usize = 200_000_000;
+
+/// Iterations of the busy loop between two reads of the clock.
+const SPINS_PER_CHECK: usize = 10_000;
+
+const MARK: usize = 0x5a5a_5a5a;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    exit(if check_spin() { 0 } else { 1 })
+}
+
+/// Returns the monotonic time in nanoseconds.
+fn now() -> usize {
+    let mut ts = [0usize; 2];
+    syscall4(
+        SYS_CLOCK_GETTIME,
+        CLOCK_MONOTONIC,
+        ts.as_mut_ptr() as usize,
+        0,
+        0,
+    );
+    ts[0] * 1_000_000_000 + ts[1]
+}
+
+fn check_spin() -> bool {
+    let mut mark = MARK;
+    let mark = &raw mut mark;
+    unsafe { mark.write_volatile(MARK) };
+
+    let start = now();
+    while now() - start < SPIN_NANOS {
+        for _ in 0..SPINS_PER_CHECK {
+            core::hint::spin_loop();
+        }
+    }
+    MARK == unsafe { mark.read_volatile() }
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,37 @@
+//! Minimal user-space binary that touches a known amount of stack.
+//!
+//! It writes one byte in each of the top `STACK_PAGES` pages of its stack,
+//! then calls SYS_EXIT(0), so the kernel reports the lowest stack fault at
+//! `STACK_PAGES` pages below the stack top.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::exit;
+
+const PAGE_SIZE: usize = 0x1000;
+
+/// Number of stack pages touched, counting the top one.
+const STACK_PAGES: usize = 4;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    // The frame of `_start` lies in the top page of the stack, well within
+    // its first half.
+    let probe = 0u8;
+    let top = (core::ptr::addr_of!(probe) as usize | (PAGE_SIZE - 1)) + 1;
+    for i in 0..STACK_PAGES {
+        let addr = top - i * PAGE_SIZE - PAGE_SIZE / 2;
+        unsafe { (addr as *mut u8).write_volatile(0x5a) };
+    }
+    exit(0)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
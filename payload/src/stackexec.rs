@@ -0,0 +1,100 @@
+//! Minimal user-space binary that executes code it wrote itself, first from
+//! an executable mapping, then from its stack.
+//!
+//! It maps an anonymous page with SYS_MMAP, readable, writable and
+//! executable, writes a return instruction into it and calls it. It then
+//! writes the same instruction to its stack, which is never executable, and
+//! calls it there: the instruction fetch faults, and the kernel terminates
+//! the task. Calls SYS_EXIT with 1 if the stack code runs, or if the mapping
+//! fails.
+//!
+//! With a W^X flags policy in the kernel, the call into the mapping already
+//! faults, as the page was mapped writable.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{
+    MAP_ANONYMOUS, MAP_PRIVATE, PROT_EXEC, PROT_READ, PROT_WRITE, SYS_MMAP, exit, is_err, syscall4,
+};
+
+const PAGE_SIZE: usize = 0x1000;
+
+/// A return instruction (`ret`, followed by padding on x86_64).
+#[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+const RET: u32 = 0x0000_8067; // jalr zero, 0(ra)
+#[cfg(target_arch = "aarch64")]
+const RET: u32 = 0xd65f_03c0;
+#[cfg(target_arch = "x86_64")]
+const RET: u32 = 0x0000_00c3;
+#[cfg(target_arch = "loongarch64")]
+const RET: u32 = 0x4c00_0020; // jirl zero, ra, 0
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    let prot = PROT_READ | PROT_WRITE | PROT_EXEC;
+    let page = syscall4(SYS_MMAP, 0, PAGE_SIZE, prot, MAP_PRIVATE | MAP_ANONYMOUS);
+    if is_err(page) {
+        exit(1);
+    }
+    unsafe { call(page as *mut u32) };
+
+    let mut code = [0u32; 4];
+    unsafe { call(code.as_mut_ptr()) };
+    exit(1)
+}
+
+/// Writes [`RET`] at `code`, makes it visible to instruction fetches, and
+/// calls it.
+///
+/// # Safety
+///
+/// `code` must be writable.
+unsafe fn call(code: *mut u32) {
+    unsafe { code.write_volatile(RET) };
+    sync_icache(code as usize);
+    let f: extern "C" fn() = unsafe { core::mem::transmute(code) };
+    f()
+}
+
+/// Makes the instruction just written at `addr` visible to instruction
+/// fetches.
+fn sync_icache(addr: usize) {
+    #[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+    {
+        let _ = addr;
+        unsafe { core::arch::asm!("fence.i", options(nostack)) };
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        core::arch::asm!(
+            "dc cvau, {0}",
+            "dsb ish",
+            "ic ivau, {0}",
+            "dsb ish",
+            "isb",
+            in(reg) addr,
+            options(nostack),
+        )
+    };
+
+    // Instruction fetches snoop stores on x86_64.
+    #[cfg(target_arch = "x86_64")]
+    let _ = addr;
+
+    #[cfg(target_arch = "loongarch64")]
+    {
+        let _ = addr;
+        unsafe { core::arch::asm!("ibar 0", options(nostack)) };
+    }
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
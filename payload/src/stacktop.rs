@@ -0,0 +1,52 @@
+//! Minimal user-space binary that reads both sides of its stack top.
+//!
+//! The stack pointer starts at the stack top. The last byte below it is in
+//! the last page of the stack, which the first read faults in. The byte at
+//! the top is past the stack, so the second read must be a clean
+//! segmentation fault that terminates the task. Calls SYS_EXIT with 1 if it
+//! ever returns.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::exit;
+
+/// Passes the initial stack pointer, the stack top, to [`start`].
+#[unsafe(naked)]
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    #[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+    core::arch::naked_asm!("mv a0, sp", "tail {start}", start = sym start);
+
+    #[cfg(target_arch = "aarch64")]
+    core::arch::naked_asm!("mov x0, sp", "b {start}", start = sym start);
+
+    // The call pushes a return address, as for any function entry.
+    #[cfg(target_arch = "x86_64")]
+    core::arch::naked_asm!(
+        "mov rdi, rsp",
+        "and rsp, -16",
+        "call {start}",
+        start = sym start,
+    );
+
+    #[cfg(target_arch = "loongarch64")]
+    core::arch::naked_asm!("move $a0, $sp", "b {start}", start = sym start);
+}
+
+unsafe extern "C" fn start(top: *const u8) -> ! {
+    unsafe {
+        top.sub(1).read_volatile();
+        top.read_volatile();
+    }
+    exit(1)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
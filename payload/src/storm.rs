@@ -0,0 +1,39 @@
+//! Minimal user-space binary that page faults in a loop, forever.
+//!
+//! It grows the heap with SYS_BRK by one page, then writes to it and frees
+//! its frame with `MADV_DONTNEED`, over and over: every write faults the
+//! page in again. The kernel must terminate it once it runs past its fault
+//! budget. Calls SYS_EXIT with 1 if the heap cannot be grown or the page
+//! freed.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{MADV_DONTNEED, SYS_BRK, SYS_MADVISE, exit, syscall1, syscall4};
+
+const PAGE_SIZE: usize = 0x1000;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    let base = syscall1(SYS_BRK, 0);
+    let end = base + PAGE_SIZE;
+    if syscall1(SYS_BRK, end) != end {
+        exit(1);
+    }
+    let word = base as *mut usize;
+    loop {
+        unsafe { word.write_volatile(1) };
+        if syscall4(SYS_MADVISE, base, PAGE_SIZE, MADV_DONTNEED, 0) != 0 {
+            exit(1);
+        }
+    }
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,53 @@
+//! Minimal user-space binary that writes a string straddling a page
+//! boundary.
+//!
+//! The string sits across the boundary between two pages of its read-only
+//! segment. The payload reads the first page, which faults it in, and leaves
+//! the second one untouched, then hands the string to SYS_WRITE: the kernel
+//! must fault the second page in from the file to copy it out. Calls
+//! SYS_EXIT with 0 if the whole string was written, and with 1 otherwise.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{SYS_WRITE, exit, syscall4};
+
+const PAGE_SIZE: usize = 0x1000;
+
+const MESSAGE: &[u8] = b"straddling write OK\n";
+
+/// Offset of the message in [`PAGES`], so that it ends on the second page.
+const OFFSET: usize = PAGE_SIZE - 8;
+
+/// Two pages of `.rodata`, on their own, holding [`MESSAGE`] at [`OFFSET`].
+#[repr(C, align(4096))]
+struct Pages([u8; 2 * PAGE_SIZE]);
+
+static PAGES: Pages = {
+    let mut bytes = [0; 2 * PAGE_SIZE];
+    let mut i = 0;
+    while i < MESSAGE.len() {
+        bytes[OFFSET + i] = MESSAGE[i];
+        i += 1;
+    }
+    Pages(bytes)
+};
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    let msg = unsafe { core::ptr::addr_of!(PAGES.0).cast::<u8>().add(OFFSET) };
+    if unsafe { msg.read_volatile() } != MESSAGE[0] {
+        exit(1);
+    }
+    let written = syscall4(SYS_WRITE, 1, msg as usize, MESSAGE.len(), 0);
+    exit(if written == MESSAGE.len() { 0 } else { 1 })
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
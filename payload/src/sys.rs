@@ -0,0 +1,196 @@
+//! Raw syscall wrappers shared by the payload variants.
+
+// Not every payload uses every wrapper.
+#![allow(dead_code)]
+
+pub const SYS_READ: usize = 63;
+pub const SYS_WRITE: usize = 64;
+pub const SYS_FSTAT: usize = 80;
+pub const SYS_EXIT: usize = 93;
+pub const SYS_EXIT_GROUP: usize = 94;
+pub const SYS_SET_TID_ADDRESS: usize = 96;
+pub const SYS_NANOSLEEP: usize = 101;
+pub const SYS_CLOCK_GETTIME: usize = 113;
+pub const SYS_SCHED_YIELD: usize = 124;
+pub const SYS_RT_SIGACTION: usize = 134;
+pub const SYS_RT_SIGRETURN: usize = 139;
+pub const SYS_UNAME: usize = 160;
+pub const SYS_GETRUSAGE: usize = 165;
+pub const SYS_GETPID: usize = 172;
+pub const SYS_GETTID: usize = 178;
+pub const SYS_BRK: usize = 214;
+pub const SYS_MUNMAP: usize = 215;
+pub const SYS_CLONE: usize = 220;
+pub const SYS_MMAP: usize = 222;
+pub const SYS_MPROTECT: usize = 226;
+pub const SYS_MADVISE: usize = 233;
+pub const SYS_GETRANDOM: usize = 278;
+
+pub const PROT_READ: usize = 0x1;
+pub const PROT_WRITE: usize = 0x2;
+pub const PROT_EXEC: usize = 0x4;
+
+pub const MAP_PRIVATE: usize = 0x02;
+pub const MAP_FIXED: usize = 0x10;
+pub const MAP_ANONYMOUS: usize = 0x20;
+
+pub const MADV_WILLNEED: usize = 3;
+pub const MADV_DONTNEED: usize = 4;
+
+pub const CLONE_VM: usize = 0x100;
+pub const CLONE_THREAD: usize = 0x10000;
+
+/// Returns whether the syscall result `ret` is an error (`-4095..=-1`).
+pub fn is_err(ret: usize) -> bool {
+    ret >= 4095usize.wrapping_neg()
+}
+
+/// Issues syscall `num` with a single argument and returns its result.
+pub fn syscall1(num: usize, arg0: usize) -> usize {
+    let ret;
+    unsafe {
+        #[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+        core::arch::asm!("ecall", inlateout("a0") arg0 => ret, in("a7") num);
+
+        #[cfg(target_arch = "aarch64")]
+        core::arch::asm!("svc #0", inlateout("x0") arg0 => ret, in("x8") num);
+
+        #[cfg(target_arch = "x86_64")]
+        core::arch::asm!(
+            "syscall",
+            inlateout("rax") num => ret,
+            in("rdi") arg0,
+            out("rcx") _,
+            out("r11") _,
+        );
+
+        #[cfg(target_arch = "loongarch64")]
+        core::arch::asm!("syscall 0", inlateout("$a0") arg0 => ret, in("$a7") num);
+    }
+    ret
+}
+
+/// Issues syscall `num` with four arguments and returns its result.
+pub fn syscall4(num: usize, arg0: usize, arg1: usize, arg2: usize, arg3: usize) -> usize {
+    let ret;
+    unsafe {
+        #[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+        core::arch::asm!(
+            "ecall",
+            inlateout("a0") arg0 => ret,
+            in("a1") arg1,
+            in("a2") arg2,
+            in("a3") arg3,
+            in("a7") num,
+        );
+
+        #[cfg(target_arch = "aarch64")]
+        core::arch::asm!(
+            "svc #0",
+            inlateout("x0") arg0 => ret,
+            in("x1") arg1,
+            in("x2") arg2,
+            in("x3") arg3,
+            in("x8") num,
+        );
+
+        #[cfg(target_arch = "x86_64")]
+        core::arch::asm!(
+            "syscall",
+            inlateout("rax") num => ret,
+            in("rdi") arg0,
+            in("rsi") arg1,
+            in("rdx") arg2,
+            in("r10") arg3,
+            out("rcx") _,
+            out("r11") _,
+        );
+
+        #[cfg(target_arch = "loongarch64")]
+        core::arch::asm!(
+            "syscall 0",
+            inlateout("$a0") arg0 => ret,
+            in("$a1") arg1,
+            in("$a2") arg2,
+            in("$a3") arg3,
+            in("$a7") num,
+        );
+    }
+    ret
+}
+
+/// Creates a thread with the SYS_CLONE `flags`, running `entry` on the stack
+/// whose top is `stack`, and returns its thread id (or a negative errno).
+///
+/// The thread resumes from the syscall like the caller, so it must leave the
+/// frame of the caller, which lives on the other stack, right away: it calls
+/// `entry` straight from the syscall return.
+pub fn clone(flags: usize, stack: usize, entry: extern "C" fn() -> !) -> usize {
+    let ret;
+    unsafe {
+        #[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+        core::arch::asm!(
+            "ecall",
+            "bnez a0, 2f",
+            "jalr {entry}",
+            "2:",
+            entry = in(reg) entry,
+            inlateout("a0") flags => ret,
+            in("a1") stack,
+            in("a7") SYS_CLONE,
+        );
+
+        #[cfg(target_arch = "aarch64")]
+        core::arch::asm!(
+            "svc #0",
+            "cbnz x0, 2f",
+            "blr {entry}",
+            "2:",
+            entry = in(reg) entry,
+            inlateout("x0") flags => ret,
+            in("x1") stack,
+            in("x8") SYS_CLONE,
+        );
+
+        // `call` leaves the stack 8 mod 16 on entry, as the ABI requires.
+        #[cfg(target_arch = "x86_64")]
+        core::arch::asm!(
+            "syscall",
+            "test rax, rax",
+            "jnz 2f",
+            "call {entry}",
+            "2:",
+            entry = in(reg) entry,
+            inlateout("rax") SYS_CLONE => ret,
+            in("rdi") flags,
+            in("rsi") stack,
+            out("rcx") _,
+            out("r11") _,
+        );
+
+        #[cfg(target_arch = "loongarch64")]
+        core::arch::asm!(
+            "syscall 0",
+            "bnez $a0, 2f",
+            "jirl $ra, {entry}, 0",
+            "2:",
+            entry = in(reg) entry,
+            inlateout("$a0") flags => ret,
+            in("$a1") stack,
+            in("$a7") SYS_CLONE,
+        );
+    }
+    ret
+}
+
+/// Terminates the task with exit code `code`.
+pub fn exit(code: usize) -> ! {
+    syscall1(SYS_EXIT, code);
+    loop {}
+}
+
+/// Terminates every thread of the task with exit code `code`.
+pub fn exit_group(code: usize) -> ! {
+    syscall1(SYS_EXIT_GROUP, code);
+    loop {}
+}
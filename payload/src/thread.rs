@@ -0,0 +1,111 @@
+//! Minimal user-space binary that creates a thread sharing its address
+//! space.
+//!
+//! It maps a data page and a stack for the thread at fixed addresses, then
+//! creates the thread with SYS_CLONE. The thread sets a word of the data page
+//! as its tid address with SYS_SET_TID_ADDRESS, stores its tid there, checks
+//! that SYS_GETPID gives the pid of the main thread, then writes a mark to
+//! the page, faulting it in, and exits. The main thread
+//! waits for the mark to show up in its own view of the page, then for the
+//! kernel to clear the tid word on the exit of the thread, yielding
+//! meanwhile. Calls SYS_EXIT with 0 on success or 1 if the mark never shows
+//! up, the tid word is never cleared or a syscall fails.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{
+    CLONE_THREAD, CLONE_VM, MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, PROT_READ, PROT_WRITE,
+    SYS_GETPID, SYS_GETTID, SYS_MMAP, SYS_SCHED_YIELD, SYS_SET_TID_ADDRESS, clone, exit, is_err,
+    syscall1, syscall4,
+};
+
+const PAGE_SIZE: usize = 0x1000;
+
+const EINVAL: usize = 22;
+
+/// Page written by the thread and read by the main thread, inside the
+/// kernel's mmap window.
+const DATA_ADDR: usize = 0x10_0020_0000;
+/// Stack of the thread, right above the data page.
+const STACK_ADDR: usize = DATA_ADDR + PAGE_SIZE;
+const STACK_SIZE: usize = 4 * PAGE_SIZE;
+
+/// Tid word of the thread, cleared by the kernel when it exits.
+const TID_ADDR: usize = DATA_ADDR + 8;
+/// Pid of the main thread, which the thread must get from SYS_GETPID too.
+const PID_ADDR: usize = DATA_ADDR + 16;
+
+const MARK: usize = 0x5a5a_5a5a;
+
+/// Number of times the main thread yields before giving up on the mark.
+const MAX_YIELDS: usize = 1000;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    exit(if check_thread() { 0 } else { 1 })
+}
+
+fn check_thread() -> bool {
+    let prot = PROT_READ | PROT_WRITE;
+    let flags = MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED;
+    if syscall4(SYS_MMAP, DATA_ADDR, PAGE_SIZE, prot, flags) != DATA_ADDR
+        || syscall4(SYS_MMAP, STACK_ADDR, STACK_SIZE, prot, flags) != STACK_ADDR
+    {
+        return false;
+    }
+    let pid = syscall1(SYS_GETPID, 0);
+    if pid != syscall1(SYS_GETTID, 0) {
+        return false;
+    }
+    unsafe { (PID_ADDR as *mut usize).write_volatile(pid) };
+    let stack_top = STACK_ADDR + STACK_SIZE;
+    // Only threads can be created.
+    if clone(CLONE_VM, stack_top, thread_main) != EINVAL.wrapping_neg() {
+        return false;
+    }
+    let tid = clone(CLONE_VM | CLONE_THREAD, stack_top, thread_main);
+    if tid == 0 || is_err(tid) {
+        return false;
+    }
+
+    let data = DATA_ADDR as *const usize;
+    let tid_word = TID_ADDR as *const u32;
+    wait_for(|| unsafe { data.read_volatile() } == MARK)
+        && wait_for(|| unsafe { tid_word.read_volatile() } == 0)
+}
+
+/// Yields until `done` returns true, and returns false if it never does.
+fn wait_for(done: impl Fn() -> bool) -> bool {
+    for _ in 0..MAX_YIELDS {
+        if done() {
+            return true;
+        }
+        syscall1(SYS_SCHED_YIELD, 0);
+    }
+    false
+}
+
+/// Entry point of the thread, on its own stack.
+extern "C" fn thread_main() -> ! {
+    let tid = syscall1(SYS_SET_TID_ADDRESS, TID_ADDR);
+    if tid != syscall1(SYS_GETTID, 0)
+        || syscall1(SYS_GETPID, 0) != unsafe { (PID_ADDR as *const usize).read_volatile() }
+    {
+        exit(1);
+    }
+    unsafe {
+        (TID_ADDR as *mut u32).write_volatile(tid as u32);
+        (DATA_ADDR as *mut usize).write_volatile(MARK);
+    }
+    exit(0)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,72 @@
+//! Minimal user-space binary that identifies the system with SYS_UNAME.
+//!
+//! It grows the heap with SYS_BRK by one page, which it does not touch, and
+//! has SYS_UNAME write its `struct utsname` there: the kernel faults the page
+//! in on the copy. It then writes `<sysname> <machine>` to the console with
+//! SYS_WRITE, and calls SYS_EXIT with 0 if the machine is the architecture
+//! it was built for, and with 1 otherwise.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{SYS_BRK, SYS_UNAME, SYS_WRITE, exit, syscall1, syscall4};
+
+/// Size of each field of `struct utsname`, its null terminator included.
+const FIELD_LEN: usize = 65;
+
+/// Index of the `machine` field of `struct utsname`.
+const MACHINE: usize = 4;
+
+#[cfg(target_arch = "riscv64")]
+const ARCH: &[u8] = b"riscv64";
+#[cfg(target_arch = "aarch64")]
+const ARCH: &[u8] = b"aarch64";
+#[cfg(target_arch = "x86_64")]
+const ARCH: &[u8] = b"x86_64";
+#[cfg(target_arch = "loongarch64")]
+const ARCH: &[u8] = b"loongarch64";
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    exit(if check_uname() { 0 } else { 1 })
+}
+
+/// Returns the field `index` of the `struct utsname` at `uts`, up to its null
+/// terminator.
+fn field(uts: usize, index: usize) -> &'static [u8] {
+    let bytes =
+        unsafe { core::slice::from_raw_parts((uts + index * FIELD_LEN) as *const u8, FIELD_LEN) };
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(FIELD_LEN);
+    &bytes[..len]
+}
+
+fn check_uname() -> bool {
+    let uts = syscall1(SYS_BRK, 0);
+    let end = uts + 0x1000;
+    if syscall1(SYS_BRK, end) != end {
+        return false;
+    }
+    // The page is faulted in by the kernel, on the copy to user space.
+    if syscall1(SYS_UNAME, uts) != 0 {
+        return false;
+    }
+    // The line is laid out right after the structure, in the same page.
+    let line = uts + 6 * FIELD_LEN;
+    let mut len = 0;
+    for part in [field(uts, 0), b" ", field(uts, MACHINE), b"\n"] {
+        for &byte in part {
+            unsafe { ((line + len) as *mut u8).write_volatile(byte) };
+            len += 1;
+        }
+    }
+    syscall4(SYS_WRITE, 1, line, len, 0) == len && field(uts, MACHINE) == ARCH
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
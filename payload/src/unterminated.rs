@@ -0,0 +1,28 @@
+//! Minimal user-space binary that writes a message with no trailing newline
+//! and exits at once.
+//!
+//! With feature `buffered-write`, the kernel holds the message until the
+//! task exits, and must flush it then. Calls SYS_EXIT with 0 once the whole
+//! message was accepted by SYS_WRITE, and with 1 otherwise.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{SYS_WRITE, exit, syscall4};
+
+const MESSAGE: &[u8] = b"unterminated message done";
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    let written = syscall4(SYS_WRITE, 1, MESSAGE.as_ptr() as usize, MESSAGE.len(), 0);
+    exit(if written == MESSAGE.len() { 0 } else { 1 })
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,84 @@
+//! Minimal user-space binary that faults in a mapping up front with
+//! MADV_WILLNEED.
+//!
+//! It maps `PAGES` anonymous pages with SYS_MMAP, advises them with
+//! MADV_WILLNEED, then reads and writes a word in each of them between two
+//! reads of its minor fault count with SYS_GETRUSAGE. Calls SYS_EXIT with
+//! the difference, 0 if the advice faulted every page in writable, or with
+//! 255 if a call fails.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{
+    MADV_WILLNEED, MAP_ANONYMOUS, MAP_PRIVATE, PROT_READ, PROT_WRITE, SYS_GETRUSAGE, SYS_MADVISE,
+    SYS_MMAP, exit, is_err, syscall4,
+};
+
+const PAGE_SIZE: usize = 0x1000;
+
+const RUSAGE_SELF: usize = 0;
+
+/// Number of words of `struct rusage`: two `struct timeval`, then 14 `long`
+/// counters.
+const RUSAGE_WORDS: usize = 18;
+
+/// Index of `ru_minflt` among the words of `struct rusage`.
+const MINFLT: usize = 8;
+
+/// Number of pages of the mapping.
+const PAGES: usize = 8;
+
+/// Returns the minor fault count of the task, or `None` if the call fails.
+fn minor_faults() -> Option<usize> {
+    let mut usage = [0usize; RUSAGE_WORDS];
+    let ret = syscall4(
+        SYS_GETRUSAGE,
+        RUSAGE_SELF,
+        usage.as_mut_ptr() as usize,
+        0,
+        0,
+    );
+    (ret == 0).then_some(usage[MINFLT])
+}
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    let base = syscall4(
+        SYS_MMAP,
+        0,
+        PAGES * PAGE_SIZE,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+    );
+    if is_err(base) || syscall4(SYS_MADVISE, base, PAGES * PAGE_SIZE, MADV_WILLNEED, 0) != 0 {
+        exit(255);
+    }
+    // Both counts are read from the same stack depth, so the stack takes no
+    // fault in between.
+    let Some(before) = minor_faults() else {
+        exit(255)
+    };
+    for page in 0..PAGES {
+        let word = (base + page * PAGE_SIZE) as *mut usize;
+        unsafe {
+            if word.read_volatile() != 0 {
+                exit(255);
+            }
+            word.write_volatile(page + 1);
+        }
+    }
+    match minor_faults() {
+        Some(after) => exit(after - before),
+        None => exit(255),
+    }
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(255)
+}
@@ -0,0 +1,46 @@
+//! Minimal user-space binary that checks that a freed frame does not leak
+//! into the next page faulted in.
+//!
+//! It grows the heap with SYS_BRK by one page, fills it with a pattern, and
+//! frees its frame with `MADV_DONTNEED`. The next access faults the page in
+//! again, most likely from the frame just freed, and must read zeros. Calls
+//! SYS_EXIT with 0 on success or 1 on any mismatch.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{MADV_DONTNEED, SYS_BRK, SYS_MADVISE, exit, syscall1, syscall4};
+
+const PAGE_SIZE: usize = 0x1000;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    exit(if check_zeroed() { 0 } else { 1 })
+}
+
+fn check_zeroed() -> bool {
+    let base = syscall1(SYS_BRK, 0);
+    let end = base + PAGE_SIZE;
+    if syscall1(SYS_BRK, end) != end {
+        return false;
+    }
+
+    let words = base as *mut usize;
+    let len = PAGE_SIZE / size_of::<usize>();
+    for i in 0..len {
+        unsafe { words.add(i).write_volatile(usize::MAX) };
+    }
+    if syscall4(SYS_MADVISE, base, PAGE_SIZE, MADV_DONTNEED, 0) != 0 {
+        return false;
+    }
+    (0..len).all(|i| unsafe { words.add(i).read_volatile() } == 0)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
@@ -0,0 +1,55 @@
+//! Minimal user-space binary that reads many fresh anonymous pages.
+//!
+//! It maps 100 pages with SYS_MMAP and reads a word of each, which must be
+//! zero. It then writes to one of the pages, which must hold the write while
+//! the others still read as zeros. With the shared zero page, the reads
+//! allocate no frame, and the write a single one. Calls SYS_EXIT with 0 on
+//! success or 1 on any mismatch.
+
+#![no_std]
+#![no_main]
+
+mod sys;
+
+use core::panic::PanicInfo;
+
+use sys::{
+    MAP_ANONYMOUS, MAP_PRIVATE, PROT_READ, PROT_WRITE, SYS_MMAP, SYS_MUNMAP, exit, syscall4,
+};
+
+const PAGE_SIZE: usize = 0x1000;
+const PAGES: usize = 100;
+
+const MARK: usize = 0x5a5a_5a5a;
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn _start() -> ! {
+    exit(if check_zero_page() { 0 } else { 1 })
+}
+
+fn check_zero_page() -> bool {
+    let len = PAGES * PAGE_SIZE;
+    let base = syscall4(
+        SYS_MMAP,
+        0,
+        len,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+    );
+    if base.is_multiple_of(PAGE_SIZE) {
+        let word = |page: usize| (base + page * PAGE_SIZE) as *mut usize;
+        let is_zero = |page: usize| unsafe { word(page).read_volatile() } == 0;
+        let zeroed = (0..PAGES).all(is_zero);
+        unsafe { word(1).write_volatile(MARK) };
+        let written = unsafe { word(1).read_volatile() } == MARK;
+        let others = is_zero(0) && is_zero(2) && is_zero(PAGES - 1);
+        zeroed && written && others && syscall4(SYS_MUNMAP, base, len, 0, 0) == 0
+    } else {
+        false
+    }
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    exit(1)
+}
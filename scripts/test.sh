@@ -90,12 +90,597 @@ run_arch_tests() {
         fi
         
         # Build and run
-        if cargo xtask run --arch="$arch" 2>&1 | grep -q "handle page fault OK!"; then
+        output=$(cargo xtask run --arch="$arch" 2>&1) || true
+        if echo "$output" | grep -q "mapped [1-9][0-9]* pages via" \
+            && echo "$output" | grep -q "userboot-0.*: user exit code 0" \
+            && echo "$output" | grep -q "all user frames freed"; then
             echo "✓ $arch test passed"
         else
             echo "Error: $arch test failed"
             exit 1
         fi
+
+        # The wild access of the segfault payload must terminate the tasks
+        # with -1, without the wild page ever being mapped.
+        output=$(cargo xtask run --arch="$arch" --payload segfault 2>&1) || true
+        if echo "$output" | grep -q "segmentation fault at VA:0xdead0000" \
+            && echo "$output" | grep -q "userboot-0 exit \[-1\]" \
+            && echo "$output" | grep -q "all user frames freed" \
+            && ! echo "$output" | grep -q "user exit code" \
+            && ! echo "$output" | grep -q "handle page fault OK! addr=VA:0xdead0000"; then
+            echo "✓ $arch segfault test passed"
+        else
+            echo "Error: $arch segfault test failed"
+            exit 1
+        fi
+
+        # The write of the rodata payload to its read-only segment must be
+        # caught as a permission fault.
+        output=$(cargo xtask run --arch="$arch" --payload rodata 2>&1) || true
+        if echo "$output" | grep -q "write to read-only page at VA:0x2000" \
+            && echo "$output" | grep -q "userboot-0 exit \[-1\]"; then
+            echo "✓ $arch rodata test passed"
+        else
+            echo "Error: $arch rodata test failed"
+            exit 1
+        fi
+
+        # An unknown syscall fails with -ENOSYS, and the task carries on.
+        output=$(cargo xtask run --arch="$arch" --payload nosys 2>&1) || true
+        if echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "monolithic kernel exit normally!"; then
+            echo "✓ $arch nosys test passed"
+        else
+            echo "Error: $arch nosys test failed"
+            exit 1
+        fi
+
+        # A caught segmentation fault runs the user handler instead of
+        # terminating the task.
+        output=$(cargo xtask run --arch="$arch" --payload sigsegv 2>&1) || true
+        if echo "$output" | grep -q "segmentation fault at VA:0xdead0000 .*SIGSEGV delivered" \
+            && echo "$output" | grep -q "userboot-0 exit \[0\]"; then
+            echo "✓ $arch sigsegv test passed"
+        else
+            echo "Error: $arch sigsegv test failed"
+            exit 1
+        fi
+
+        # The stack watermark is the lowest of the 4 stack pages touched,
+        # below the stack top at 0x4000000000.
+        output=$(cargo xtask run --arch="$arch" --payload stack 2>&1) || true
+        if echo "$output" | grep -q "lowest stack fault at VA:0x3fffffc000" \
+            && echo "$output" | grep -q "userboot-0 exit \[0\]"; then
+            echo "✓ $arch stack test passed"
+        else
+            echo "Error: $arch stack test failed"
+            exit 1
+        fi
+
+        # Each pre-allocated stack page is mapped to the frame of its index,
+        # and no frame backs two indices.
+        output=$(cargo xtask run --arch="$arch" --payload stack --features prealloc-stack,verbose_faults 2>&1) || true
+        mappings=$(echo "$output" | grep -o "page=VA:0x3fffff[c-f]000 -> pa=[^ ]* (idx [0-9]*)" | sort -u)
+        if [ "$(echo "$mappings" | grep -c "(idx 1[2-5])")" -ge 4 ] \
+            && [ -z "$(echo "$mappings" | awk '{print $3}' | sort | uniq -d)" ] \
+            && echo "$output" | grep -q "userboot-0 exit \[0\]"; then
+            echo "✓ $arch stack frames test passed"
+        else
+            echo "Error: $arch stack frames test failed"
+            exit 1
+        fi
+
+        # MAP_FIXED places mappings exactly where requested.
+        output=$(cargo xtask run --arch="$arch" --payload mmapfixed 2>&1) || true
+        if echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch mmapfixed test passed"
+        else
+            echo "Error: $arch mmapfixed test failed"
+            exit 1
+        fi
+
+        # A page faulted in by a thread is visible to the other.
+        output=$(cargo xtask run --arch="$arch" --payload thread 2>&1) || true
+        if echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "userboot-0-thread.*: mapped" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch thread test passed"
+        else
+            echo "Error: $arch thread test failed"
+            exit 1
+        fi
+
+        # The kernel faults in the lazy page it writes the random bytes to.
+        output=$(cargo xtask run --arch="$arch" --payload getrandom 2>&1) || true
+        if echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch getrandom test passed"
+        else
+            echo "Error: $arch getrandom test failed"
+            exit 1
+        fi
+
+        # A frame freed with MADV_DONTNEED is faulted in again as zeros.
+        output=$(cargo xtask run --arch="$arch" --payload zeroed 2>&1) || true
+        if echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch zeroed test passed"
+        else
+            echo "Error: $arch zeroed test failed"
+            exit 1
+        fi
+
+        # The two arguments pushed on the stack are read back by the payload.
+        output=$(cargo xtask run --arch="$arch" --payload args --features user-args 2>&1) || true
+        if echo "$output" | grep -q "userboot-0 exit \[2\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch args test passed"
+        else
+            echo "Error: $arch args test failed"
+            exit 1
+        fi
+
+        # Both tasks sleep, and each still finds its pages mapped on wakeup.
+        output=$(cargo xtask run --arch="$arch" --payload sleep 2>&1) || true
+        if echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "userboot-1 exit \[0\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch sleep test passed"
+        else
+            echo "Error: $arch sleep test failed"
+            exit 1
+        fi
+
+        # The last pre-allocated stack page is mapped, and the stack top is
+        # a clean segfault instead of an out-of-range page index.
+        output=$(cargo xtask run --arch="$arch" --payload stacktop --features prealloc-stack 2>&1) || true
+        if echo "$output" | grep -q "segmentation fault at VA:0x4000000000" \
+            && echo "$output" | grep -q "userboot-0 exit \[-1\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch stacktop test passed"
+        else
+            echo "Error: $arch stacktop test failed"
+            exit 1
+        fi
+
+        # Bad syscall arguments fail with an errno, and the kernel survives.
+        output=$(cargo xtask run --arch="$arch" --payload adversarial 2>&1) || true
+        if echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch adversarial test passed"
+        else
+            echo "Error: $arch adversarial test failed"
+            exit 1
+        fi
+
+        # With stack readahead, a linear stack scan takes fewer faults than
+        # the pages it maps.
+        output=$(cargo xtask run --arch="$arch" --payload linear --features stack-readahead 2>&1) || true
+        counts=$(echo "$output" | sed -n 's/.*userboot-0.*: mapped \([0-9]*\) pages via \([0-9]*\) faults.*/\1 \2/p' | head -1)
+        if echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && [ -n "$counts" ] && [ "${counts% *}" -gt "${counts#* }" ] \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch linear test passed"
+        else
+            echo "Error: $arch linear test failed"
+            exit 1
+        fi
+
+        # Stack pages written out of order each take one fault, and keep
+        # what was written to them.
+        output=$(cargo xtask run --arch="$arch" --payload scramble --features verbose_faults 2>&1) || true
+        pages=$(echo "$output" | grep -o "page=VA:0x3fffff[7-9a-e]000" | sort)
+        if echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "userboot-1 exit \[0\]" \
+            && [ "$(echo "$pages" | wc -l)" -eq 16 ] \
+            && [ "$(echo "$pages" | uniq | wc -l)" -eq 8 ]; then
+            echo "✓ $arch scramble test passed"
+        else
+            echo "Error: $arch scramble test failed"
+            exit 1
+        fi
+
+        # Code written to the stack never runs; with the W^X policy, code
+        # written to a writable and executable mapping does not either.
+        output=$(cargo xtask run --arch="$arch" --payload stackexec 2>&1) || true
+        if echo "$output" | grep -q "instruction fetch from non-executable page at VA:0x3fffff" \
+            && echo "$output" | grep -q "userboot-0 exit \[-1\]" \
+            && echo "$output" | grep -q "userboot-1 exit \[-1\]"; then
+            echo "✓ $arch stack exec test passed"
+        else
+            echo "Error: $arch stack exec test failed"
+            exit 1
+        fi
+        output=$(cargo xtask run --arch="$arch" --payload stackexec --features wx-policy 2>&1) || true
+        if echo "$output" | grep -q "instruction fetch from non-executable page at VA:0x1000000000" \
+            && echo "$output" | grep -q "userboot-0 exit \[-1\]" \
+            && echo "$output" | grep -q "userboot-1 exit \[-1\]"; then
+            echo "✓ $arch W^X policy test passed"
+        else
+            echo "Error: $arch W^X policy test failed"
+            exit 1
+        fi
+
+        # A write past a mapping is a plain segfault, or, with a guard page
+        # after the mapping, a heap overrun.
+        output=$(cargo xtask run --arch="$arch" --payload overrun 2>&1) || true
+        if echo "$output" | grep -q "segmentation fault at VA:0x1000002000" \
+            && echo "$output" | grep -q "userboot-0 exit \[-1\]" \
+            && echo "$output" | grep -q "userboot-1 exit \[-1\]"; then
+            echo "✓ $arch overrun test passed"
+        else
+            echo "Error: $arch overrun test failed"
+            exit 1
+        fi
+        output=$(cargo xtask run --arch="$arch" --payload overrun --features heap-guard 2>&1) || true
+        if echo "$output" | grep -q "heap overrun at VA:0x1000002000, past \[VA:0x1000000000, VA:0x1000002000)" \
+            && echo "$output" | grep -q "userboot-0 exit \[-4\]" \
+            && echo "$output" | grep -q "userboot-1 exit \[-4\]"; then
+            echo "✓ $arch heap guard test passed"
+        else
+            echo "Error: $arch heap guard test failed"
+            exit 1
+        fi
+
+        # The guard of the heap follows the program break.
+        output=$(cargo xtask run --arch="$arch" --payload brkoverrun --features heap-guard 2>&1) || true
+        if echo "$output" | grep -q "heap overrun at VA:0x10003000, past the break \[VA:0x10000000, VA:0x10002008)" \
+            && echo "$output" | grep -q "userboot-0 exit \[-4\]" \
+            && echo "$output" | grep -q "userboot-1 exit \[-4\]"; then
+            echo "✓ $arch heap break guard test passed"
+        else
+            echo "Error: $arch heap break guard test failed"
+            exit 1
+        fi
+
+        # The first stack fault maps the 16 pages of the initial stack, and
+        # leaves no other stack fault.
+        output=$(cargo xtask run --arch="$arch" --payload linear --features stack-first-touch 2>&1) || true
+        counts=$(echo "$output" | sed -n 's/.*userboot-0.*: mapped \([0-9]*\) pages via \([0-9]*\) faults.*/\1 \2/p' | head -1)
+        if echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && [ -n "$counts" ] && [ $((${counts% *} - ${counts#* })) -eq 15 ] \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch linear first-touch test passed"
+        else
+            echo "Error: $arch linear first-touch test failed"
+            exit 1
+        fi
+
+        # Reads of fresh anonymous pages all map the shared zero page.
+        output=$(cargo xtask run --arch="$arch" --payload zeropage --features zero-page 2>&1) || true
+        if echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "userboot-0.*: 100 pages mapped to the zero page" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch zeropage test passed"
+        else
+            echo "Error: $arch zeropage test failed"
+            exit 1
+        fi
+
+        # A page made read-only with SYS_MPROTECT takes writes no more.
+        output=$(cargo xtask run --arch="$arch" --payload mprotect 2>&1) || true
+        if echo "$output" | grep -q "write to read-only page at VA:0x1000000000" \
+            && echo "$output" | grep -q "userboot-0 exit \[-1\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch mprotect test passed"
+        else
+            echo "Error: $arch mprotect test failed"
+            exit 1
+        fi
+
+        # FP registers survive a page fault and a switch to another task.
+        output=$(cargo xtask run --arch="$arch" --payload fp --features fp-simd 2>&1) || true
+        if echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch fp test passed"
+        else
+            echo "Error: $arch fp test failed"
+            exit 1
+        fi
+
+        # A task faulting in a loop is stopped by its fault budget.
+        output=$(cargo xtask run --arch="$arch" --payload storm 2>&1) || true
+        if echo "$output" | grep -q "fault storm: 4097 page faults" \
+            && echo "$output" | grep -q "userboot-0 exit \[-2\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch storm test passed"
+        else
+            echo "Error: $arch storm test failed"
+            exit 1
+        fi
+
+        # The kernel faults in the untouched page of a buffer it copies out.
+        output=$(cargo xtask run --arch="$arch" --payload straddle 2>&1) || true
+        if [ "$(echo "$output" | grep -c "straddling write OK")" -eq 2 ] \
+            && echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch straddle test passed"
+        else
+            echo "Error: $arch straddle test failed"
+            exit 1
+        fi
+
+        # An unexpected trap is reported with the function it stopped in.
+        output=$(cargo xtask run --arch="$arch" --payload illegal --features user-symbols 2>&1) || true
+        if echo "$output" | grep -q "Unexpected trap from user space: .* at pc 0x[0-9a-f]* (_start+0x" \
+            && echo "$output" | grep -q "userboot-0 exit \[-1\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch illegal test passed"
+        else
+            echo "Error: $arch illegal test failed"
+            exit 1
+        fi
+
+        # Running the stack into its guard page is a stack overflow.
+        output=$(cargo xtask run --arch="$arch" --payload overflow 2>&1) || true
+        if echo "$output" | grep -q "stack overflow at VA:0x3fffeff000" \
+            && echo "$output" | grep -q "userboot-0 exit \[-3\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch overflow test passed"
+        else
+            echo "Error: $arch overflow test failed"
+            exit 1
+        fi
+
+        # User pages are normal, cached memory, coherent with the kernel's
+        # view of their frames (see the memory attributes of aarch64).
+        output=$(cargo xtask run --arch="$arch" --payload pattern 2>&1) || true
+        if [ "$(echo "$output" | grep -c "coherent page pattern OK")" -eq 2 ] \
+            && echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch pattern test passed"
+        else
+            echo "Error: $arch pattern test failed"
+            exit 1
+        fi
+
+        # SYS_UNAME names the architecture the kernel was built for.
+        output=$(cargo xtask run --arch="$arch" --payload uname 2>&1) || true
+        if [ "$(echo "$output" | grep -c "ArceOS $arch")" -eq 2 ] \
+            && echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch uname test passed"
+        else
+            echo "Error: $arch uname test failed"
+            exit 1
+        fi
+
+        # SYS_FSTAT reports stdout as a character device.
+        output=$(cargo xtask run --arch="$arch" --payload fstat 2>&1) || true
+        if echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "userboot-1 exit \[0\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch fstat test passed"
+        else
+            echo "Error: $arch fstat test failed"
+            exit 1
+        fi
+
+        # A static PIE, compiled into the kernel, is relocated at 0x400000.
+        output=$(cargo xtask run --arch="$arch" --payload pie --pie --features embed-payload 2>&1) || true
+        if [ "$(echo "$output" | grep -c "relocated table OK")" -eq 2 ] \
+            && echo "$output" | grep -q "Loaded app embedded (.*), entry 0x401000" \
+            && echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch pie test passed"
+        else
+            echo "Error: $arch pie test failed"
+            exit 1
+        fi
+
+        # Timer interrupts in user space resume the task: both spin to the end.
+        output=$(cargo xtask run --arch="$arch" --payload spin --features preempt 2>&1) || true
+        ticks=$(echo "$output" | grep -o "interrupted [0-9]* times" | awk '$2 >= 2' | wc -l)
+        if [ "$ticks" -eq 2 ] \
+            && echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "userboot-1 exit \[0\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch spin test passed"
+        else
+            echo "Error: $arch spin test failed"
+            exit 1
+        fi
+
+        # Exiting the thread group takes down a thread spinning in user space.
+        output=$(cargo xtask run --arch="$arch" --payload exitgroup --features preempt 2>&1) || true
+        if echo "$output" | grep -q "userboot-0 exit \[7\]" \
+            && echo "$output" | grep -q "userboot-1 exit \[7\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch exit group test passed"
+        else
+            echo "Error: $arch exit group test failed"
+            exit 1
+        fi
+
+        # Only the low 8 bits of the SYS_EXIT code make up the exit status.
+        output=$(cargo xtask run --arch="$arch" --payload exitcode 2>&1) || true
+        if echo "$output" | grep -q "userboot-0 exit \[66\]" \
+            && echo "$output" | grep -q "userboot-1 exit \[66\]" \
+            && echo "$output" | grep -q "user exit code 66"; then
+            echo "✓ $arch exitcode test passed"
+        else
+            echo "Error: $arch exitcode test failed"
+            exit 1
+        fi
+
+        # The first write to the watched top stack page names its pc.
+        output=$(cargo xtask run --arch="$arch" --features track-dirty 2>&1) || true
+        if [ "$(echo "$output" | grep -c "first write to watched page at VA:0x3ffffff[0-9a-f]*, pc 0x1")" -eq 2 ] \
+            && echo "$output" | grep -q "dirty stack pages: \[0\]" \
+            && echo "$output" | grep -q "userboot-0 exit \[0\]"; then
+            echo "✓ $arch write watch test passed"
+        else
+            echo "Error: $arch write watch test failed"
+            exit 1
+        fi
+
+        # The word written at the stack base before entry is read back, and
+        # its page takes no fault.
+        output=$(cargo xtask run --arch="$arch" --payload preload --features preload-stack,verbose_faults 2>&1) || true
+        if echo "$output" | grep -q "userboot-0 exit \[42\]" \
+            && echo "$output" | grep -q "userboot-1 exit \[42\]" \
+            && ! echo "$output" | grep -q "page=VA:0x3fffff0000"; then
+            echo "✓ $arch preload test passed"
+        else
+            echo "Error: $arch preload test failed"
+            exit 1
+        fi
+
+        # A line piped to the serial console is read into a lazy heap page
+        # and echoed back, by each task.
+        output=$(printf 'lazy echo\nlazy echo\n' | cargo xtask run --arch="$arch" --payload echo 2>&1) || true
+        if [ "$(echo "$output" | grep -c "> lazy echo")" -eq 2 ] \
+            && echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "userboot-1 exit \[0\]"; then
+            echo "✓ $arch echo test passed"
+        else
+            echo "Error: $arch echo test failed"
+            exit 1
+        fi
+
+        # Each task exits with the task id read from its shared info page.
+        output=$(cargo xtask run --arch="$arch" --payload info --features info-page 2>&1) || true
+        if [ "$(echo "$output" | grep -cE 'Task\(([0-9]+), "userboot-[01]"\): user exit code \1$')" -eq 2 ]; then
+            echo "✓ $arch info page test passed"
+        else
+            echo "Error: $arch info page test failed"
+            exit 1
+        fi
+
+        # A message with no newline is flushed when the task exits.
+        output=$(cargo xtask run --arch="$arch" --payload unterminated --features buffered-write 2>&1) || true
+        if [ "$(echo "$output" | grep -c "unterminated message done")" -eq 2 ] \
+            && echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "userboot-1 exit \[0\]"; then
+            echo "✓ $arch unterminated write test passed"
+        else
+            echo "Error: $arch unterminated write test failed"
+            exit 1
+        fi
+
+        # The kernel-wide fault count is the sum of the per-task counts.
+        output=$(cargo xtask run --arch="$arch" --payload heap 2>&1) || true
+        sum=$(echo "$output" | grep -o "exit \[0\], page faults: PageFaultStats { faults: [0-9]*" \
+            | awk '{ sum += $NF } END { print sum + 0 }')
+        if [ "$sum" -gt 0 ] \
+            && echo "$output" | grep -q "handled $sum faults across 2 tasks"; then
+            echo "✓ $arch global stats test passed"
+        else
+            echo "Error: $arch global stats test failed"
+            exit 1
+        fi
+
+        # Randomized stack bases differ between the two tasks, which both
+        # fault their stack in and exit cleanly.
+        output=$(cargo xtask run --arch="$arch" --features stack-aslr 2>&1) || true
+        if [ "$(echo "$output" | grep "Mapping user stack" | sort -u | wc -l)" -eq 2 ] \
+            && echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "userboot-1 exit \[0\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch stack aslr test passed"
+        else
+            echo "Error: $arch stack aslr test failed"
+            exit 1
+        fi
+
+        # The fault count a task reads with getrusage grows by one for each of
+        # the 5 stack pages it touches.
+        output=$(cargo xtask run --arch="$arch" --payload rusage 2>&1) || true
+        if echo "$output" | grep -q "userboot-0 exit \[5\]" \
+            && echo "$output" | grep -q "userboot-1 exit \[5\]"; then
+            echo "✓ $arch rusage test passed"
+        else
+            echo "Error: $arch rusage test failed"
+            exit 1
+        fi
+
+        # Pages advised with MADV_WILLNEED take no fault once touched, not
+        # even on the first write with zero pages.
+        for features in "" zero-page; do
+            output=$(cargo xtask run --arch="$arch" --payload willneed --features "$features" 2>&1) || true
+            if echo "$output" | grep -q "userboot-0 exit \[0\]" \
+                && echo "$output" | grep -q "userboot-1 exit \[0\]" \
+                && echo "$output" | grep -q "all user frames freed"; then
+                echo "✓ $arch willneed test passed${features:+ with $features}"
+            else
+                echo "Error: $arch willneed test failed${features:+ with $features}"
+                exit 1
+            fi
+        done
+
+        # Threads faulting in the same pages at once all see the same frames,
+        # with or without preemption.
+        for features in "" preempt; do
+            output=$(cargo xtask run --arch="$arch" --payload race --features "$features" 2>&1) || true
+            if echo "$output" | grep -q "userboot-0 exit \[0\]" \
+                && echo "$output" | grep -q "userboot-1 exit \[0\]" \
+                && [ "$(echo "$output" | grep -c "userboot-0-thread.*: mapped")" -eq 4 ] \
+                && echo "$output" | grep -q "all user frames freed"; then
+                echo "✓ $arch race test passed${features:+ with $features}"
+            else
+                echo "Error: $arch race test failed${features:+ with $features}"
+                exit 1
+            fi
+        done
+
+        # The kernel stack canaries survive the syscalls and signal delivery
+        # of a task.
+        output=$(cargo xtask run --arch="$arch" --payload sigsegv --features kstack-guard 2>&1) || true
+        if echo "$output" | grep -q "userboot-0 exit \[0\]" \
+            && echo "$output" | grep -q "userboot-1 exit \[0\]" \
+            && ! echo "$output" | grep -q "kernel stack overflow"; then
+            echo "✓ $arch kernel stack guard test passed"
+        else
+            echo "Error: $arch kernel stack guard test failed"
+            exit 1
+        fi
+
+        # Single-stepping logs each user instruction and still runs the
+        # tasks to completion, where the architecture supports it.
+        if [ "$arch" = x86_64 ] || [ "$arch" = aarch64 ]; then
+            output=$(cargo xtask run --arch="$arch" --features single_step 2>&1) || true
+            if echo "$output" | grep -q "userboot-0\"): step at pc 0x[0-9a-f]*: \[" \
+                && echo "$output" | grep -q "userboot-0 exit \[0\]" \
+                && echo "$output" | grep -q "userboot-1 exit \[0\]"; then
+                echo "✓ $arch single step test passed"
+            else
+                echo "Error: $arch single step test failed"
+                exit 1
+            fi
+        fi
+
+        # The regions of an exited task can be inspected: 2 heap pages in.
+        output=$(cargo xtask run --arch="$arch" --payload heap --features debug_faults 2>&1) || true
+        if echo "$output" | grep -A3 "\[VA:0x10000000, VA:0x11000000) .* on-demand" \
+                | grep -q "2 of 4096 pages faulted in" \
+            && echo "$output" | grep -q "userboot-0 exit \[0\]"; then
+            echo "✓ $arch region inspection test passed"
+        else
+            echo "Error: $arch region inspection test failed"
+            exit 1
+        fi
+
+        # A single blocking call runs the app, and frees all of its frames.
+        output=$(cargo xtask run --arch="$arch" --features run-payload 2>&1) || true
+        if echo "$output" | grep -q "run_user_payload: exit \[0\]" \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch run_user_payload test passed"
+        else
+            echo "Error: $arch run_user_payload test failed"
+            exit 1
+        fi
+
+        # The page arithmetic of the fault handler holds for every arch,
+        # checked once at boot however many tasks are spawned.
+        output=$(cargo xtask run --arch="$arch" --features fault_selftest 2>&1) || true
+        if [ "$(echo "$output" | grep -c "fault self-test passed")" -eq 1 ] \
+            && echo "$output" | grep -q "all user frames freed"; then
+            echo "✓ $arch fault self-test passed"
+        else
+            echo "Error: $arch fault self-test failed"
+            exit 1
+        fi
     done
     
     if [ "$qemu_ok" = true ]; then
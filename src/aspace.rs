@@ -0,0 +1,198 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use axerrno::AxError;
+use axhal::mem::PAGE_SIZE_4K;
+use axhal::paging::MappingFlags;
+use memory_addr::{MemoryAddr, VirtAddr, VirtAddrRange};
+
+use crate::mm::{LazyRegion, LazyRegions};
+use crate::task::{ExitCodes, SpawnError, StackLayout, UserApp};
+
+/// Errors in the layout of an [`AddrSpaceBuilder`].
+#[allow(dead_code)] // Only the self-test builds an app with a layout.
+#[derive(Debug)]
+pub enum LayoutError {
+    /// A bound of the region starting at the given address is not
+    /// page-aligned.
+    Misaligned(VirtAddr),
+    /// The region starting at the given address is empty, or not inside the
+    /// address space.
+    OutOfRange(VirtAddr),
+    /// The region starting at the given address overlaps another region.
+    Overlap(VirtAddr),
+    /// No stack was given.
+    NoStack,
+    /// The entry point is not inside an executable region.
+    BadEntry(usize),
+    /// The stack layout is invalid.
+    Stack(SpawnError),
+    /// The address space cannot be created, or a region registered in it.
+    Setup(AxError),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Misaligned(start) => write!(f, "region at {:#x} is not page-aligned", start),
+            Self::OutOfRange(start) => {
+                write!(f, "region at {:#x} is outside the address space", start)
+            }
+            Self::Overlap(start) => write!(f, "region at {:#x} overlaps another region", start),
+            Self::NoStack => write!(f, "no user stack"),
+            Self::BadEntry(entry) => write!(f, "entry {:#x} is not in an executable region", entry),
+            Self::Stack(e) => write!(f, "invalid user stack: {}", e),
+            Self::Setup(e) => write!(f, "cannot set up the address space: {:?}", e),
+        }
+    }
+}
+
+/// Builder of a [`UserApp`] in a new address space spanning
+/// `[base, base + size)`.
+///
+/// The regions of the app (its code and data), its stack and its heap window
+/// are given up front, and checked together by [`build`](Self::build): each
+/// must be page-aligned and inside the address space, and none may overlap
+/// another, nor the `SYS_MMAP` window.
+///
+/// By default the heap window is
+/// [`USER_HEAP_BASE`](crate::USER_HEAP_BASE) and the entry point
+/// [`APP_ENTRY`](crate::APP_ENTRY).
+#[allow(dead_code)] // The demo loads its app with `loader` instead.
+pub struct AddrSpaceBuilder {
+    range: VirtAddrRange,
+    regions: Vec<LazyRegion>,
+    stack: Option<StackLayout>,
+    heap: VirtAddrRange,
+    entry: usize,
+}
+
+#[allow(dead_code)] // The demo loads its app with `loader` instead.
+impl AddrSpaceBuilder {
+    /// Starts the layout of an address space spanning `[base, base + size)`.
+    pub fn new(base: VirtAddr, size: usize) -> Self {
+        Self {
+            range: VirtAddrRange::from_start_size(base, size),
+            regions: Vec::new(),
+            stack: None,
+            heap: VirtAddrRange::from_start_size(
+                crate::USER_HEAP_BASE.into(),
+                crate::USER_HEAP_SIZE,
+            ),
+            entry: crate::APP_ENTRY,
+        }
+    }
+
+    /// Adds `region`, such as the code or data of the app. Its pages are
+    /// faulted in from its backend on first touch.
+    pub fn with_region(mut self, region: LazyRegion) -> Self {
+        self.regions.push(region);
+        self
+    }
+
+    /// Sets the layout of the user stack.
+    pub fn with_stack(mut self, layout: StackLayout) -> Self {
+        self.stack = Some(layout);
+        self
+    }
+
+    /// Sets the window the program break moves in to `[base, base + size)`.
+    pub fn with_heap(mut self, base: VirtAddr, size: usize) -> Self {
+        self.heap = VirtAddrRange::from_start_size(base, size);
+        self
+    }
+
+    /// Sets the user entry point.
+    pub fn with_entry(mut self, entry: usize) -> Self {
+        self.entry = entry;
+        self
+    }
+
+    /// Checks the layout, creates the address space and registers the regions
+    /// and the stack in it, without mapping any page.
+    ///
+    /// The stack takes the range it may grow to, with its guard pages if any,
+    /// so no region may sit right below it, nor right above its top. The app
+    /// gets no fault nor exit hook, no fault budget, and the default
+    /// [`ExitCodes`].
+    pub fn build(self) -> Result<UserApp, LayoutError> {
+        let stack = self
+            .stack
+            .ok_or(LayoutError::NoStack)?
+            .build()
+            .map_err(LayoutError::Stack)?;
+        let guard = if stack.guard_page { PAGE_SIZE_4K } else { 0 };
+        // The guard page above the top only exists inside the address space.
+        let high = stack
+            .top
+            .checked_add(guard)
+            .filter(|&high| high <= self.range.end)
+            .unwrap_or(stack.top);
+        let stack_range = stack
+            .max_size
+            .checked_add(guard)
+            .and_then(|size| stack.top.checked_sub(size))
+            .and_then(|low| VirtAddrRange::try_new(low, high))
+            .ok_or(LayoutError::OutOfRange(stack.bottom))?;
+
+        let mmap =
+            VirtAddrRange::from_start_size(crate::USER_MMAP_BASE.into(), crate::USER_MMAP_SIZE);
+        let mut taken = Vec::from([mmap]);
+        let ranges = self.regions.iter().map(|r| (r.start, r.guard_end()));
+        for (start, end) in ranges.chain([
+            (stack_range.start, stack_range.end),
+            (self.heap.start, self.heap.end),
+        ]) {
+            if !start.is_aligned_4k() || !end.is_aligned_4k() {
+                return Err(LayoutError::Misaligned(start));
+            }
+            let range = VirtAddrRange::try_new(start, end)
+                .filter(|range| !range.is_empty() && self.range.contains_range(*range))
+                .ok_or(LayoutError::OutOfRange(start))?;
+            if taken.iter().any(|other| other.overlaps(range)) {
+                return Err(LayoutError::Overlap(start));
+            }
+            taken.push(range);
+        }
+        let executable = self
+            .regions
+            .iter()
+            .any(|r| r.contains(self.entry.into()) && r.flags.contains(MappingFlags::EXECUTE));
+        if !executable {
+            return Err(LayoutError::BadEntry(self.entry));
+        }
+
+        let mut uspace = axmm::new_user_aspace(self.range.start, self.range.size())
+            .map_err(LayoutError::Setup)?;
+        let mut regions = LazyRegions::new();
+        for region in self.regions {
+            region
+                .backend
+                .register(
+                    &mut uspace,
+                    region.start,
+                    region.end - region.start,
+                    region.flags,
+                )
+                .map_err(LayoutError::Setup)?;
+            regions.insert(region);
+        }
+        stack.register(&mut uspace).map_err(LayoutError::Setup)?;
+        Ok(UserApp {
+            uspace,
+            regions,
+            entry: self.entry,
+            stack,
+            heap: self.heap,
+            on_fault: None,
+            on_exit: None,
+            exit_codes: ExitCodes::default(),
+            max_faults: None,
+            symbols: None,
+            info_page: None,
+            flags_policy: None,
+            single_step: false,
+            heap_guard: false,
+        })
+    }
+}
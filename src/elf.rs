@@ -0,0 +1,315 @@
+use alloc::vec::Vec;
+
+use axerrno::{AxError, AxResult};
+use axhal::paging::MappingFlags;
+
+const ELF_MAGIC: [u8; 4] = *b"\x7fELF";
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+
+#[cfg(target_arch = "x86_64")]
+const EM_CURRENT: u16 = 62; // EM_X86_64
+#[cfg(target_arch = "aarch64")]
+const EM_CURRENT: u16 = 183; // EM_AARCH64
+#[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+const EM_CURRENT: u16 = 243; // EM_RISCV
+#[cfg(target_arch = "loongarch64")]
+const EM_CURRENT: u16 = 258; // EM_LOONGARCH
+
+#[cfg(target_arch = "x86_64")]
+const R_RELATIVE: u32 = 8; // R_X86_64_RELATIVE
+#[cfg(target_arch = "aarch64")]
+const R_RELATIVE: u32 = 1027; // R_AARCH64_RELATIVE
+#[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+const R_RELATIVE: u32 = 3; // R_RISCV_RELATIVE
+#[cfg(target_arch = "loongarch64")]
+const R_RELATIVE: u32 = 3; // R_LARCH_RELATIVE
+const R_NONE: u32 = 0;
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+const SHDR_SIZE: usize = 64;
+const SYM_SIZE: usize = 24;
+const DYN_SIZE: usize = 16;
+const RELA_SIZE: usize = 24;
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+
+const DT_NULL: u64 = 0;
+const DT_RELA: u64 = 7;
+const DT_RELASZ: u64 = 8;
+const DT_RELAENT: u64 = 9;
+const DT_REL: u64 = 17;
+const DT_RELRSZ: u64 = 35;
+
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+const SHT_SYMTAB: u32 = 2;
+const STT_FUNC: u8 = 2;
+const SHN_UNDEF: u16 = 0;
+
+/// A loadable segment (`PT_LOAD` program header) of an ELF file.
+pub struct Segment {
+    /// Virtual address of the first byte of the segment.
+    pub vaddr: usize,
+    /// Size of the segment in memory. The bytes past `file_size` are zero.
+    pub mem_size: usize,
+    /// Offset of the segment contents in the file.
+    pub file_offset: usize,
+    /// Number of bytes of the segment stored in the file.
+    pub file_size: usize,
+    /// User permissions of the segment.
+    pub flags: MappingFlags,
+}
+
+/// A relative relocation of a position-independent ELF file: the word at
+/// `vaddr` must hold the load base plus `addend`.
+pub struct Relocation {
+    /// Address of the word to patch, relative to the load base.
+    pub vaddr: usize,
+    /// Address the word points to, relative to the load base.
+    pub addend: usize,
+}
+
+/// A function symbol of an ELF file.
+pub struct Symbol<'a> {
+    /// Name of the function, as found in the file (Rust names are mangled).
+    pub name: &'a str,
+    /// Address of the first instruction of the function.
+    pub addr: usize,
+    /// Size of the function in bytes, or 0 if unknown.
+    pub size: usize,
+}
+
+/// A parsed ELF executable.
+pub struct ElfFile<'a> {
+    data: &'a [u8],
+    pie: bool,
+    entry: usize,
+    phoff: usize,
+    phnum: usize,
+}
+
+/// Returns whether `data` starts with the ELF magic number.
+pub fn is_elf(data: &[u8]) -> bool {
+    data.starts_with(&ELF_MAGIC)
+}
+
+impl<'a> ElfFile<'a> {
+    /// Parses the ELF header of `data`.
+    ///
+    /// Returns [`AxError::InvalidData`] if `data` is not a little-endian ELF64
+    /// executable (`ET_EXEC`) or position-independent executable (`ET_DYN`)
+    /// for the current architecture, or if its program header table is
+    /// truncated.
+    pub fn parse(data: &'a [u8]) -> AxResult<Self> {
+        if data.len() < EHDR_SIZE
+            || !is_elf(data)
+            || data[4] != ELFCLASS64
+            || data[5] != ELFDATA2LSB
+            || !matches!(read_u16(data, 16), ET_EXEC | ET_DYN)
+            || read_u16(data, 18) != EM_CURRENT
+            || read_u16(data, 54) as usize != PHDR_SIZE
+        {
+            return Err(AxError::InvalidData);
+        }
+        let phoff = read_u64(data, 32) as usize;
+        let phnum = read_u16(data, 56) as usize;
+        let phend = phnum
+            .checked_mul(PHDR_SIZE)
+            .and_then(|size| phoff.checked_add(size))
+            .ok_or(AxError::InvalidData)?;
+        if phend > data.len() {
+            return Err(AxError::InvalidData);
+        }
+        Ok(Self {
+            data,
+            pie: read_u16(data, 16) == ET_DYN,
+            entry: read_u64(data, 24) as usize,
+            phoff,
+            phnum,
+        })
+    }
+
+    /// Returns the entry point of the executable, relative to the load base
+    /// if it is position-independent.
+    pub const fn entry(&self) -> usize {
+        self.entry
+    }
+
+    /// Returns whether the executable is position-independent (`ET_DYN`): its
+    /// addresses are all relative to the base it is loaded at, and its
+    /// [`relocations`](Self::relocations) must be applied for that base.
+    pub const fn is_pie(&self) -> bool {
+        self.pie
+    }
+
+    /// Returns the loadable segments, in program header order.
+    ///
+    /// A segment whose contents lie outside the file, or whose file size
+    /// exceeds its memory size, is reported as [`AxError::InvalidData`].
+    pub fn segments(&self) -> impl Iterator<Item = AxResult<Segment>> + '_ {
+        (0..self.phnum)
+            .map(|i| &self.data[self.phoff + i * PHDR_SIZE..][..PHDR_SIZE])
+            .filter(|ph| read_u32(ph, 0) == PT_LOAD)
+            .map(|ph| {
+                let segment = Segment {
+                    vaddr: read_u64(ph, 16) as usize,
+                    mem_size: read_u64(ph, 40) as usize,
+                    file_offset: read_u64(ph, 8) as usize,
+                    file_size: read_u64(ph, 32) as usize,
+                    flags: segment_flags(read_u32(ph, 4)),
+                };
+                let file_end = segment.file_offset.checked_add(segment.file_size);
+                if file_end.is_none_or(|end| end > self.data.len())
+                    || segment.file_size > segment.mem_size
+                    || segment.vaddr.checked_add(segment.mem_size).is_none()
+                {
+                    return Err(AxError::InvalidData);
+                }
+                Ok(segment)
+            })
+    }
+
+    /// Returns the offset in the file of the `len` bytes at `vaddr`, if they
+    /// are all in the file contents of a single loadable segment.
+    pub fn file_offset(&self, vaddr: usize, len: usize) -> Option<usize> {
+        let end = vaddr.checked_add(len)?;
+        self.segments().flatten().find_map(|segment| {
+            let offset = vaddr.checked_sub(segment.vaddr)?;
+            (end <= segment.vaddr + segment.file_size).then(|| segment.file_offset + offset)
+        })
+    }
+
+    /// Returns the relocations to apply when loading the executable, from the
+    /// `DT_RELA` table of its dynamic section.
+    ///
+    /// Only the relative relocations of a static PIE are supported (and
+    /// `R_*_NONE` ones, skipped): a file without a dynamic section has none.
+    /// Returns [`AxError::Unsupported`] if there is any other kind of
+    /// relocation, or a `DT_REL` or `DT_RELR` table, and
+    /// [`AxError::InvalidData`] if a table lies outside the file.
+    pub fn relocations(&self) -> AxResult<Vec<Relocation>> {
+        let dynamic = (0..self.phnum)
+            .map(|i| &self.data[self.phoff + i * PHDR_SIZE..][..PHDR_SIZE])
+            .find(|ph| read_u32(ph, 0) == PT_DYNAMIC);
+        let Some(ph) = dynamic else {
+            return Ok(Vec::new());
+        };
+        let offset = read_u64(ph, 8) as usize;
+        let size = read_u64(ph, 32) as usize;
+        let entries = offset
+            .checked_add(size)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or(AxError::InvalidData)?;
+        let (mut rela, mut rela_size, mut rela_ent) = (0, 0, RELA_SIZE);
+        for entry in entries.chunks_exact(DYN_SIZE) {
+            let value = read_u64(entry, 8) as usize;
+            match read_u64(entry, 0) {
+                DT_NULL => break,
+                DT_RELA => rela = value,
+                DT_RELASZ => rela_size = value,
+                DT_RELAENT => rela_ent = value,
+                DT_REL | DT_RELRSZ if value != 0 => return Err(AxError::Unsupported),
+                _ => {}
+            }
+        }
+        if rela_size == 0 {
+            return Ok(Vec::new());
+        }
+        if rela_ent != RELA_SIZE {
+            return Err(AxError::Unsupported);
+        }
+        let table = self
+            .file_offset(rela, rela_size)
+            .map(|offset| &self.data[offset..offset + rela_size])
+            .ok_or(AxError::InvalidData)?;
+        let mut relocations = Vec::with_capacity(rela_size / RELA_SIZE);
+        for rela in table.chunks_exact(RELA_SIZE) {
+            match read_u64(rela, 8) as u32 {
+                R_NONE => {}
+                R_RELATIVE => relocations.push(Relocation {
+                    vaddr: read_u64(rela, 0) as usize,
+                    addend: read_u64(rela, 16) as usize,
+                }),
+                _ => return Err(AxError::Unsupported),
+            }
+        }
+        Ok(relocations)
+    }
+
+    /// Returns the defined function symbols of the symbol table (`.symtab`),
+    /// in table order.
+    ///
+    /// A file without a symbol table, such as a stripped one, has none. The
+    /// section headers are only read here, so a section table, symbol table
+    /// or name outside the file is skipped rather than an error.
+    pub fn function_symbols(&self) -> impl Iterator<Item = Symbol<'a>> + 'a {
+        let data = self.data;
+        let shoff = read_u64(data, 40) as usize;
+        let shnum = read_u16(data, 60) as usize;
+        // The type, linked section and contents of the `i`-th section.
+        let section = move |i: usize| {
+            let header = data
+                .get(shoff.checked_add(i.checked_mul(SHDR_SIZE)?)?..)?
+                .get(..SHDR_SIZE)?;
+            let offset = read_u64(header, 24) as usize;
+            let size = read_u64(header, 32) as usize;
+            let contents = data.get(offset..offset.checked_add(size)?)?;
+            Some((read_u32(header, 4), read_u32(header, 40) as usize, contents))
+        };
+        let tables = (read_u16(data, 58) as usize == SHDR_SIZE)
+            .then(|| {
+                (0..shnum).find_map(|i| match section(i)? {
+                    (SHT_SYMTAB, link, symtab) => Some((symtab, section(link)?.2)),
+                    _ => None,
+                })
+            })
+            .flatten();
+        tables.into_iter().flat_map(|(symtab, strtab)| {
+            symtab
+                .chunks_exact(SYM_SIZE)
+                .filter(|sym| sym[4] & 0xf == STT_FUNC && read_u16(sym, 6) != SHN_UNDEF)
+                .filter_map(move |sym| {
+                    let name = strtab.get(read_u32(sym, 0) as usize..)?;
+                    let name = &name[..name.iter().position(|&b| b == 0)?];
+                    Some(Symbol {
+                        name: core::str::from_utf8(name).ok()?,
+                        addr: read_u64(sym, 8) as usize,
+                        size: read_u64(sym, 16) as usize,
+                    })
+                })
+        })
+    }
+}
+
+fn segment_flags(p_flags: u32) -> MappingFlags {
+    let mut flags = MappingFlags::USER;
+    if p_flags & PF_R != 0 {
+        flags |= MappingFlags::READ;
+    }
+    if p_flags & PF_W != 0 {
+        flags |= MappingFlags::WRITE;
+    }
+    if p_flags & PF_X != 0 {
+        flags |= MappingFlags::EXECUTE;
+    }
+    flags
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
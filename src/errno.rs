@@ -0,0 +1,43 @@
+//! Linux errno values, as syscalls return them to user space.
+//!
+//! A failed syscall returns `-errno` in the result register, which [`err`]
+//! encodes. The values are those of the generic Linux ABI, shared by every
+//! architecture the kernel runs on.
+
+// The table is kept whole, not only the errnos returned so far.
+#![allow(dead_code)]
+
+/// Operation not permitted.
+pub const EPERM: i32 = 1;
+/// No such file or directory.
+pub const ENOENT: i32 = 2;
+/// No such process.
+pub const ESRCH: i32 = 3;
+/// Interrupted system call.
+pub const EINTR: i32 = 4;
+/// Bad file descriptor.
+pub const EBADF: i32 = 9;
+/// Resource temporarily unavailable.
+pub const EAGAIN: i32 = 11;
+/// Out of memory, or address range not mapped.
+pub const ENOMEM: i32 = 12;
+/// Permission denied.
+pub const EACCES: i32 = 13;
+/// Bad user address.
+pub const EFAULT: i32 = 14;
+/// Device or resource busy.
+pub const EBUSY: i32 = 16;
+/// File exists.
+pub const EEXIST: i32 = 17;
+/// Invalid argument.
+pub const EINVAL: i32 = 22;
+/// Result out of range.
+pub const ERANGE: i32 = 34;
+/// Function not implemented.
+pub const ENOSYS: i32 = 38;
+
+/// Encodes the errno `e` as the value of the result register of a failed
+/// syscall: `-e`, sign-extended to the full register width.
+pub const fn err(e: i32) -> usize {
+    -(e as isize) as usize
+}
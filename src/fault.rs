@@ -0,0 +1,504 @@
+use alloc::collections::BTreeMap;
+
+use axerrno::{AxError, AxResult};
+use axhal::mem::{PAGE_SIZE_4K, phys_to_virt};
+use axhal::paging::MappingFlags;
+use axhal::trap::PageFaultFlags;
+use axmm::AddrSpace;
+use memory_addr::{MemoryAddr, VirtAddr};
+
+use crate::mm::{
+    DirtyPages, FrameSource, GlobalFrames, LazyBackend, LazyRegion, PageProvider, PhysFrame,
+    SharedPages, UserMemory, is_normal_memory, zero_frame,
+};
+use crate::task::SpawnError;
+
+#[cfg(feature = "fault_selftest")]
+mod selftest;
+
+#[cfg(feature = "fault_selftest")]
+pub use selftest::self_test;
+
+/// What the fault handler did about a user page fault.
+pub enum FaultOutcome {
+    /// The faulting page is now mapped, and the user can be resumed.
+    Mapped,
+    /// The faulting page was already mapped with the needed permissions (a
+    /// spurious fault, or one already handled), and the user can be resumed.
+    AlreadyMapped,
+    /// The faulting page was mapped without `WRITE` to catch the first write
+    /// to it, and is now writable and dirty. The user can be resumed.
+    Dirtied,
+    /// No page can be mapped at the faulting address. Carries the error that
+    /// prevented the mapping, if the address belongs to a region.
+    Segfault(Option<SpawnError>),
+    /// The access is not allowed by the permissions of the region. Carries a
+    /// description of the denied access.
+    Permission(&'static str),
+}
+
+/// Handles a fault at `vaddr`, caused by an access of type `access_flags`, in
+/// the region of `mem` containing it.
+///
+/// A fault that hits no region is a segfault, and so is one past the program
+/// break in a guarded heap (see
+/// [`ProgramBreak::is_overrun`](crate::mm::ProgramBreak::is_overrun)). With a
+/// flags policy, the page is mapped with the flags the policy gives for it
+/// (see [`FlagsPolicy`](crate::mm::FlagsPolicy)). A fault in the
+/// dirty-tracked stack, if any, is handled by [`handle_dirty_fault`].
+/// On-demand pages are mapped to frames of the global allocator (see
+/// [`GlobalFrames`]).
+pub fn handle_fault(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    vaddr: VirtAddr,
+    access_flags: PageFaultFlags,
+) -> FaultOutcome {
+    let Some(region) = mem
+        .regions
+        .find(vaddr)
+        .filter(|_| !mem.brk.is_overrun(vaddr))
+    else {
+        return FaultOutcome::Segfault(None);
+    };
+    let restricted;
+    let region = match &mem.flags_policy {
+        Some(policy) => {
+            let flags = policy(region, access_flags);
+            if !is_normal_memory(flags) || !region.flags.contains(flags) {
+                return FaultOutcome::Segfault(None);
+            }
+            restricted = region.with_flags(flags);
+            &restricted
+        }
+        None => region,
+    };
+    match mem.dirty.as_mut().filter(|dirty| dirty.contains(vaddr)) {
+        Some(dirty) => {
+            handle_dirty_fault(uspace, vaddr, access_flags, region, &mut mem.frames, dirty)
+        }
+        None => handle_region_fault(
+            uspace,
+            vaddr,
+            access_flags,
+            region,
+            &mut mem.frames,
+            &GlobalFrames,
+            mem.cow,
+        ),
+    }
+}
+
+/// Handles a fault at `vaddr` in `region`, whose dirty pages are tracked in
+/// `dirty`.
+///
+/// A page faulted in by a read is mapped without `WRITE`, even if `region`
+/// allows it. The first write to the page then faults again, and only adds
+/// `WRITE` to its mapping. A page written to, on either fault, is marked
+/// dirty.
+///
+/// The pages of `region` must be private 4K pages: neither huge, nor shared
+/// copy-on-write.
+fn handle_dirty_fault(
+    uspace: &mut AddrSpace,
+    vaddr: VirtAddr,
+    access_flags: PageFaultFlags,
+    region: &LazyRegion,
+    frames: &mut BTreeMap<VirtAddr, PhysFrame>,
+    dirty: &mut DirtyPages,
+) -> FaultOutcome {
+    if let Some(outcome) = check_access(vaddr, access_flags, region) {
+        return outcome;
+    }
+    let page = vaddr.align_down_4k();
+    let write = access_flags.contains(PageFaultFlags::WRITE);
+    let protect =
+        |uspace: &mut AddrSpace, flags, outcome| match uspace.protect(page, PAGE_SIZE_4K, flags) {
+            Ok(()) => outcome,
+            Err(_) => FaultOutcome::Segfault(Some(SpawnError::MapFailed(page))),
+        };
+    let outcome = match uspace.page_table().query(vaddr) {
+        Ok((_, flags, _)) if flags.contains(access_flags) => FaultOutcome::AlreadyMapped,
+        // Mapped read-only by an earlier read: this is the first write.
+        Ok(_) if write => protect(uspace, region.flags, FaultOutcome::Dirtied),
+        _ => match handle_region_fault(
+            uspace,
+            vaddr,
+            access_flags,
+            region,
+            frames,
+            &GlobalFrames,
+            false,
+        ) {
+            FaultOutcome::Mapped if !write => {
+                let read_only = region.flags - MappingFlags::WRITE;
+                protect(uspace, read_only, FaultOutcome::Mapped)
+            }
+            outcome => outcome,
+        },
+    };
+    if write && matches!(outcome, FaultOutcome::Mapped | FaultOutcome::Dirtied) {
+        dirty.mark(vaddr);
+    }
+    outcome
+}
+
+/// Handles a fault at `vaddr`, caused by an access of type `access_flags`, in
+/// `region`.
+///
+/// The access is checked against the region flags. If the page is already
+/// mapped with the needed permissions, there is nothing left to do; otherwise
+/// the faulting page is mapped by the region backend: from the pre-allocated
+/// pages (see [`handle_stack_fault`]), with a fresh zeroed frame by
+/// `AddrSpace`'s handler, with the shared zero frame or a fresh zeroed frame
+/// (see [`map_zero_page`]), with a fresh frame filled from the file (see
+/// [`map_file_page`]), or with the frame of a custom provider (see
+/// [`map_provided_page`]). A fault outside `region` is a segfault.
+///
+/// `frames` keeps the frames the handler allocates itself, by the page they
+/// are mapped at, and an on-demand page is mapped to a zeroed frame of
+/// `frame_source`. If `cow` is set, pre-allocated pages are shared
+/// copy-on-write.
+pub fn handle_region_fault(
+    uspace: &mut AddrSpace,
+    vaddr: VirtAddr,
+    access_flags: PageFaultFlags,
+    region: &LazyRegion,
+    frames: &mut BTreeMap<VirtAddr, PhysFrame>,
+    frame_source: &dyn FrameSource,
+    cow: bool,
+) -> FaultOutcome {
+    if let Some(outcome) = check_access(vaddr, access_flags, region) {
+        return outcome;
+    }
+    // Mapping over a present entry fails, so a repeated fault must not reach
+    // the backend.
+    if matches!(
+        uspace.page_table().query(vaddr),
+        Ok((_, flags, _)) if flags.contains(access_flags)
+    ) {
+        return FaultOutcome::AlreadyMapped;
+    }
+    match &region.backend {
+        LazyBackend::Prealloc(pages) => handle_stack_fault(
+            uspace,
+            vaddr,
+            access_flags,
+            region,
+            pages,
+            cow.then_some(frames),
+        ),
+        LazyBackend::FileBacked {
+            data,
+            file_offset,
+            file_size,
+        } => {
+            let file = &data[*file_offset..*file_offset + *file_size];
+            match map_file_page(uspace, region, file, vaddr, frames) {
+                Ok(()) => FaultOutcome::Mapped,
+                Err(e) => FaultOutcome::Segfault(Some(e)),
+            }
+        }
+        LazyBackend::ZeroPage => match map_zero_page(uspace, region, vaddr, access_flags, frames) {
+            Ok(()) => {
+                if cfg!(feature = "assert_zeroed") {
+                    assert_zeroed(uspace, vaddr);
+                }
+                FaultOutcome::Mapped
+            }
+            Err(e) => FaultOutcome::Segfault(Some(e)),
+        },
+        LazyBackend::Custom(provider) => {
+            map_provided_page(uspace, region, provider.as_ref(), vaddr, access_flags)
+        }
+        LazyBackend::OnDemand => {
+            let page = vaddr.align_down_4k();
+            if frame_source
+                .map_zeroed(uspace, page, region.flags)
+                .is_some()
+            {
+                if cfg!(feature = "assert_zeroed") {
+                    assert_zeroed(uspace, vaddr);
+                }
+                FaultOutcome::Mapped
+            } else {
+                FaultOutcome::Segfault(None)
+            }
+        }
+    }
+}
+
+/// Handles a fault at `vaddr` in `region`, which is backed by the
+/// pre-allocated `pages`.
+///
+/// The access is checked against the region flags before the matching page
+/// is mapped (see [`map_shared_page`] for `cow_copies`). A fault outside
+/// `region` is a segfault.
+pub fn handle_stack_fault(
+    uspace: &mut AddrSpace,
+    vaddr: VirtAddr,
+    access_flags: PageFaultFlags,
+    region: &LazyRegion,
+    pages: &SharedPages,
+    cow_copies: Option<&mut BTreeMap<VirtAddr, PhysFrame>>,
+) -> FaultOutcome {
+    if let Some(outcome) = check_access(vaddr, access_flags, region) {
+        return outcome;
+    }
+    match map_shared_page(uspace, pages, region, vaddr, access_flags, cow_copies) {
+        Ok(()) => FaultOutcome::Mapped,
+        Err(e) => FaultOutcome::Segfault(Some(e)),
+    }
+}
+
+/// Reclaims the page of `region` containing `vaddr`, as memory pressure
+/// would: the page is unmapped, so that the next access to it faults again
+/// and the backend maps it anew.
+///
+/// A frame allocated for the page is freed: an on-demand or zero-page page
+/// then reads as zeros again, a file-backed page as the file contents, and a
+/// private copy-on-write copy as the shared page. A pre-allocated page keeps
+/// its contents, as its frame stays in the [`SharedPages`]. A huge
+/// pre-allocated page is reclaimed as a whole.
+///
+/// Returns whether the page was mapped, or [`AxError::InvalidInput`] if
+/// `vaddr` is outside `region`.
+#[allow(dead_code)] // The demo never reclaims a page outside the self-test.
+pub fn reclaim_page(
+    uspace: &mut AddrSpace,
+    region: &LazyRegion,
+    frames: &mut BTreeMap<VirtAddr, PhysFrame>,
+    vaddr: VirtAddr,
+) -> AxResult<bool> {
+    if !region.contains(vaddr) {
+        return Err(AxError::InvalidInput);
+    }
+    let page_size = region.page_size();
+    let page = vaddr.align_down(page_size);
+    if uspace.page_table().query(page).is_err() {
+        return Ok(false);
+    }
+    uspace.unmap(page, page_size)?;
+    if matches!(region.backend, LazyBackend::OnDemand) {
+        // Unmapping freed the frame along with its part of the area, which
+        // is registered again to be demand-paged.
+        uspace.map_alloc(page, page_size, region.flags, false)?;
+    }
+    frames.remove(&page);
+    Ok(true)
+}
+
+/// Maps a private copy of the page of `file` containing `vaddr`.
+///
+/// The page at `region.start` holds the first bytes of `file`, and the part
+/// of the page past the end of `file` (e.g. the `.bss` of a segment) is
+/// zero-filled. The new frame is mapped with the region flags and kept in
+/// `frames`.
+fn map_file_page(
+    uspace: &mut AddrSpace,
+    region: &LazyRegion,
+    file: &[u8],
+    vaddr: VirtAddr,
+    frames: &mut BTreeMap<VirtAddr, PhysFrame>,
+) -> Result<(), SpawnError> {
+    let aligned_va = vaddr.align_down_4k();
+    let offset = (aligned_va - region.start).min(file.len());
+    let end = (offset + PAGE_SIZE_4K).min(file.len());
+    let frame = PhysFrame::with_data(&file[offset..end]).ok_or(SpawnError::NoMemory)?;
+    uspace
+        .map_linear(aligned_va, frame.paddr(), PAGE_SIZE_4K, region.flags)
+        .map_err(|_| SpawnError::MapFailed(aligned_va))?;
+    frames.insert(aligned_va, frame);
+    Ok(())
+}
+
+/// Maps the page of `vaddr` in `region` to the frame `provider` gives for it,
+/// with the flags it gives, for an access of type `access_flags`.
+///
+/// The page is refused, and the fault is a segfault, if the frame is not
+/// page-aligned, or the flags grant more than the region flags, map anything
+/// but normal memory, or deny the access.
+fn map_provided_page(
+    uspace: &mut AddrSpace,
+    region: &LazyRegion,
+    provider: &dyn PageProvider,
+    vaddr: VirtAddr,
+    access_flags: PageFaultFlags,
+) -> FaultOutcome {
+    let page = vaddr.align_down_4k();
+    let refused = FaultOutcome::Segfault(Some(SpawnError::MapFailed(page)));
+    let Ok((frame, flags)) = provider.provide_page(region, page) else {
+        return refused;
+    };
+    if !frame.is_aligned_4k()
+        || !region.flags.contains(flags)
+        || !is_normal_memory(flags)
+        || permission_fault(flags, access_flags).is_some()
+    {
+        return refused;
+    }
+    match uspace.map_linear(page, frame, PAGE_SIZE_4K, flags) {
+        Ok(()) => FaultOutcome::Mapped,
+        Err(_) => refused,
+    }
+}
+
+/// Maps the page of `vaddr` in `region`, a zero-page region, for an access
+/// of type `access_flags`.
+///
+/// A read maps the shared [`zero_frame`] without `WRITE`. A write maps a
+/// fresh zeroed frame with the region flags instead, dropping the mapping of
+/// the zero frame if any, and keeps the frame in `frames`.
+fn map_zero_page(
+    uspace: &mut AddrSpace,
+    region: &LazyRegion,
+    vaddr: VirtAddr,
+    access_flags: PageFaultFlags,
+    frames: &mut BTreeMap<VirtAddr, PhysFrame>,
+) -> Result<(), SpawnError> {
+    let page = vaddr.align_down_4k();
+    let map_err = |_| SpawnError::MapFailed(page);
+    if !access_flags.contains(PageFaultFlags::WRITE) {
+        return uspace
+            .map_linear(
+                page,
+                zero_frame(),
+                PAGE_SIZE_4K,
+                region.flags - MappingFlags::WRITE,
+            )
+            .map_err(map_err);
+    }
+    let frame = PhysFrame::with_data(&[]).ok_or(SpawnError::NoMemory)?;
+    if uspace.page_table().query(page).is_ok() {
+        uspace.unmap(page, PAGE_SIZE_4K).map_err(map_err)?;
+    }
+    uspace
+        .map_linear(page, frame.paddr(), PAGE_SIZE_4K, region.flags)
+        .map_err(map_err)?;
+    frames.insert(page, frame);
+    Ok(())
+}
+
+/// Checks that the page just mapped at `vaddr` from a fresh frame reads as
+/// zeros, so that an allocator handing out dirty frames cannot leak their
+/// contents to user space.
+///
+/// Panics if any byte of the frame is set.
+fn assert_zeroed(uspace: &AddrSpace, vaddr: VirtAddr) {
+    let page = vaddr.align_down_4k();
+    let Ok((paddr, _, _)) = uspace.page_table().query(page) else {
+        panic!("fresh page {:#x} is not mapped", page);
+    };
+    let frame = paddr.align_down_4k();
+    let bytes = unsafe { core::slice::from_raw_parts(phys_to_virt(frame).as_ptr(), PAGE_SIZE_4K) };
+    assert!(
+        bytes.iter().all(|&b| b == 0),
+        "fresh page {:#x} is backed by dirty frame {:#x}",
+        page,
+        frame
+    );
+}
+
+/// Returns the outcome of a fault at `vaddr` that `region` cannot satisfy,
+/// because `vaddr` is outside of it or the access is not permitted.
+fn check_access(
+    vaddr: VirtAddr,
+    access_flags: PageFaultFlags,
+    region: &LazyRegion,
+) -> Option<FaultOutcome> {
+    if !region.contains(vaddr) {
+        return Some(FaultOutcome::Segfault(None));
+    }
+    permission_fault(region.flags, access_flags).map(FaultOutcome::Permission)
+}
+
+/// Maps the page of `pages` backing the stack page that contains `vaddr`.
+///
+/// The lowest address of `region` is backed by `pages.phys_pages[0]`. The page
+/// is mapped with the flags of `region`, as a whole: the faulting address is
+/// aligned down to `pages.page_size`, and one fault maps the full (possibly
+/// huge) page.
+///
+/// `AddrSpace` can only install 4K entries, so a huge page is mapped as a run
+/// of contiguous 4K entries: this saves faults, but not TLB entries.
+///
+/// With copy-on-write (`cow_copies` is `Some`), the shared frame is mapped
+/// without `WRITE`. The first write fault to the page then replaces that
+/// mapping with a private copy of the frame, mapped with the region flags and
+/// kept in `cow_copies`, so the write is never visible to other tasks sharing
+/// `pages`.
+fn map_shared_page(
+    uspace: &mut AddrSpace,
+    pages: &SharedPages,
+    region: &LazyRegion,
+    vaddr: VirtAddr,
+    access_flags: PageFaultFlags,
+    cow_copies: Option<&mut BTreeMap<VirtAddr, PhysFrame>>,
+) -> Result<(), SpawnError> {
+    // The one granularity the page is looked up, mapped and unmapped with.
+    let stack_page_size = region.page_size();
+    debug_assert_eq!(stack_page_size, pages.page_size as usize);
+    let (aligned_va, page_idx) =
+        page_index(region.start, vaddr, stack_page_size).ok_or(SpawnError::MapFailed(vaddr))?;
+    let flags = region.flags;
+    let frame = *pages
+        .phys_pages
+        .get(page_idx)
+        .ok_or(SpawnError::NoPhysPage(page_idx))?;
+    let map_err = |_| SpawnError::MapFailed(aligned_va);
+    match cow_copies {
+        None => uspace.map_linear(aligned_va, frame, stack_page_size, flags),
+        Some(copies) if access_flags.contains(PageFaultFlags::WRITE) => {
+            // Spawning rejects copy-on-write of huge pages, so the page is
+            // the 4K of the private copy.
+            let copy = PhysFrame::copy_of(frame).ok_or(SpawnError::NoMemory)?;
+            // Drop the read-only mapping of the shared frame, if any.
+            if uspace.page_table().query(aligned_va).is_ok() {
+                uspace.unmap(aligned_va, stack_page_size).map_err(map_err)?;
+            }
+            uspace
+                .map_linear(aligned_va, copy.paddr(), stack_page_size, flags)
+                .map_err(map_err)?;
+            copies.insert(aligned_va, copy);
+            Ok(())
+        }
+        Some(_) => uspace.map_linear(
+            aligned_va,
+            frame,
+            stack_page_size,
+            flags - MappingFlags::WRITE,
+        ),
+    }
+    .map_err(map_err)
+}
+
+/// Returns the start of the page of `page_size` bytes that contains `vaddr`,
+/// and its index from `start`, the page-aligned lowest address of a region.
+///
+/// Returns `None` if the page of `vaddr` starts below `start`, rather than
+/// wrapping around to a huge index: [`map_shared_page`] then fails, and the
+/// fault is a segfault.
+fn page_index(start: VirtAddr, vaddr: VirtAddr, page_size: usize) -> Option<(VirtAddr, usize)> {
+    let aligned_va = vaddr.align_down(page_size);
+    let page_idx = aligned_va.checked_sub_addr(start)? / page_size;
+    Some((aligned_va, page_idx))
+}
+
+/// Checks whether an access of type `access_flags` is denied by the
+/// permissions `region_flags` of the region it hits.
+///
+/// Returns a description of the denied access, or `None` if it is allowed.
+fn permission_fault(
+    region_flags: MappingFlags,
+    access_flags: PageFaultFlags,
+) -> Option<&'static str> {
+    if region_flags.contains(access_flags) {
+        None
+    } else if access_flags.contains(PageFaultFlags::WRITE) {
+        Some("write to read-only page")
+    } else if access_flags.contains(PageFaultFlags::EXECUTE) {
+        Some("instruction fetch from non-executable page")
+    } else {
+        Some("read from non-readable page")
+    }
+}
@@ -0,0 +1,766 @@
+//! Boot-time self-test of the fault handler, with feature `fault_selftest`.
+//!
+//! The crate has no unit-test harness, so the checks run in the kernel, on
+//! scratch address spaces of their own, before any user task is spawned.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use axerrno::AxError;
+use axhal::mem::{PAGE_SIZE_4K, phys_to_virt};
+use axhal::paging::{MappingFlags, PageSize};
+use axhal::trap::PageFaultFlags;
+use axmm::AddrSpace;
+use memory_addr::{MemoryAddr, VirtAddr, VirtAddrRange};
+
+use super::{FaultOutcome, handle_region_fault, map_shared_page, page_index, reclaim_page};
+use crate::aspace::{AddrSpaceBuilder, LayoutError};
+use crate::mm::{
+    FramePool, GlobalFrames, LazyBackend, LazyRegion, LazyRegions, MmapArea, ProgramBreak,
+    STACK_FLAGS, SharedPages, UserMemory,
+};
+use crate::task::{self, ExitCodes, SpawnError, StackLayout, UserApp};
+
+/// A case of [`page_index`]: the region start, the faulting address, the page
+/// size, and the expected page index and page start, if any.
+type PageIndexCase = (usize, usize, usize, Option<(usize, usize)>);
+
+/// Cases of [`page_index`] with 32-bit addresses.
+const PAGE_INDEX_CASES: &[PageIndexCase] = &[
+    // riscv32 (Sv32): a stack right below the 2G split, and one at the top
+    // of the 32-bit address space, where a cast or an overflow would show.
+    (0x7fff_0000, 0x7fff_0000, 0x1000, Some((0, 0x7fff_0000))),
+    (0x7fff_0000, 0x7fff_ffff, 0x1000, Some((15, 0x7fff_f000))),
+    (0xfff0_0000, 0xffff_ffff, 0x1000, Some((255, 0xffff_f000))),
+    (0x7fc0_0000, 0x7fff_ffff, 0x20_0000, Some((1, 0x7fe0_0000))),
+    (0x7fff_0000, 0x7ffe_ffff, 0x1000, None),
+    (0, 0, 0x1000, Some((0, 0))),
+    // A huge page starting below the region, even for an address inside it,
+    // has no index rather than a wrapped one.
+    (0x7fe1_0000, 0x7fe1_0000, 0x20_0000, None),
+];
+
+/// Cases of [`page_index`] with 64-bit addresses.
+#[cfg(target_pointer_width = "64")]
+const PAGE_INDEX_CASES_64: &[PageIndexCase] = &[
+    // riscv64 (Sv39) and loongarch64: the stack of the demo.
+    (
+        0x3f_ffff_0000,
+        0x3f_ffff_ffff,
+        0x1000,
+        Some((15, 0x3f_ffff_f000)),
+    ),
+    // x86_64: a stack below the canonical hole.
+    (
+        0x7fff_ffff_0000,
+        0x7fff_ffff_e123,
+        0x1000,
+        Some((14, 0x7fff_ffff_e000)),
+    ),
+    // aarch64: a stack at the top of a 48-bit user space.
+    (
+        0xffff_fff0_0000,
+        0xffff_ffff_ffff,
+        0x1000,
+        Some((255, 0xffff_ffff_f000)),
+    ),
+    // A huge page right above 4G, lost if addresses were cut to 32 bits.
+    (
+        0xffe0_0000,
+        0x1_0000_0005,
+        0x20_0000,
+        Some((1, 0x1_0000_0000)),
+    ),
+    (0x40_0000_0000, 0x3f_ffff_ffff, 0x1000, None),
+];
+
+/// Start of the region each simulation adds to its [`test_aspace`].
+const TEST_BASE: usize = 0x1000_0000;
+/// Size of the address space of a simulation, with room for its region.
+const TEST_ASPACE_SIZE: usize = 2 * TEST_BASE;
+
+/// Creates a scratch address space `[0, size)` for a simulation, and the
+/// memory of a task in it, with no region, heap or mmap window, and no
+/// copy-on-write.
+fn test_aspace(size: usize) -> (AddrSpace, UserMemory) {
+    let uspace = match axmm::new_user_aspace(VirtAddr::from(0), size) {
+        Ok(uspace) => uspace,
+        Err(e) => panic!("Cannot create the self-test address space! {:?}", e),
+    };
+    let mem = UserMemory {
+        regions: LazyRegions::new(),
+        brk: ProgramBreak::new(uspace.end(), 0),
+        mmap: MmapArea::new(uspace.end(), 0),
+        cow: false,
+        frames: BTreeMap::new(),
+        dirty: None,
+        flags_policy: None,
+    };
+    (uspace, mem)
+}
+
+/// Adds a region of `pages` 4K pages at [`TEST_BASE`] to `regions`, those of
+/// the memory of a [`test_aspace`], with the stack flags, and returns it.
+fn add_test_region(regions: &mut LazyRegions, pages: usize, backend: LazyBackend) -> &LazyRegion {
+    let start = VirtAddr::from(TEST_BASE);
+    regions.insert(LazyRegion::new(
+        start,
+        start + pages * PAGE_SIZE_4K,
+        STACK_FLAGS,
+        backend,
+    ));
+    match regions.find(start) {
+        Some(region) => region,
+        None => panic!("Cannot add the self-test region at {:#x}!", start),
+    }
+}
+
+/// What a fault of [`STACK_FAULT_CASES`] must lead to.
+#[derive(Clone, Copy)]
+enum Expected {
+    /// The faulting page is now mapped to the pre-allocated page of the given
+    /// index.
+    Mapped(usize),
+    /// The faulting page was already mapped to the pre-allocated page of the
+    /// given index, and is left as it is.
+    AlreadyMapped(usize),
+    /// A segfault, with no page mapped.
+    Segfault,
+    /// A denied access, with no page mapped.
+    Denied,
+}
+
+/// Number of pre-allocated pages of the stack [`STACK_FAULT_CASES`] fault in.
+const TEST_STACK_PAGES: usize = 4;
+
+/// Faults taken in turn in the stack of [`simulate_stack_faults`]: the
+/// offset of the faulting address from the stack bottom, the access, and what
+/// the fault must lead to without and with copy-on-write.
+const STACK_FAULT_CASES: &[(isize, PageFaultFlags, Expected, Expected)] = &[
+    // The very first byte of the stack.
+    (
+        0,
+        PageFaultFlags::READ,
+        Expected::Mapped(0),
+        Expected::Mapped(0),
+    ),
+    // Another fault in the page changes nothing, nor does a write to it
+    // unless it is shared copy-on-write.
+    (
+        0x10,
+        PageFaultFlags::READ,
+        Expected::AlreadyMapped(0),
+        Expected::AlreadyMapped(0),
+    ),
+    (
+        0xff8,
+        PageFaultFlags::WRITE,
+        Expected::AlreadyMapped(0),
+        Expected::Mapped(0),
+    ),
+    (
+        0x20,
+        PageFaultFlags::WRITE,
+        Expected::AlreadyMapped(0),
+        Expected::AlreadyMapped(0),
+    ),
+    (
+        0x1000,
+        PageFaultFlags::EXECUTE,
+        Expected::Denied,
+        Expected::Denied,
+    ),
+    // The last byte of the stack, then the first bytes past either end:
+    // one byte below the stack is already out of it.
+    (
+        0x3fff,
+        PageFaultFlags::WRITE,
+        Expected::Mapped(3),
+        Expected::Mapped(3),
+    ),
+    (
+        0x4000,
+        PageFaultFlags::READ,
+        Expected::Segfault,
+        Expected::Segfault,
+    ),
+    (
+        -1,
+        PageFaultFlags::WRITE,
+        Expected::Segfault,
+        Expected::Segfault,
+    ),
+    (
+        0x1000,
+        PageFaultFlags::WRITE,
+        Expected::Mapped(1),
+        Expected::Mapped(1),
+    ),
+];
+
+/// Takes the faults of [`STACK_FAULT_CASES`] in a pre-allocated stack of a
+/// new address space, and checks the page table after each of them.
+///
+/// With `cow`, a read maps the shared page without `WRITE`, and a write
+/// replaces it with a private copy. With `custom`, the stack is instead a
+/// [`LazyBackend::Custom`] region, the same pages acting as its
+/// [`PageProvider`], which must map them as the pre-allocated stack does
+/// without copy-on-write. Returns the number of faults taken, and panics on
+/// the first one that goes wrong.
+fn simulate_stack_faults(cow: bool, custom: bool) -> usize {
+    let (mut uspace, mut mem) = test_aspace(TEST_ASPACE_SIZE);
+    let pages = match SharedPages::new(TEST_STACK_PAGES * PAGE_SIZE_4K, PageSize::Size4K) {
+        Ok(pages) => pages,
+        Err(e) => panic!("Cannot allocate the self-test stack! {:?}", e),
+    };
+    let shared = pages.phys_pages.clone();
+    let backend = if custom {
+        LazyBackend::Custom(Arc::new(pages))
+    } else {
+        LazyBackend::Prealloc(Arc::new(pages))
+    };
+    mem.cow = cow;
+    let region = add_test_region(&mut mem.regions, TEST_STACK_PAGES, backend);
+    let frames = &mut mem.frames;
+    for &(offset, access_flags, expected, expected_cow) in STACK_FAULT_CASES {
+        let expected = if cow { expected_cow } else { expected };
+        let vaddr = VirtAddr::from(TEST_BASE.wrapping_add_signed(offset));
+        let outcome = handle_region_fault(
+            &mut uspace,
+            vaddr,
+            access_flags,
+            region,
+            frames,
+            &GlobalFrames,
+            mem.cow,
+        );
+        let page = vaddr.align_down_4k();
+        let mapping = uspace.page_table().query(page).ok();
+        let ok = match (expected, outcome) {
+            (Expected::Mapped(idx), FaultOutcome::Mapped)
+            | (Expected::AlreadyMapped(idx), FaultOutcome::AlreadyMapped) => {
+                mapping.is_some_and(|(paddr, flags, _)| {
+                    // A page of `frames` is a private copy made on a write;
+                    // any other maps the shared page, read-only with `cow`.
+                    match frames.get(&page) {
+                        Some(copy) => paddr == copy.paddr() && flags == region.flags,
+                        None if cow => {
+                            paddr == shared[idx] && flags == region.flags - MappingFlags::WRITE
+                        }
+                        None => paddr == shared[idx] && flags == region.flags,
+                    }
+                })
+            }
+            (Expected::Segfault, FaultOutcome::Segfault(None))
+            | (Expected::Denied, FaultOutcome::Permission(_)) => mapping.is_none(),
+            _ => false,
+        };
+        assert!(
+            ok,
+            "fault at {:#x} ({:?}, cow: {}) left {:x?}",
+            vaddr, access_flags, cow, mapping
+        );
+        if cow && access_flags.contains(PageFaultFlags::WRITE) && mapping.is_some() {
+            assert!(frames.contains_key(&page), "no private copy of {:#x}", page);
+        }
+    }
+    // A custom provider looks its pages up by itself.
+    let LazyBackend::Prealloc(pages) = &region.backend else {
+        return STACK_FAULT_CASES.len();
+    };
+    // Past the range check, the page index of the byte below the stack is
+    // still not computed by wrapping around.
+    let below = VirtAddr::from(TEST_BASE - 1);
+    let mapped = map_shared_page(
+        &mut uspace,
+        pages,
+        region,
+        below,
+        PageFaultFlags::READ,
+        None,
+    );
+    assert!(
+        matches!(mapped, Err(SpawnError::MapFailed(_))),
+        "page below the stack mapped at {:#x}",
+        below
+    );
+    // Nor is a page past the last pre-allocated one, in a region that would
+    // be longer than its pages, looked up out of bounds.
+    let longer = LazyRegion::new(
+        region.start,
+        region.end + PAGE_SIZE_4K,
+        region.flags,
+        region.backend.clone(),
+    );
+    let mapped = map_shared_page(
+        &mut uspace,
+        pages,
+        &longer,
+        region.end,
+        PageFaultFlags::READ,
+        None,
+    );
+    assert!(
+        matches!(mapped, Err(SpawnError::NoPhysPage(TEST_STACK_PAGES))),
+        "page past the pre-allocated pages mapped at {:#x}",
+        region.end
+    );
+    STACK_FAULT_CASES.len() + 2
+}
+
+/// Stacks [`spawn_misaligned_stacks`] tries to spawn a task with: whether the
+/// stack is pre-allocated, and how far its bottom is moved up and its top
+/// down, which is never a whole page.
+const MISALIGNED_STACK_CASES: &[(bool, usize, usize)] = &[
+    (false, 0, 0x10),
+    (false, 0x10, 0),
+    (true, 0, 0x10),
+    (true, 0x800, 0),
+];
+
+/// Spawns tasks with the stacks of [`MISALIGNED_STACK_CASES`], each built
+/// aligned and then cut to a bound that is not 4K-aligned, though still
+/// aligned to `STACK_ALIGN`, and checks that each of them is rejected before
+/// the task starts. Returns the number of stacks rejected, and panics on the
+/// first one that is not.
+fn spawn_misaligned_stacks() -> usize {
+    for &(prealloc, bottom_up, top_down) in MISALIGNED_STACK_CASES {
+        // The address space of a user task, for its stack layout.
+        let (uspace, _) = test_aspace(crate::USER_ASPACE_SIZE);
+        let layout = StackLayout::new(
+            uspace.end() - crate::USER_STACK_SIZE,
+            crate::USER_STACK_SIZE,
+        );
+        let layout = if prealloc {
+            layout.with_prealloc(PageSize::Size4K)
+        } else {
+            layout
+        };
+        let mut stack = match layout.build() {
+            Ok(stack) => stack,
+            Err(e) => panic!("Cannot build the self-test stack! {}", e),
+        };
+        stack.bottom += bottom_up;
+        stack.top -= top_down;
+        let (bottom, top) = (stack.bottom, stack.top);
+        let app = UserApp {
+            uspace,
+            regions: LazyRegions::new(),
+            entry: crate::APP_ENTRY,
+            stack,
+            heap: VirtAddrRange::from_start_size(
+                crate::USER_HEAP_BASE.into(),
+                crate::USER_HEAP_SIZE,
+            ),
+            on_fault: None,
+            on_exit: None,
+            exit_codes: ExitCodes::default(),
+            max_faults: None,
+            symbols: None,
+            info_page: None,
+            flags_policy: None,
+            single_step: false,
+            heap_guard: false,
+        };
+        let spawned =
+            task::spawn_user_task_at(app, String::from("misaligned"), crate::KERNEL_STACK_SIZE);
+        assert!(
+            matches!(spawned, Err(SpawnError::AddrSpaceSetup)),
+            "stack [{:#x}, {:#x}) not rejected at spawn",
+            bottom,
+            top
+        );
+    }
+    MISALIGNED_STACK_CASES.len()
+}
+
+/// Faults in a page of a stack of a new address space, writes to it,
+/// reclaims it with [`reclaim_page`] and faults it in again, checking the
+/// page table and the contents of the page at each step.
+///
+/// With `prealloc`, the stack is backed by pre-allocated pages, shared
+/// copy-on-write with `cow`: the page written to must come back with its
+/// contents, unless the write went to a private copy, which reclaiming
+/// drops. Otherwise the stack is demand-paged from fresh frames, and the
+/// page must come back zeroed. Returns the number of steps checked, and
+/// panics on the first one that goes wrong.
+fn simulate_reclaim(prealloc: bool, cow: bool) -> usize {
+    const PATTERN: u64 = 0x1a2b_c0de_f00d_5eed;
+    let (mut uspace, mut mem) = test_aspace(TEST_ASPACE_SIZE);
+    let start = VirtAddr::from(TEST_BASE);
+    let size = TEST_STACK_PAGES * PAGE_SIZE_4K;
+    let (backend, shared) = if prealloc {
+        let pages = match SharedPages::new(size, PageSize::Size4K) {
+            Ok(pages) => pages,
+            Err(e) => panic!("Cannot allocate the self-test stack! {:?}", e),
+        };
+        let shared = pages.phys_pages.clone();
+        (LazyBackend::Prealloc(Arc::new(pages)), shared)
+    } else {
+        if let Err(e) = uspace.map_alloc(start, size, STACK_FLAGS, false) {
+            panic!("Cannot register the self-test stack! {:?}", e);
+        }
+        (LazyBackend::OnDemand, Vec::new())
+    };
+    mem.cow = cow;
+    let region = add_test_region(&mut mem.regions, TEST_STACK_PAGES, backend);
+    let frames = &mut mem.frames;
+    let page = start + PAGE_SIZE_4K;
+    let word = |uspace: &AddrSpace| {
+        uspace.page_table().query(page).ok().map(|(paddr, _, _)| {
+            let ptr: *mut u64 = phys_to_virt(paddr).as_mut_ptr().cast();
+            (paddr, ptr)
+        })
+    };
+
+    let outcome = handle_region_fault(
+        &mut uspace,
+        page + 8,
+        PageFaultFlags::WRITE,
+        region,
+        frames,
+        &GlobalFrames,
+        mem.cow,
+    );
+    let Some((_, ptr)) = word(&uspace).filter(|_| matches!(outcome, FaultOutcome::Mapped)) else {
+        panic!(
+            "page {:#x} not faulted in (prealloc: {}, cow: {})",
+            page, prealloc, cow
+        );
+    };
+    unsafe { ptr.write_volatile(PATTERN) };
+
+    for expected in [Ok(true), Ok(false)] {
+        let reclaimed = reclaim_page(&mut uspace, region, frames, page + 8);
+        assert!(
+            reclaimed == expected && word(&uspace).is_none() && !frames.contains_key(&page),
+            "page {:#x} not reclaimed (prealloc: {}, cow: {}): {:?}",
+            page,
+            prealloc,
+            cow,
+            reclaimed
+        );
+    }
+
+    let outcome = handle_region_fault(
+        &mut uspace,
+        page,
+        PageFaultFlags::READ,
+        region,
+        frames,
+        &GlobalFrames,
+        mem.cow,
+    );
+    let refaulted = word(&uspace).filter(|_| matches!(outcome, FaultOutcome::Mapped));
+    // Only a write to the shared page itself survives reclaiming it.
+    let expected = if prealloc && !cow { PATTERN } else { 0 };
+    let ok = refaulted.is_some_and(|(paddr, ptr)| {
+        (!prealloc || paddr == shared[1]) && unsafe { ptr.read_volatile() } == expected
+    });
+    assert!(
+        ok,
+        "page {:#x} not faulted in again as expected (prealloc: {}, cow: {})",
+        page, prealloc, cow
+    );
+    3
+}
+
+/// Pages of the on-demand region of [`simulate_pool_faults`], in the order
+/// they are faulted in, with the access.
+const POOL_FAULT_CASES: &[(usize, PageFaultFlags)] = &[
+    (2, PageFaultFlags::WRITE),
+    (0, PageFaultFlags::READ),
+    (3, PageFaultFlags::WRITE),
+    (1, PageFaultFlags::READ),
+];
+
+/// Takes the faults of [`POOL_FAULT_CASES`] in an on-demand region of a new
+/// address space, whose frames come from a [`FramePool`] of as many frames,
+/// then one more fault, in a page left.
+///
+/// The `n`-th fault must map its page to the `n`-th frame of the pool,
+/// zeroed, with the region flags, whatever the order of the pages, and the
+/// last one must be a segfault, the pool being exhausted. Returns the number
+/// of faults taken, and panics on the first one that goes wrong.
+fn simulate_pool_faults() -> usize {
+    let pool = match FramePool::new(POOL_FAULT_CASES.len()) {
+        Ok(pool) => pool,
+        Err(e) => panic!("Cannot allocate the self-test frame pool! {:?}", e),
+    };
+    // Dropped before the pool, which its mappings point to.
+    let (mut uspace, mut mem) = test_aspace(TEST_ASPACE_SIZE);
+    let start = VirtAddr::from(TEST_BASE);
+    let region = add_test_region(
+        &mut mem.regions,
+        POOL_FAULT_CASES.len() + 1,
+        LazyBackend::OnDemand,
+    );
+    let frames = &mut mem.frames;
+    for (n, &(index, access_flags)) in POOL_FAULT_CASES.iter().enumerate() {
+        let page = start + index * PAGE_SIZE_4K;
+        let outcome = handle_region_fault(
+            &mut uspace,
+            page + 0x10,
+            access_flags,
+            region,
+            frames,
+            &pool,
+            mem.cow,
+        );
+        let expected = pool.base() + n * PAGE_SIZE_4K;
+        let mapping = uspace.page_table().query(page).ok();
+        let zeroed = || {
+            let bytes: *const u8 = phys_to_virt(expected).as_ptr();
+            (0..PAGE_SIZE_4K).all(|i| unsafe { bytes.add(i).read_volatile() } == 0)
+        };
+        assert!(
+            matches!(outcome, FaultOutcome::Mapped)
+                && mapping.is_some_and(|(paddr, flags, _)| {
+                    paddr == expected && flags == region.flags
+                })
+                && zeroed(),
+            "fault {} at {:#x} did not map frame {:#x}: {:x?}",
+            n,
+            page,
+            expected,
+            mapping
+        );
+        // Dirtied, so that a frame handed out again would not read as zeros.
+        unsafe { phys_to_virt(expected).as_mut_ptr().write_volatile(0xff) };
+    }
+    let last = start + POOL_FAULT_CASES.len() * PAGE_SIZE_4K;
+    let outcome = handle_region_fault(
+        &mut uspace,
+        last,
+        PageFaultFlags::READ,
+        region,
+        frames,
+        &pool,
+        mem.cow,
+    );
+    assert!(
+        matches!(outcome, FaultOutcome::Segfault(None)) && uspace.page_table().query(last).is_err(),
+        "fault at {:#x} mapped a page past the pool",
+        last
+    );
+    POOL_FAULT_CASES.len() + 1
+}
+
+/// Unmaps parts of a pre-allocated and of a file-backed region of the mmap
+/// window of a new address space, as `SYS_MUNMAP` would.
+///
+/// Cutting the pre-allocated region must fail with [`AxError::InvalidInput`]
+/// and leave it whole, while unmapping all of it must succeed. Cutting the
+/// file-backed region must leave a tail that still maps the bytes of the
+/// file it did. Returns the number of unmaps checked, and panics on the first
+/// one that goes wrong.
+fn simulate_indexed_unmap() -> usize {
+    let (mut uspace, mut mem) = test_aspace(TEST_ASPACE_SIZE);
+    let start = VirtAddr::from(TEST_BASE);
+    let size = TEST_STACK_PAGES * PAGE_SIZE_4K;
+    let window = MmapArea::new(start, 2 * size);
+    let pages = match SharedPages::new(size, PageSize::Size4K) {
+        Ok(pages) => pages,
+        Err(e) => panic!("Cannot allocate the self-test pages! {:?}", e),
+    };
+    add_test_region(
+        &mut mem.regions,
+        TEST_STACK_PAGES,
+        LazyBackend::Prealloc(Arc::new(pages)),
+    );
+    let cut = window.unmap(
+        &mut uspace,
+        &mut mem.regions,
+        &mut mem.frames,
+        start + PAGE_SIZE_4K,
+        PAGE_SIZE_4K,
+    );
+    assert!(
+        cut == Err(AxError::InvalidInput)
+            && mem
+                .regions
+                .find(start)
+                .is_some_and(|r| (r.start, r.end) == (start, start + size)),
+        "pre-allocated region at {:#x} cut: {:?}",
+        start,
+        cut
+    );
+    let whole = window.unmap(&mut uspace, &mut mem.regions, &mut mem.frames, start, size);
+    assert!(
+        whole.is_ok() && mem.regions.find(start).is_none(),
+        "pre-allocated region at {:#x} not unmapped: {:?}",
+        start,
+        whole
+    );
+
+    let data: Arc<[u8]> = Arc::from(alloc::vec![0u8; size]);
+    add_test_region(
+        &mut mem.regions,
+        TEST_STACK_PAGES,
+        LazyBackend::FileBacked {
+            data,
+            file_offset: 0,
+            file_size: size,
+        },
+    );
+    let cut = window.unmap(
+        &mut uspace,
+        &mut mem.regions,
+        &mut mem.frames,
+        start + PAGE_SIZE_4K,
+        PAGE_SIZE_4K,
+    );
+    let tail = start + 2 * PAGE_SIZE_4K;
+    let ok = cut.is_ok()
+        && mem.regions.find(start + PAGE_SIZE_4K).is_none()
+        && matches!(
+            mem.regions.find(tail).map(|r| &r.backend),
+            Some(LazyBackend::FileBacked { file_offset, file_size, .. })
+                if *file_offset == 2 * PAGE_SIZE_4K && *file_size == size - 2 * PAGE_SIZE_4K
+        );
+    assert!(
+        ok,
+        "file-backed region at {:#x} cut wrong: {:?}",
+        start, cut
+    );
+    3
+}
+
+/// A broken layout of [`BROKEN_LAYOUTS`]: what is wrong with it, the change
+/// that breaks a valid layout, and whether an error is the one expected.
+type BrokenLayout = (
+    &'static str,
+    fn(AddrSpaceBuilder) -> AddrSpaceBuilder,
+    fn(&LayoutError) -> bool,
+);
+
+/// Layouts [`build_layouts`] hands to an [`AddrSpaceBuilder`], each broken in
+/// one way, with the error its build must fail with.
+const BROKEN_LAYOUTS: &[BrokenLayout] = &[
+    (
+        "misaligned heap",
+        |b| b.with_heap(VirtAddr::from(crate::USER_HEAP_BASE + 0x10), PAGE_SIZE_4K),
+        |e| matches!(e, LayoutError::Misaligned(_)),
+    ),
+    (
+        "region past the end",
+        |b| b.with_region(test_code_region(crate::USER_ASPACE_SIZE)),
+        |e| matches!(e, LayoutError::OutOfRange(_)),
+    ),
+    (
+        "region in the mmap window",
+        |b| b.with_region(test_code_region(crate::USER_MMAP_BASE)),
+        |e| matches!(e, LayoutError::Overlap(_)),
+    ),
+    (
+        "entry outside the code",
+        |b| b.with_entry(crate::APP_ENTRY + PAGE_SIZE_4K),
+        |e| matches!(e, LayoutError::BadEntry(_)),
+    ),
+    (
+        "empty stack",
+        |b| b.with_stack(StackLayout::new(VirtAddr::from(crate::USER_ASPACE_SIZE), 0)),
+        |e| matches!(e, LayoutError::Stack(_)),
+    ),
+    (
+        "pre-allocated pages smaller than their region",
+        |b| {
+            let pages = match SharedPages::new(PAGE_SIZE_4K, PageSize::Size4K) {
+                Ok(pages) => pages,
+                Err(e) => panic!("Cannot allocate the self-test pages! {:?}", e),
+            };
+            let start = VirtAddr::from(TEST_BASE / 2);
+            b.with_region(LazyRegion::new(
+                start,
+                start + 2 * PAGE_SIZE_4K,
+                STACK_FLAGS,
+                LazyBackend::Prealloc(Arc::new(pages)),
+            ))
+        },
+        |e| matches!(e, LayoutError::Setup(_)),
+    ),
+];
+
+/// Returns a region of one page of code at `start`.
+fn test_code_region(start: usize) -> LazyRegion {
+    let flags = MappingFlags::READ | MappingFlags::EXECUTE | MappingFlags::USER;
+    let start = VirtAddr::from(start);
+    LazyRegion::new(start, start + PAGE_SIZE_4K, flags, LazyBackend::OnDemand)
+}
+
+/// Builds an app with an [`AddrSpaceBuilder`] from a valid layout of the
+/// demo, a page of code at its entry and its stack, then from the same
+/// layout with no stack and with each change of [`BROKEN_LAYOUTS`], and
+/// checks that each of those fails with its error. Returns the number of
+/// layouts built, and panics on the first one that goes wrong.
+fn build_layouts() -> usize {
+    let valid = || {
+        let top = crate::USER_ASPACE_SIZE;
+        AddrSpaceBuilder::new(VirtAddr::from(0), crate::USER_ASPACE_SIZE)
+            .with_region(test_code_region(crate::APP_ENTRY))
+            .with_stack(StackLayout::new(
+                VirtAddr::from(top - crate::USER_STACK_SIZE),
+                crate::USER_STACK_SIZE,
+            ))
+    };
+    if let Err(e) = valid().build() {
+        panic!("valid layout rejected: {}", e);
+    }
+    let no_stack = AddrSpaceBuilder::new(VirtAddr::from(0), crate::USER_ASPACE_SIZE)
+        .with_region(test_code_region(crate::APP_ENTRY));
+    assert!(
+        matches!(no_stack.build(), Err(LayoutError::NoStack)),
+        "layout with no stack not rejected"
+    );
+    for &(name, broken, expected) in BROKEN_LAYOUTS {
+        match broken(valid()).build() {
+            Err(e) if expected(&e) => {}
+            Err(e) => panic!("layout with {} rejected as: {}", name, e),
+            Ok(_) => panic!("layout with {} not rejected", name),
+        }
+    }
+    BROKEN_LAYOUTS.len() + 2
+}
+
+/// Checks [`page_index`], the page arithmetic of the pre-allocated stack
+/// backend, against faulting addresses typical of each architecture, then
+/// drives the fault handler with the faults of [`STACK_FAULT_CASES`], with
+/// and without copy-on-write, and from a custom provider. Then a page of
+/// each kind of stack is reclaimed and faulted in again (see
+/// [`simulate_reclaim`]), on-demand pages are faulted in from a pool of
+/// known frames (see [`simulate_pool_faults`]), stacks whose bounds cut a
+/// page are spawned, to be rejected (see [`spawn_misaligned_stacks`]), and
+/// regions of the mmap window are unmapped in part (see
+/// [`simulate_indexed_unmap`]), and last, apps are built from valid and
+/// broken layouts (see [`build_layouts`]).
+///
+/// Panics on the first case it gets wrong.
+pub fn self_test() {
+    let cases = PAGE_INDEX_CASES.iter();
+    #[cfg(target_pointer_width = "64")]
+    let cases = cases.chain(PAGE_INDEX_CASES_64);
+    let mut count = 0;
+    for &(start, vaddr, page_size, expected) in cases {
+        let got = page_index(start.into(), vaddr.into(), page_size)
+            .map(|(aligned_va, page_idx)| (page_idx, aligned_va.as_usize()));
+        assert_eq!(
+            got, expected,
+            "page_index({:#x}, {:#x}, {:#x})",
+            start, vaddr, page_size
+        );
+        count += 1;
+    }
+    count += simulate_stack_faults(false, false)
+        + simulate_stack_faults(true, false)
+        + simulate_stack_faults(false, true)
+        + simulate_reclaim(true, false)
+        + simulate_reclaim(true, true)
+        + simulate_reclaim(false, false)
+        + simulate_pool_faults()
+        + spawn_misaligned_stacks()
+        + simulate_indexed_unmap()
+        + build_layouts();
+    ax_println!("fault self-test passed: {} cases", count);
+}
@@ -1,40 +1,247 @@
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
 use axfs::ROOT_FS_CONTEXT;
 use axhal::mem::PAGE_SIZE_4K;
 use axhal::paging::MappingFlags;
 #[allow(unused_imports)]
 use axio::Read;
 use axmm::AddrSpace;
+use memory_addr::{MemoryAddr, VirtAddr};
 
-use crate::APP_ENTRY;
+use crate::elf::{self, ElfFile};
+use crate::mm::{LazyBackend, LazyRegion, LazyRegions};
+use crate::{APP_ENTRY, USER_PIE_BASE};
 
-pub fn load_user_app(fname: &str, uspace: &mut AddrSpace) -> Result<(), axio::Error> {
-    let mut buf = [0u8; PAGE_SIZE_4K];
-    let n = load_file(fname, &mut buf)?;
+/// A user app loaded into an address space.
+pub struct UserImage {
+    /// User entry point.
+    pub entry: usize,
+    /// Function symbols of the app, with the `user-symbols` feature and if
+    /// it is an ELF file with a symbol table.
+    pub symbols: Option<SymbolTable>,
+}
+
+/// The function symbols of a user app, sorted by address, to name the code
+/// at a user address in diagnostics.
+pub struct SymbolTable {
+    symbols: Vec<(usize, usize, String)>,
+}
+
+impl SymbolTable {
+    /// Collects the function symbols of `elf`, loaded at `bias`, or returns
+    /// `None` if it has none.
+    fn from_elf(elf: &ElfFile, bias: usize) -> Option<Self> {
+        let mut symbols: Vec<_> = elf
+            .function_symbols()
+            .map(|sym| (bias + sym.addr, sym.size, String::from(sym.name)))
+            .collect();
+        symbols.sort_unstable_by_key(|&(addr, ..)| addr);
+        (!symbols.is_empty()).then_some(Self { symbols })
+    }
+
+    /// Returns the name of the function containing `addr`, and the offset of
+    /// `addr` in it.
+    ///
+    /// That is the nearest function at or below `addr`, unless its size is
+    /// known and `addr` is past its end.
+    pub fn lookup(&self, addr: usize) -> Option<(&str, usize)> {
+        let i = self.symbols.partition_point(|&(start, ..)| start <= addr);
+        let (start, size, name) = self.symbols.get(i.checked_sub(1)?)?;
+        let offset = addr - start;
+        (*size == 0 || offset < *size).then_some((name.as_str(), offset))
+    }
+}
+
+/// Loads the user app `fname` into `uspace`.
+///
+/// See [`load_user_image`] for how the file is loaded.
+pub fn load_user_app(
+    fname: &str,
+    uspace: &mut AddrSpace,
+    regions: &mut LazyRegions,
+) -> Result<UserImage, axio::Error> {
+    load_user_image(fname, load_file(fname)?.into(), uspace, regions)
+}
+
+/// Loads the user app `data`, named `name`, into `uspace`.
+///
+/// An ELF executable gets one region per `PT_LOAD` segment, mapped with the
+/// segment permissions, and its entry point is read from the ELF header. A
+/// position-independent one is loaded at [`USER_PIE_BASE`], its relative
+/// relocations applied, and its entry point offset by that base: nothing else
+/// in the kernel needs to know where it went. With the `user-symbols` feature,
+/// its function symbols are collected as well. Any other file is a raw binary,
+/// whose first page is loaded at [`APP_ENTRY`], and has no symbols. The regions
+/// of the app are added to `regions`.
+pub fn load_user_image(
+    name: &str,
+    data: Arc<[u8]>,
+    uspace: &mut AddrSpace,
+    regions: &mut LazyRegions,
+) -> Result<UserImage, axio::Error> {
+    let image = if elf::is_elf(&data) {
+        load_elf(&data, uspace, regions)?
+    } else {
+        UserImage {
+            entry: load_raw(&data, uspace, regions)?,
+            symbols: None,
+        }
+    };
+
+    ax_println!(
+        "Loaded app {} ({} bytes), entry {:#x}",
+        name,
+        data.len(),
+        image.entry
+    );
+
+    Ok(image)
+}
+
+/// Registers one file-backed region per segment of the ELF file `data`.
+///
+/// Nothing is mapped here: the pages of the segments are filled from `data` on
+/// first touch by the page fault handler. The relocations of a PIE are applied
+/// to a copy of `data` first, so the pages come in already patched. With the
+/// `zero-page` feature, the whole pages of a segment past its file contents
+/// (most of its `.bss`) are a [`LazyBackend::ZeroPage`] region instead, so
+/// reading them allocates no frame.
+fn load_elf(
+    data: &Arc<[u8]>,
+    uspace: &mut AddrSpace,
+    regions: &mut LazyRegions,
+) -> Result<UserImage, axio::Error> {
+    let elf = ElfFile::parse(data)?;
+    let bias = if elf.is_pie() { USER_PIE_BASE } else { 0 };
+    let relocations = elf.relocations()?;
+    let data: Arc<[u8]> = if relocations.is_empty() {
+        data.clone()
+    } else {
+        let mut patched = data.to_vec();
+        for reloc in relocations {
+            let offset = elf
+                .file_offset(reloc.vaddr, size_of::<usize>())
+                .ok_or(axio::Error::InvalidData)?;
+            patched[offset..offset + size_of::<usize>()]
+                .copy_from_slice(&bias.wrapping_add(reloc.addend).to_le_bytes());
+        }
+        patched.into()
+    };
+    for segment in elf.segments() {
+        let segment = segment?;
+        let vaddr = segment
+            .vaddr
+            .checked_add(bias)
+            .map(VirtAddr::from)
+            .ok_or(axio::Error::InvalidData)?;
+        let start = vaddr.align_down_4k();
+        let end = (vaddr + segment.mem_size).align_up_4k();
+        // The region starts on a page boundary, and so does its file contents.
+        let lead = vaddr - start;
+        let file_offset = segment
+            .file_offset
+            .checked_sub(lead)
+            .ok_or(axio::Error::InvalidData)?;
+        // Segments must not share a page: a page has a single owner region.
+        let overlaps = (start.as_usize()..end.as_usize())
+            .step_by(PAGE_SIZE_4K)
+            .any(|page| regions.find(page.into()).is_some());
+        if overlaps || !uspace.contains_range(start, end - start) {
+            return Err(axio::Error::InvalidData);
+        }
+        // With the `zero-page` feature, the pages past the file contents are
+        // a zero-page region of their own.
+        let file_end = if cfg!(feature = "zero-page") {
+            (vaddr + segment.file_size).align_up_4k().min(end)
+        } else {
+            end
+        };
+        if file_end > start {
+            regions.insert(LazyRegion::new(
+                start,
+                file_end,
+                segment.flags,
+                LazyBackend::FileBacked {
+                    data: data.clone(),
+                    file_offset,
+                    file_size: segment.file_size + lead,
+                },
+            ));
+        }
+        if end > file_end {
+            regions.insert(LazyRegion::new(
+                file_end,
+                end,
+                segment.flags,
+                LazyBackend::ZeroPage,
+            ));
+        }
+    }
+    Ok(UserImage {
+        entry: bias + elf.entry(),
+        symbols: if cfg!(feature = "user-symbols") {
+            SymbolTable::from_elf(&elf, bias)
+        } else {
+            None
+        },
+    })
+}
+
+fn load_raw(
+    data: &[u8],
+    uspace: &mut AddrSpace,
+    regions: &mut LazyRegions,
+) -> Result<usize, axio::Error> {
+    let flags =
+        MappingFlags::READ | MappingFlags::WRITE | MappingFlags::EXECUTE | MappingFlags::USER;
+    let n = data.len().min(PAGE_SIZE_4K);
 
     // Map user code with eager allocation (populate=true)
     uspace
-        .map_alloc(
-            (APP_ENTRY).into(),
-            PAGE_SIZE_4K,
-            MappingFlags::READ | MappingFlags::WRITE | MappingFlags::EXECUTE | MappingFlags::USER,
-            true,
-        )
+        .map_alloc(APP_ENTRY.into(), PAGE_SIZE_4K, flags, true)
         .map_err(|_| axio::Error::NoMemory)?;
 
     // Write the loaded data into the address space
     uspace
-        .write((APP_ENTRY).into(), &buf[..n])
+        .write(APP_ENTRY.into(), &data[..n])
         .map_err(|_| axio::Error::NoMemory)?;
+    regions.insert(LazyRegion::new(
+        APP_ENTRY.into(),
+        (APP_ENTRY + PAGE_SIZE_4K).into(),
+        flags,
+        LazyBackend::OnDemand,
+    ));
 
-    ax_println!("Loaded app {} ({} bytes) at {:#x}", fname, n, APP_ENTRY);
+    Ok(APP_ENTRY)
+}
 
-    Ok(())
+/// Returns the payload compiled into the kernel with the `embed-payload`
+/// feature, or `None` without it.
+///
+/// That is the file named by `LAZYMAPPING_PAYLOAD` at build time, which
+/// `cargo xtask build` sets to the payload it built.
+pub fn embedded_app() -> Option<&'static [u8]> {
+    #[cfg(feature = "embed-payload")]
+    return Some(include_bytes!(env!("LAZYMAPPING_PAYLOAD")));
+    #[cfg(not(feature = "embed-payload"))]
+    None
 }
 
-fn load_file(fname: &str, buf: &mut [u8]) -> Result<usize, axio::Error> {
+/// Reads the whole file `fname` from the root file system.
+pub fn load_file(fname: &str) -> Result<Vec<u8>, axio::Error> {
     ax_println!("app: {}", fname);
     let ctx = ROOT_FS_CONTEXT.get().expect("Root FS not initialized");
     let file = axfs::File::open(ctx, fname).map_err(|_| axio::Error::NotFound)?;
-    let n = file.read(buf)?;
-    Ok(n)
+    let mut data = Vec::new();
+    let mut buf = [0u8; PAGE_SIZE_4K];
+    loop {
+        let n = file.read(&mut buf[..])?;
+        if n == 0 {
+            break;
+        }
+        data.extend_from_slice(&buf[..n]);
+    }
+    Ok(data)
 }
@@ -16,65 +16,274 @@ extern crate axfs;
 #[cfg(feature = "axstd")]
 extern crate axio;
 
+#[cfg(feature = "axstd")]
+mod aspace;
+#[cfg(feature = "axstd")]
+mod elf;
+#[cfg(feature = "axstd")]
+mod errno;
+// The modules on the trap paths, which deny `unwrap` and `expect`, must not
+// panic on anything the user does: a bad fault address or syscall argument
+// either fails the syscall with an errno or terminates the task, never the
+// kernel.
+#[cfg(feature = "axstd")]
+#[deny(clippy::unwrap_used, clippy::expect_used)]
+mod fault;
 #[cfg(feature = "axstd")]
 mod loader;
 #[cfg(feature = "axstd")]
+#[deny(clippy::unwrap_used, clippy::expect_used)]
+mod mm;
+#[cfg(feature = "axstd")]
+mod shared_info;
+#[cfg(feature = "axstd")]
+#[deny(clippy::unwrap_used, clippy::expect_used)]
+mod signal;
+#[cfg(feature = "axstd")]
+mod stat;
+#[cfg(feature = "axstd")]
+mod stats;
+#[cfg(feature = "axstd")]
+#[deny(clippy::unwrap_used, clippy::expect_used)]
+mod step;
+#[cfg(feature = "axstd")]
+#[deny(clippy::unwrap_used, clippy::expect_used)]
 mod syscall;
 #[cfg(feature = "axstd")]
+#[deny(clippy::unwrap_used, clippy::expect_used)]
 mod task;
 
+#[cfg(feature = "axstd")]
+const USER_ASPACE_SIZE: usize = 0x40_0000_0000; // 256 GiB
 #[cfg(feature = "axstd")]
 const USER_STACK_SIZE: usize = 0x10000;
 #[cfg(feature = "axstd")]
+const USER_STACK_MAX_SIZE: usize = 0x10_0000; // 1 MiB
+#[cfg(feature = "axstd")]
+const STACK_READAHEAD_PAGES: usize = 4; // with feature `stack-readahead`
+#[cfg(feature = "axstd")]
+const STACK_ASLR_WINDOW: usize = 0x1000_0000; // 256 MiB, with `stack-aslr`
+#[cfg(feature = "axstd")]
+const STACK_PRELOAD_VALUE: usize = 42; // at the stack base, with `preload-stack`
+#[cfg(feature = "axstd")]
 const KERNEL_STACK_SIZE: usize = 0x40000; // 256 KiB
 #[cfg(feature = "axstd")]
-const APP_ENTRY: usize = 0x1000;
+const APP_ENTRY: usize = 0x1000; // load address and entry of raw binaries
+#[cfg(feature = "axstd")]
+const USER_PIE_BASE: usize = 0x40_0000; // load base of position-independent ELFs
+#[cfg(feature = "axstd")]
+const USER_HEAP_BASE: usize = 0x1000_0000;
+#[cfg(feature = "axstd")]
+const USER_HEAP_SIZE: usize = 0x100_0000; // 16 MiB
+#[cfg(feature = "axstd")]
+const USER_INFO_ADDR: usize = 0x3f_0000_0000; // shared info page, with `info-page`
+#[cfg(feature = "axstd")]
+const USER_MMAP_BASE: usize = 0x10_0000_0000;
+#[cfg(feature = "axstd")]
+const USER_MMAP_SIZE: usize = 0x10_0000_0000; // 64 GiB
+
+#[cfg(feature = "axstd")]
+const MAX_USER_FAULTS: usize = 4096;
+#[cfg(feature = "axstd")]
+const NUM_USER_TASKS: usize = 2;
+
+/// Builds the app run by a user task: the app is loaded from `/sbin/origin`,
+/// or with the `embed-payload` feature from the payload compiled into the
+/// kernel, into a new address space, and the user stack is registered for lazy
+/// mapping.
+#[cfg(feature = "axstd")]
+fn init_user_app() -> task::UserApp {
+    use axhal::paging::{MappingFlags, PageSize};
+    use memory_addr::va;
+
+    // A new address space for user app using axmm::new_user_aspace().
+    // User space: [0x0, 0x40_0000_0000) — 256GB, below kernel space.
+    let mut uspace = axmm::new_user_aspace(va!(0x0), USER_ASPACE_SIZE).unwrap();
+
+    // Load user app binary file into address space.
+    let mut regions = mm::LazyRegions::new();
+    let loaded = match loader::embedded_app() {
+        Some(bytes) => loader::load_user_image("embedded", bytes.into(), &mut uspace, &mut regions),
+        None => loader::load_user_app("/sbin/origin", &mut uspace, &mut regions),
+    };
+    let image = match loaded {
+        Ok(image) => image,
+        Err(e) => panic!("Cannot load app! {:?}", e),
+    };
+
+    // Init user stack with LAZY mapping:
+    // No page of the stack is mapped up front. When user touches the
+    // stack, a page fault occurs, and the handler maps the page on demand,
+    // either from a fresh frame (OnDemand) or from frames committed in a
+    // SharedPages (Prealloc).
+    let layout = task::StackLayout::new(uspace.end() - USER_STACK_SIZE, USER_STACK_SIZE)
+        .with_max_size(USER_STACK_MAX_SIZE);
+    let layout = if cfg!(feature = "prealloc-stack") {
+        layout.with_prealloc(PageSize::Size4K)
+    } else {
+        layout
+    };
+    let layout = if cfg!(feature = "stack-aslr") {
+        layout.with_random_base(STACK_ASLR_WINDOW)
+    } else {
+        layout
+    };
+    let layout = if cfg!(feature = "preload-stack") {
+        layout.with_page_data(0, &STACK_PRELOAD_VALUE.to_ne_bytes())
+    } else {
+        layout
+    };
+    let layout = layout.with_lazy(!cfg!(feature = "eager-stack"));
+    let layout = if cfg!(feature = "track-dirty") {
+        layout.with_dirty_tracking()
+    } else {
+        layout
+    };
+    let layout = if cfg!(feature = "stack-readahead") {
+        layout.with_readahead(STACK_READAHEAD_PAGES)
+    } else {
+        layout
+    };
+    let layout = layout.with_first_touch_full(cfg!(feature = "stack-first-touch"));
+    let layout = if cfg!(feature = "user-args") {
+        layout.with_args(&["/sbin/origin", "lazy"], &["HOME=/"])
+    } else {
+        layout
+    };
+    let stack = layout.build().unwrap();
+    ax_println!(
+        "Mapping user stack: {:#x?} -> {:#x?}",
+        stack.bottom,
+        stack.top
+    );
+    stack.register(&mut uspace).unwrap();
+
+    ax_println!("New user address space: {:#x?}", uspace);
+
+    task::UserApp {
+        uspace,
+        regions,
+        entry: image.entry,
+        stack,
+        heap: memory_addr::VirtAddrRange::from_start_size(USER_HEAP_BASE.into(), USER_HEAP_SIZE),
+        on_fault: None,
+        // Runs on the leader thread of the task, which it can name.
+        on_exit: Some(alloc::boxed::Box::new(|code| {
+            ax_println!("{}: user exit code {}", axtask::current().id_name(), code)
+        })),
+        exit_codes: if cfg!(feature = "posix-exit-codes") {
+            task::ExitCodes::POSIX
+        } else {
+            task::ExitCodes::default()
+        },
+        // Far more than any payload takes, short of faulting in a loop.
+        max_faults: Some(MAX_USER_FAULTS),
+        symbols: image.symbols,
+        info_page: cfg!(feature = "info-page").then(|| USER_INFO_ADDR.into()),
+        flags_policy: cfg!(feature = "wx-policy").then(|| {
+            // W^X: no page is both writable and executable.
+            alloc::boxed::Box::new(|region: &mm::LazyRegion, _| {
+                if region.flags.contains(MappingFlags::WRITE) {
+                    region.flags - MappingFlags::EXECUTE
+                } else {
+                    region.flags
+                }
+            }) as mm::FlagsPolicy
+        }),
+        single_step: cfg!(feature = "single_step"),
+        heap_guard: cfg!(feature = "heap-guard"),
+    }
+}
 
 #[cfg_attr(feature = "axstd", unsafe(no_mangle))]
 fn main() {
     #[cfg(feature = "axstd")]
     {
-        use axhal::paging::MappingFlags;
-        use memory_addr::va;
+        // Once, before any user task runs.
+        #[cfg(feature = "fault_selftest")]
+        fault::self_test();
 
-        // A new address space for user app using axmm::new_user_aspace().
-        // User space: [0x0, 0x40_0000_0000) — 256GB, below kernel space.
-        let mut uspace = axmm::new_user_aspace(va!(0x0), 0x40_0000_0000).unwrap();
+        let user_bytes = mm::user_memory_in_use();
+        let start = axhal::time::monotonic_time();
 
-        // Load user app binary file into address space.
-        if let Err(e) = loader::load_user_app("/sbin/origin", &mut uspace) {
-            panic!("Cannot load app! {:?}", e);
+        // Let's kick off the user processes, each in its own address space.
+        let user_tasks = match task::spawn_user_tasks(NUM_USER_TASKS, |_| Ok(init_user_app())) {
+            Ok(tasks) => tasks,
+            Err(e) => panic!("Cannot spawn user task! {:?}", e),
+        };
+
+        // With dirty tracking, the first write to the top stack page of
+        // each task is reported, with the pc that made it. The tasks have not
+        // run yet.
+        if cfg!(feature = "track-dirty") {
+            let page = memory_addr::va!(USER_ASPACE_SIZE - axhal::mem::PAGE_SIZE_4K);
+            for user_task in &user_tasks {
+                let name = user_task.id_name();
+                let hook = alloc::boxed::Box::new(move |pc, vaddr| {
+                    ax_println!(
+                        "{}: first write to watched page at {:#x}, pc {:#x}",
+                        name,
+                        vaddr,
+                        pc
+                    )
+                });
+                if let Err(e) = task::watch_first_write(user_task, page, hook) {
+                    ax_println!("{}: cannot watch {:#x}: {:?}", user_task.id_name(), page, e);
+                }
+            }
         }
 
-        // Init user stack with LAZY mapping:
-        // Use map_alloc with populate=false to enable demand paging.
-        // When user touches the stack, a page fault occurs, and the handler
-        // allocates and maps the page on demand.
-        let ustack_top = uspace.end();
-        let ustack_vaddr = ustack_top - USER_STACK_SIZE;
+        // Wait for user processes to exit ...
+        for user_task in &user_tasks {
+            let exit_code = user_task.join();
+            ax_println!(
+                "{} exit [{:?}], page faults: {:?}",
+                user_task.name(),
+                exit_code,
+                stats::page_fault_stats(user_task)
+            );
+            if let Some(pages) = task::dirty_stack_pages(user_task) {
+                ax_println!("{} dirty stack pages: {:?}", user_task.name(), pages);
+            }
+            if cfg!(feature = "debug_faults") {
+                task::inspect_user_memory(user_task, mm::dump_regions);
+            }
+            task::reap_user_task(user_task);
+        }
+        // From spawning to reaping: compare with and without `eager-stack`.
         ax_println!(
-            "Mapping user stack: {:#x?} -> {:#x?}",
-            ustack_vaddr,
-            ustack_top
+            "user tasks ran in {:?}",
+            axhal::time::monotonic_time() - start
         );
-
-        uspace
-            .map_alloc(
-                ustack_vaddr,
-                USER_STACK_SIZE,
-                MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER,
-                false, // populate=false: lazy/demand paging
-            )
-            .unwrap();
-
-        ax_println!("New user address space: {:#x?}", uspace);
-
-        // Let's kick off the user process.
-        let user_task = task::spawn_user_task(uspace, ustack_top, ustack_vaddr);
-
-        // Wait for user process to exit ...
-        let exit_code = user_task.join();
-        ax_println!("monolithic kernel exit [{:?}] normally!", exit_code);
+        if cfg!(feature = "run-payload") {
+            // The same app once more, in a single blocking call.
+            let bytes = match loader::embedded_app() {
+                Some(bytes) => Ok(bytes.to_vec()),
+                None => loader::load_file("/sbin/origin"),
+            };
+            let result = bytes
+                .map_err(|_| task::SpawnError::BadApp)
+                .and_then(|bytes| task::run_user_payload(&bytes, &["/sbin/origin"]));
+            match result {
+                Ok(exit_code) => ax_println!("run_user_payload: exit [{}]", exit_code),
+                Err(e) => ax_println!("run_user_payload failed: {}", e),
+            }
+        }
+        // Every frame the tasks faulted in must have been freed with them.
+        let leaked = mm::user_memory_in_use().saturating_sub(user_bytes);
+        if leaked == 0 {
+            ax_println!("all user frames freed");
+        } else {
+            ax_println!("leaked {} bytes of user frames!", leaked);
+        }
+        let global = stats::global_fault_stats();
+        ax_println!(
+            "handled {} faults across {} tasks: {:?}",
+            global.faults,
+            global.tasks,
+            global
+        );
+        ax_println!("monolithic kernel exit normally!");
     }
     #[cfg(not(feature = "axstd"))]
     {
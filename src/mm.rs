@@ -0,0 +1,1311 @@
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axalloc::{UsageKind, global_allocator};
+use axerrno::{AxError, AxResult};
+use axhal::mem::{PAGE_SIZE_4K, phys_to_virt, virt_to_phys};
+use axhal::paging::{MappingFlags, PageSize};
+use axhal::trap::PageFaultFlags;
+use axmm::AddrSpace;
+use memory_addr::{MemoryAddr, PageIter4K, PhysAddr, VirtAddr, VirtAddrRange};
+
+use crate::shared_info::SharedInfo;
+
+/// Default permissions of every page in the user stack.
+pub const STACK_FLAGS: MappingFlags = MappingFlags::READ
+    .union(MappingFlags::WRITE)
+    .union(MappingFlags::USER);
+
+/// Permissions of the shared info page (see [`InfoPage`]).
+pub const INFO_FLAGS: MappingFlags = MappingFlags::READ.union(MappingFlags::USER);
+
+/// Alignment of the user stack pointer required by the ABI of the current
+/// architecture when a task starts: 16 bytes on all of x86_64, aarch64,
+/// riscv64 and loongarch64.
+pub const STACK_ALIGN: usize = 16;
+
+/// Permissions of every page in the user heap.
+pub const HEAP_FLAGS: MappingFlags = MappingFlags::READ
+    .union(MappingFlags::WRITE)
+    .union(MappingFlags::USER);
+
+/// Memory-type flags no user page is mapped with.
+///
+/// User pages are normal RAM. Without these flags, the page table of every
+/// architecture maps a page as normal, cached memory: with the `Normal`
+/// attribute index of `MAIR_EL1` on aarch64, the coherent cached `MAT` on
+/// loongarch64, neither `PCD` nor `PWT` on x86_64, and the default memory
+/// type of the physical range (no Svpbmt bits) on riscv64.
+const MEMORY_TYPE_FLAGS: MappingFlags = MappingFlags::DEVICE.union(MappingFlags::UNCACHED);
+
+/// Returns whether pages mapped with `flags` are normal, cached memory, as
+/// every user page must be (see [`MEMORY_TYPE_FLAGS`]).
+pub const fn is_normal_memory(flags: MappingFlags) -> bool {
+    !flags.intersects(MEMORY_TYPE_FLAGS)
+}
+
+const _: () = assert!(is_normal_memory(STACK_FLAGS) && is_normal_memory(HEAP_FLAGS));
+
+/// Returns whether lazily mapped user pages of `page_size` are supported on
+/// the current architecture.
+pub const fn is_supported_page_size(page_size: PageSize) -> bool {
+    match page_size {
+        PageSize::Size4K => true,
+        // Sv32 megapages are 4M, so riscv32 has no 2M pages.
+        PageSize::Size2M => !cfg!(target_arch = "riscv32"),
+        _ => false,
+    }
+}
+
+/// Physical pages allocated up front for a lazily mapped region.
+///
+/// The pages are zeroed on allocation and given back to the global
+/// allocator when the last reference is dropped.
+pub struct SharedPages {
+    /// Physical address of each page, in ascending virtual address order.
+    pub phys_pages: Vec<PhysAddr>,
+    /// Size of each page. A huge page is a physically contiguous run of 4K
+    /// frames aligned to its size.
+    pub page_size: PageSize,
+}
+
+impl SharedPages {
+    /// Allocates enough zeroed pages of `page_size` to back `size` bytes.
+    ///
+    /// Returns [`AxError::Unsupported`] if `page_size` is not supported on the
+    /// current architecture (see [`is_supported_page_size`]).
+    pub fn new(size: usize, page_size: PageSize) -> AxResult<Self> {
+        if !is_supported_page_size(page_size) {
+            return Err(AxError::Unsupported);
+        }
+        let num_pages = size.div_ceil(page_size as usize);
+        let mut pages = Self {
+            phys_pages: Vec::with_capacity(num_pages),
+            page_size,
+        };
+        for _ in 0..num_pages {
+            // On failure, the pages allocated so far are freed by `Drop`.
+            pages
+                .phys_pages
+                .push(alloc_zeroed_pages(page_size).ok_or(AxError::NoMemory)?);
+        }
+        Ok(pages)
+    }
+
+    /// Returns the number of bytes backed by the pages.
+    pub fn size(&self) -> usize {
+        self.phys_pages.len() * self.page_size as usize
+    }
+}
+
+/// A [`SharedPages`] can also back a [`LazyBackend::Custom`] region, as an
+/// example of a provider: the page at the region start is backed by the
+/// first 4K of the first page, and every page is mapped 4K at a time with the
+/// region flags, never copy-on-write.
+impl PageProvider for SharedPages {
+    fn provide_page(
+        &self,
+        region: &LazyRegion,
+        aligned_va: VirtAddr,
+    ) -> AxResult<(PhysAddr, MappingFlags)> {
+        let offset = aligned_va
+            .checked_sub_addr(region.start)
+            .ok_or(AxError::BadAddress)?;
+        let page_size = self.page_size as usize;
+        let page = self
+            .phys_pages
+            .get(offset / page_size)
+            .ok_or(AxError::BadAddress)?;
+        Ok((*page + offset % page_size, region.flags))
+    }
+
+    fn kind(&self) -> &'static str {
+        "shared"
+    }
+}
+
+impl Drop for SharedPages {
+    fn drop(&mut self) {
+        for &frame in &self.phys_pages {
+            dealloc_pages(frame, self.page_size);
+        }
+    }
+}
+
+/// A physical frame privately owned by one task, freed on drop.
+pub struct PhysFrame(PhysAddr);
+
+impl PhysFrame {
+    /// Allocates a new frame holding `data`, followed by zeros.
+    ///
+    /// `data` must not be larger than a 4K page.
+    pub fn with_data(data: &[u8]) -> Option<Self> {
+        let frame = Self(alloc_zeroed_pages(PageSize::Size4K)?);
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                data.as_ptr(),
+                phys_to_virt(frame.0).as_mut_ptr(),
+                data.len().min(PAGE_SIZE_4K),
+            )
+        };
+        Some(frame)
+    }
+
+    /// Allocates a new frame holding a copy of the 4K frame at `src`.
+    pub fn copy_of(src: PhysAddr) -> Option<Self> {
+        let frame = Self(alloc_pages(PageSize::Size4K)?);
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                phys_to_virt(src).as_ptr(),
+                phys_to_virt(frame.0).as_mut_ptr(),
+                PAGE_SIZE_4K,
+            )
+        };
+        Some(frame)
+    }
+
+    /// Returns the physical address of the frame.
+    pub const fn paddr(&self) -> PhysAddr {
+        self.0
+    }
+}
+
+impl Drop for PhysFrame {
+    fn drop(&mut self) {
+        dealloc_pages(self.0, PageSize::Size4K);
+    }
+}
+
+/// Where a [`LazyBackend::OnDemand`] region takes the zeroed frames of the
+/// pages it faults in.
+///
+/// The tasks take them from the global allocator ([`GlobalFrames`]). A test
+/// can take them from a [`FramePool`] instead, to know which frame each
+/// fault maps.
+pub trait FrameSource {
+    /// Maps the 4K page at `page` in `uspace` to a zeroed frame of the
+    /// source, with `flags`, and returns the frame. Returns `None` if the
+    /// source is out of frames or the page cannot be mapped.
+    fn map_zeroed(
+        &self,
+        uspace: &mut AddrSpace,
+        page: VirtAddr,
+        flags: MappingFlags,
+    ) -> Option<PhysAddr>;
+}
+
+/// The frames of the global allocator, allocated by the address space on
+/// the fault and freed by it on unmap: the page must be in an area of the
+/// address space registered with `AddrSpace::map_alloc`, unpopulated.
+pub struct GlobalFrames;
+
+impl FrameSource for GlobalFrames {
+    fn map_zeroed(
+        &self,
+        uspace: &mut AddrSpace,
+        page: VirtAddr,
+        flags: MappingFlags,
+    ) -> Option<PhysAddr> {
+        // The page is mapped with the flags of the area, which `SYS_MPROTECT`
+        // may have changed the region flags of since it was registered, then
+        // given `flags`.
+        if !uspace.handle_page_fault(page, PageFaultFlags::empty()) {
+            return None;
+        }
+        match uspace.page_table().query(page) {
+            Ok((frame, mapped, _)) if mapped == flags => Some(frame),
+            Ok((frame, ..)) => uspace
+                .protect(page, PAGE_SIZE_4K, flags)
+                .ok()
+                .map(|()| frame),
+            Err(_) => None,
+        }
+    }
+}
+
+/// A fixed pool of contiguous 4K frames, handed out in order: the `n`-th
+/// page mapped from the pool gets the frame at `base() + n * 4K`.
+///
+/// A frame is never handed out twice, not even once its page is unmapped,
+/// and the frames are all freed with the pool, which must outlive their
+/// mappings. The pages mapped from the pool must not be in an area of the
+/// address space, as the frames are mapped linearly.
+#[allow(dead_code)] // The demo maps from a pool in the self-test only.
+pub struct FramePool {
+    base: PhysAddr,
+    len: usize,
+    next: AtomicUsize,
+}
+
+#[allow(dead_code)] // The demo maps from a pool in the self-test only.
+impl FramePool {
+    /// Allocates a pool of `len` contiguous frames.
+    pub fn new(len: usize) -> AxResult<Self> {
+        let vaddr = global_allocator()
+            .alloc_pages(len, PAGE_SIZE_4K, UsageKind::VirtMem)
+            .map_err(|_| AxError::NoMemory)?;
+        Ok(Self {
+            base: virt_to_phys(vaddr.into()),
+            len,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Returns the first frame of the pool.
+    pub const fn base(&self) -> PhysAddr {
+        self.base
+    }
+}
+
+impl FrameSource for FramePool {
+    fn map_zeroed(
+        &self,
+        uspace: &mut AddrSpace,
+        page: VirtAddr,
+        flags: MappingFlags,
+    ) -> Option<PhysAddr> {
+        let index = self
+            .next
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |next| {
+                (next < self.len).then_some(next + 1)
+            })
+            .ok()?;
+        let frame = self.base + index * PAGE_SIZE_4K;
+        unsafe { core::ptr::write_bytes(phys_to_virt(frame).as_mut_ptr(), 0, PAGE_SIZE_4K) };
+        uspace.map_linear(page, frame, PAGE_SIZE_4K, flags).ok()?;
+        Some(frame)
+    }
+}
+
+impl Drop for FramePool {
+    fn drop(&mut self) {
+        global_allocator().dealloc_pages(
+            phys_to_virt(self.base).as_usize(),
+            self.len,
+            UsageKind::VirtMem,
+        );
+    }
+}
+
+/// The shared info page of a user task: a frame holding a
+/// [`SharedInfo`], mapped read-only into the task with [`INFO_FLAGS`].
+///
+/// As the provider of a [`LazyBackend::Custom`] region, it hands out its
+/// frame for the page at the region start, and stamps the monotonic clock
+/// into it each time it does.
+pub struct InfoPage {
+    frame: PhysFrame,
+}
+
+impl InfoPage {
+    /// Allocates the frame of the page, all fields zero.
+    pub fn new() -> AxResult<Self> {
+        let frame = PhysFrame::with_data(&[]).ok_or(AxError::NoMemory)?;
+        Ok(Self { frame })
+    }
+
+    /// Returns the kernel view of the contents of the page.
+    fn info(&self) -> *mut SharedInfo {
+        phys_to_virt(self.frame.0).as_mut_ptr().cast()
+    }
+
+    /// Sets the task id of the page.
+    pub fn set_pid(&self, pid: u64) {
+        unsafe { (&raw mut (*self.info()).pid).write_volatile(pid) };
+    }
+}
+
+impl PageProvider for InfoPage {
+    fn provide_page(
+        &self,
+        region: &LazyRegion,
+        aligned_va: VirtAddr,
+    ) -> AxResult<(PhysAddr, MappingFlags)> {
+        if aligned_va != region.start {
+            return Err(AxError::BadAddress);
+        }
+        let now = axhal::time::monotonic_time_nanos();
+        unsafe { (&raw mut (*self.info()).clock_ns).write_volatile(now) };
+        Ok((self.frame.0, region.flags))
+    }
+
+    fn kind(&self) -> &'static str {
+        "info"
+    }
+}
+
+/// A source of frames for the pages of a [`LazyBackend::Custom`] region, to
+/// plug in a demand-paging strategy of its own (e.g. compressed or remote
+/// memory) without changing the fault handler.
+///
+/// The provider owns the frames it hands out, which must stay allocated as
+/// long as it lives: unmapping or discarding a page only drops its mapping,
+/// and the next fault in the page asks the provider again.
+pub trait PageProvider: Send + Sync {
+    /// Returns the 4K frame to map the page at `aligned_va` of `region` to,
+    /// and the flags to map it with.
+    ///
+    /// The flags may not grant more than the region flags, and must map
+    /// normal memory (see [`is_normal_memory`]), or the fault handler refuses
+    /// the page. An error, or flags that deny the faulting access, make the
+    /// fault a segfault.
+    fn provide_page(
+        &self,
+        region: &LazyRegion,
+        aligned_va: VirtAddr,
+    ) -> AxResult<(PhysAddr, MappingFlags)>;
+
+    /// Returns the name of the kind of provider, as shown in region dumps.
+    fn kind(&self) -> &'static str {
+        "custom"
+    }
+}
+
+/// Where the physical frames behind a lazy region come from.
+///
+/// In all cases no page table entry exists until the user first touches a
+/// page of the region; the strategies only differ in when the memory is
+/// committed and what it initially holds.
+#[derive(Clone)]
+pub enum LazyBackend {
+    /// Frames are committed up front in a [`SharedPages`], and the fault
+    /// handler only installs the frame matching the faulting page.
+    Prealloc(Arc<SharedPages>),
+    /// A fresh zeroed frame is taken from the global allocator on first
+    /// touch (by `AddrSpace::handle_page_fault`).
+    OnDemand,
+    /// A page first touched by a read maps the shared [`zero_frame`]
+    /// read-only, and is given a fresh zeroed frame of its own only on its
+    /// first write, as a copy-on-write zero page.
+    ZeroPage,
+    /// A fresh frame is filled with the matching bytes of a file on first
+    /// touch. The region start maps to `data[file_offset]`, and the bytes
+    /// past `file_size` read as zeros.
+    FileBacked {
+        /// Contents of the whole file.
+        data: Arc<[u8]>,
+        /// Offset in `data` of the bytes at the region start.
+        file_offset: usize,
+        /// Number of bytes of the region stored in the file.
+        file_size: usize,
+    },
+    /// Each page is mapped on first touch to the frame a [`PageProvider`]
+    /// gives for it. Like a pre-allocated region, the region may not be cut,
+    /// so that the provider always sees it whole.
+    Custom(Arc<dyn PageProvider>),
+}
+
+impl LazyBackend {
+    /// Registers the range `[start, start + size)` with permissions `flags`
+    /// in `uspace` without mapping any page.
+    pub fn register(
+        &self,
+        uspace: &mut AddrSpace,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+    ) -> AxResult {
+        match self {
+            // Pages are mapped one by one from the shared frames on fault.
+            Self::Prealloc(pages) if pages.size() >= size => Ok(()),
+            Self::Prealloc(_) => Err(AxError::InvalidInput),
+            // populate=false: lazy/demand paging
+            Self::OnDemand => uspace.map_alloc(start, size, flags, false),
+            // Pages are mapped one by one from fresh frames on fault.
+            Self::ZeroPage | Self::FileBacked { .. } => Ok(()),
+            // Pages are mapped one by one from the provider on fault.
+            Self::Custom(_) => Ok(()),
+        }
+    }
+
+    /// Returns the name of the kind of backend, as shown in region dumps.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Prealloc(_) => "prealloc",
+            Self::OnDemand => "on-demand",
+            Self::ZeroPage => "zero-page",
+            Self::FileBacked { .. } => "file",
+            Self::Custom(provider) => provider.kind(),
+        }
+    }
+
+    /// Returns whether the backend indexes the pages of its region from the
+    /// region start, so that the region may only be taken whole.
+    pub const fn is_indexed(&self) -> bool {
+        matches!(self, Self::Prealloc(_) | Self::Custom(_))
+    }
+
+    /// Returns the backend of anonymous memory: [`ZeroPage`](Self::ZeroPage)
+    /// with the `zero-page` feature, [`OnDemand`](Self::OnDemand) otherwise.
+    pub const fn anonymous() -> Self {
+        if cfg!(feature = "zero-page") {
+            Self::ZeroPage
+        } else {
+            Self::OnDemand
+        }
+    }
+}
+
+/// A 4K page of zeros, in the kernel image.
+#[repr(C, align(4096))]
+struct ZeroPage([u8; PAGE_SIZE_4K]);
+
+static ZERO_PAGE: ZeroPage = ZeroPage([0; PAGE_SIZE_4K]);
+
+/// Returns the frame of zeros shared read-only by every page of a
+/// [`LazyBackend::ZeroPage`] region not written to yet.
+///
+/// The frame is part of the kernel image, so it is never allocated nor
+/// freed, and is not accounted as user memory.
+pub fn zero_frame() -> PhysAddr {
+    virt_to_phys(VirtAddr::from_ptr_of(&ZERO_PAGE))
+}
+
+/// Returns whether the page of `vaddr` is mapped to the [`zero_frame`] in
+/// `uspace`.
+pub fn is_zero_page(uspace: &AddrSpace, vaddr: VirtAddr) -> bool {
+    matches!(uspace.page_table().query(vaddr), Ok((paddr, _, _)) if paddr.align_down_4k() == zero_frame())
+}
+
+/// A demand-paged range `[start, end)` of a user address space.
+pub struct LazyRegion {
+    /// Lowest address of the region.
+    pub start: VirtAddr,
+    /// End of the region (exclusive).
+    pub end: VirtAddr,
+    /// Permissions of every page in the region.
+    pub flags: MappingFlags,
+    /// Where the frames of the region come from. With
+    /// [`LazyBackend::Prealloc`], the page at `start` is backed by the first
+    /// pre-allocated page.
+    pub backend: LazyBackend,
+    /// Whether the page at `end` is a guard page: it is never mapped, no
+    /// other region may take it, and a fault there is an overrun of the
+    /// region.
+    pub guard_page: bool,
+}
+
+impl LazyRegion {
+    /// Creates a region spanning `[start, end)` with permissions `flags`,
+    /// backed by `backend`.
+    ///
+    /// `flags` must map normal memory (see [`is_normal_memory`]).
+    pub const fn new(
+        start: VirtAddr,
+        end: VirtAddr,
+        flags: MappingFlags,
+        backend: LazyBackend,
+    ) -> Self {
+        debug_assert!(is_normal_memory(flags));
+        Self {
+            start,
+            end,
+            flags,
+            backend,
+            guard_page: false,
+        }
+    }
+
+    /// Sets whether the page at `end`, which must be page-aligned, is a guard
+    /// page of the region (see [`LazyRegion::guard_end`]).
+    pub fn with_guard_page(mut self, guard_page: bool) -> Self {
+        debug_assert!(!guard_page || self.end.is_aligned_4k());
+        self.guard_page = guard_page;
+        self
+    }
+
+    /// Returns the end of the guard page of the region, or `end` if it has
+    /// none, or if `end` is the last page boundary of the address range.
+    pub fn guard_end(&self) -> VirtAddr {
+        match self.end.checked_add(PAGE_SIZE_4K) {
+            Some(guard_end) if self.guard_page => guard_end,
+            _ => self.end,
+        }
+    }
+
+    /// Returns whether `vaddr` is inside the region.
+    pub fn contains(&self, vaddr: VirtAddr) -> bool {
+        (self.start..self.end).contains(&vaddr)
+    }
+
+    /// Returns the size of the pages the region is faulted in by: the
+    /// pre-allocated page size for a [`LazyBackend::Prealloc`] region, and 4K
+    /// otherwise.
+    ///
+    /// A fault maps, and [`UserMemory::discard`] unmaps, one such page at a
+    /// time.
+    pub fn page_size(&self) -> usize {
+        match &self.backend {
+            LazyBackend::Prealloc(pages) => pages.page_size as usize,
+            _ => PAGE_SIZE_4K,
+        }
+    }
+
+    /// Returns the number of pages of the region in the whole address
+    /// space.
+    pub fn total_pages(&self) -> usize {
+        (self.end.align_up_4k() - self.start) / PAGE_SIZE_4K
+    }
+
+    /// Returns the number of pages of the region faulted in so far in
+    /// `uspace`, that is mapped in its page table.
+    pub fn mapped_pages(&self, uspace: &AddrSpace) -> usize {
+        PageIter4K::new(self.start, self.end.align_up_4k())
+            .into_iter()
+            .flatten()
+            .filter(|&vaddr| uspace.page_table().query(vaddr).is_ok())
+            .count()
+    }
+
+    /// Returns the region with the permissions `flags` instead of its own,
+    /// and the same contents.
+    pub fn with_flags(&self, flags: MappingFlags) -> Self {
+        Self::new(self.start, self.end, flags, self.backend.clone())
+            .with_guard_page(self.guard_page)
+    }
+
+    /// Returns the part `[start, end)` of the region, which must be inside
+    /// it, with the same flags and contents. It keeps the guard page of the
+    /// region if it ends where the region does.
+    ///
+    /// A pre-allocated or custom region indexes its pages from its start, so
+    /// only the whole of it may be taken.
+    fn slice(&self, start: VirtAddr, end: VirtAddr) -> Self {
+        let backend = match &self.backend {
+            LazyBackend::FileBacked {
+                data,
+                file_offset,
+                file_size,
+            } => {
+                let skipped = start - self.start;
+                LazyBackend::FileBacked {
+                    data: data.clone(),
+                    file_offset: file_offset + skipped.min(*file_size),
+                    file_size: file_size.saturating_sub(skipped),
+                }
+            }
+            backend => {
+                debug_assert!(!backend.is_indexed() || (start, end) == (self.start, self.end));
+                backend.clone()
+            }
+        };
+        Self::new(start, end, self.flags, backend)
+            .with_guard_page(self.guard_page && end == self.end)
+    }
+}
+
+impl fmt::Display for LazyRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{:#x}, {:#x}) {:?} {}",
+            self.start,
+            self.end,
+            self.flags,
+            self.backend.kind()
+        )
+    }
+}
+
+/// The demand-paged regions of a user address space.
+///
+/// The page fault handler looks up the region containing the faulting
+/// address here; a fault that hits no region is a segmentation fault.
+/// Regions never overlap, nor take the guard page of another region.
+#[derive(Default)]
+pub struct LazyRegions {
+    regions: Vec<LazyRegion>,
+}
+
+impl LazyRegions {
+    /// Creates an empty region list.
+    pub const fn new() -> Self {
+        Self {
+            regions: Vec::new(),
+        }
+    }
+
+    /// Adds `region`, which must not overlap any existing region, guard
+    /// pages included.
+    pub fn insert(&mut self, region: LazyRegion) {
+        debug_assert!(!self.overlaps(region.start, region.guard_end()));
+        self.regions.push(region);
+    }
+
+    /// Returns the regions, in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = &LazyRegion> {
+        self.regions.iter()
+    }
+
+    /// Returns whether any region, or the guard page of one, overlaps
+    /// `[start, end)`.
+    pub fn overlaps(&self, start: VirtAddr, end: VirtAddr) -> bool {
+        self.regions
+            .iter()
+            .any(|r| r.start < end && start < r.guard_end())
+    }
+
+    /// Returns the region containing `vaddr`, if any.
+    pub fn find(&self, vaddr: VirtAddr) -> Option<&LazyRegion> {
+        self.regions.iter().find(|r| r.contains(vaddr))
+    }
+
+    /// Returns the region whose guard page contains `vaddr`, if any.
+    pub fn find_guard(&self, vaddr: VirtAddr) -> Option<&LazyRegion> {
+        self.regions
+            .iter()
+            .find(|r| (r.end..r.guard_end()).contains(&vaddr))
+    }
+
+    /// Returns whether `[start, end)` covers only part of a
+    /// [`LazyBackend::Prealloc`] or [`LazyBackend::Custom`] region, which
+    /// indexes its pages from its start address and may only be taken whole.
+    pub fn cuts_indexed(&self, start: VirtAddr, end: VirtAddr) -> bool {
+        self.regions.iter().any(|r| {
+            r.backend.is_indexed()
+                && r.start < end
+                && start < r.end
+                && (r.start < start || r.end > end)
+        })
+    }
+
+    /// Sets the permissions of `[start, end)` to `flags`, splitting the
+    /// regions that partially overlap it.
+    ///
+    /// Returns [`AxError::InvalidInput`], and changes nothing, if the range
+    /// cuts a [`LazyBackend::Prealloc`] or [`LazyBackend::Custom`] region.
+    pub fn protect(&mut self, start: VirtAddr, end: VirtAddr, flags: MappingFlags) -> AxResult {
+        debug_assert!(is_normal_memory(flags));
+        if self.cuts_indexed(start, end) {
+            return Err(AxError::InvalidInput);
+        }
+        let mut kept = Vec::with_capacity(self.regions.len() + 2);
+        for r in self.regions.drain(..) {
+            if r.end <= start || r.start >= end {
+                kept.push(r);
+                continue;
+            }
+            let (mid_start, mid_end) = (r.start.max(start), r.end.min(end));
+            if r.start < mid_start {
+                kept.push(r.slice(r.start, mid_start));
+            }
+            if mid_end < r.end {
+                kept.push(r.slice(mid_end, r.end));
+            }
+            let mut mid = r.slice(mid_start, mid_end);
+            mid.flags = flags;
+            kept.push(mid);
+        }
+        self.regions = kept;
+        Ok(())
+    }
+
+    /// Removes `[start, end)` from the regions, shrinking or splitting the
+    /// regions that partially overlap it. A region shrunk from its end loses
+    /// its guard page, and so does a region whose guard page is in the range.
+    ///
+    /// Returns [`AxError::InvalidInput`], and changes nothing, if the range
+    /// cuts a [`LazyBackend::Prealloc`] or [`LazyBackend::Custom`] region
+    /// (see [`cuts_indexed`](Self::cuts_indexed)). The part of a file-backed
+    /// region left past the range still maps the bytes of the file it did.
+    pub fn remove(&mut self, start: VirtAddr, end: VirtAddr) -> AxResult {
+        if self.cuts_indexed(start, end) {
+            return Err(AxError::InvalidInput);
+        }
+        let mut kept = Vec::with_capacity(self.regions.len() + 1);
+        for mut r in self.regions.drain(..) {
+            if r.end <= start || r.start >= end {
+                if r.end < end && start < r.guard_end() {
+                    r.guard_page = false;
+                }
+                kept.push(r);
+                continue;
+            }
+            if r.start < start {
+                kept.push(r.slice(r.start, start));
+            }
+            if r.end > end {
+                kept.push(r.slice(end, r.end));
+            }
+        }
+        self.regions = kept;
+        Ok(())
+    }
+}
+
+/// The dirty pages of a user stack.
+///
+/// The pages of `[limit, top)` are first mapped without `WRITE`, so that the
+/// first write to each of them faults, and marks it dirty (see
+/// [`handle_fault`](crate::fault::handle_fault)).
+pub struct DirtyPages {
+    top: VirtAddr,
+    limit: VirtAddr,
+    pages: BTreeSet<usize>,
+}
+
+impl DirtyPages {
+    /// Tracks the stack `[limit, top)`, with no dirty page yet.
+    pub const fn new(top: VirtAddr, limit: VirtAddr) -> Self {
+        Self {
+            top,
+            limit,
+            pages: BTreeSet::new(),
+        }
+    }
+
+    /// Returns whether `vaddr` is in the tracked stack.
+    pub fn contains(&self, vaddr: VirtAddr) -> bool {
+        (self.limit..self.top).contains(&vaddr)
+    }
+
+    /// Returns whether the page containing `vaddr`, in the tracked stack, is
+    /// dirty.
+    pub fn is_dirty(&self, vaddr: VirtAddr) -> bool {
+        self.pages
+            .contains(&((self.top - vaddr.align_down_4k()) / PAGE_SIZE_4K - 1))
+    }
+
+    /// Marks the page containing `vaddr` dirty.
+    pub fn mark(&mut self, vaddr: VirtAddr) {
+        self.pages
+            .insert((self.top - vaddr.align_down_4k()) / PAGE_SIZE_4K - 1);
+    }
+
+    /// Returns the indices of the dirty pages, counted down from the stack
+    /// top: page 0 is the top page.
+    pub const fn pages(&self) -> &BTreeSet<usize> {
+        &self.pages
+    }
+}
+
+/// Policy choosing the flags a page is mapped with when it is faulted in.
+///
+/// It is called with the region of the faulting page and the access that
+/// faulted, and returns the flags to map the page with, against which the
+/// access is then checked instead of the flags of the region. It may only
+/// take flags away: flags that grant more than the region, or that do not
+/// map normal memory (see [`is_normal_memory`]), make the fault a segfault.
+/// Without a policy, each page is mapped with the flags of its region.
+///
+/// A page mapped without `EXECUTE` cannot be executed from on any
+/// architecture: the page table sets an explicit no-execute attribute for it
+/// where there is one (`NX` on x86_64 and loongarch64, `UXN` on aarch64),
+/// and leaves out `X` on riscv64.
+///
+/// The policy runs in kernel context with the state of the task locked, and
+/// must not fault, as a [`FaultHook`](crate::task::FaultHook). It only
+/// applies to pages as they are faulted in: `SYS_MPROTECT` gives the pages
+/// of a range already mapped the new flags of their region as they are.
+pub type FlagsPolicy = Box<dyn Fn(&LazyRegion, PageFaultFlags) -> MappingFlags + Send>;
+
+/// The demand-paging state of a user task, besides its address space.
+pub struct UserMemory {
+    /// Demand-paged regions of the address space.
+    pub regions: LazyRegions,
+    /// Program break moved by `SYS_BRK`.
+    pub brk: ProgramBreak,
+    /// Window of the `SYS_MMAP` mappings.
+    pub mmap: MmapArea,
+    /// Whether pre-allocated pages are shared copy-on-write.
+    pub cow: bool,
+    /// Frames allocated by the fault handler itself, by the page they are
+    /// mapped at: the private copies of copy-on-write pages, the pages
+    /// filled from a file, and the written pages of zero-page regions.
+    pub frames: BTreeMap<VirtAddr, PhysFrame>,
+    /// Dirty pages of the stack, if they are tracked.
+    pub dirty: Option<DirtyPages>,
+    /// Policy choosing the flags of each page faulted in, if any.
+    pub flags_policy: Option<FlagsPolicy>,
+}
+
+impl UserMemory {
+    /// Returns the demand-paged regions of the task, in insertion order: the
+    /// regions of the app, the stack and its extensions, the heap, and the
+    /// `SYS_MMAP` mappings.
+    ///
+    /// The number of pages of a region faulted in so far is given by
+    /// [`LazyRegion::mapped_pages`].
+    pub fn lazy_regions(&self) -> impl Iterator<Item = &LazyRegion> {
+        self.regions.iter()
+    }
+
+    /// Unmaps the pages of `[start, start + size)` faulted in so far, so that
+    /// the next access to them faults again, as on first touch.
+    ///
+    /// The frames allocated for the range are freed: an on-demand or
+    /// zero-page page then reads as zeros again and a file-backed page as the
+    /// file contents. A pre-allocated page, a page of a custom provider, or
+    /// one mapped to the [`zero_frame`], is only unmapped, as its frame is
+    /// not the region's to free.
+    ///
+    /// Returns [`AxError::InvalidInput`] if the range is not page-aligned,
+    /// cuts a huge pre-allocated page, or is not fully covered by regions.
+    pub fn discard(&mut self, uspace: &mut AddrSpace, start: VirtAddr, size: usize) -> AxResult {
+        let end = start.checked_add(size).ok_or(AxError::InvalidInput)?;
+        if !start.is_aligned_4k() || !size.is_multiple_of(PAGE_SIZE_4K) {
+            return Err(AxError::InvalidInput);
+        }
+        // Regions never overlap, so the parts of the range they cover add up
+        // to its size only if the range is fully covered.
+        let parts: Vec<_> = self
+            .regions
+            .iter()
+            .filter(|r| r.start < end && r.end > start)
+            .map(|r| {
+                let on_demand = matches!(r.backend, LazyBackend::OnDemand);
+                let part = (r.start.max(start), r.end.min(end), r.flags);
+                (part, on_demand, r.page_size())
+            })
+            .collect();
+        if parts.iter().map(|&((s, e, _), ..)| e - s).sum::<usize>() != size {
+            return Err(AxError::InvalidInput);
+        }
+        // A huge page is mapped and unmapped as a whole, so it may not be
+        // cut by the range.
+        let cut = parts
+            .iter()
+            .any(|&((s, e, _), _, page_size)| !s.is_aligned(page_size) || !e.is_aligned(page_size));
+        if cut {
+            return Err(AxError::InvalidInput);
+        }
+        for ((part_start, part_end, flags), on_demand, page_size) in parts {
+            let part_size = part_end - part_start;
+            if on_demand {
+                // Unmapping frees the frames of the area, so the range is
+                // registered again to be demand-paged.
+                uspace.unmap(part_start, part_size)?;
+                uspace.map_alloc(part_start, part_size, flags, false)?;
+                continue;
+            }
+            for page in (part_start.as_usize()..part_end.as_usize()).step_by(page_size) {
+                let page = VirtAddr::from(page);
+                if uspace.page_table().query(page).is_ok() {
+                    uspace.unmap(page, page_size)?;
+                }
+                self.frames.remove(&page);
+            }
+        }
+        Ok(())
+    }
+
+    /// Changes the permissions of `[start, start + size)` to `flags`, as
+    /// `SYS_MPROTECT` does.
+    ///
+    /// The regions covering the range are split at its bounds, and the part
+    /// inside it takes `flags`. The pages of the range faulted in so far are
+    /// updated in place if their frame is private, keeping `WRITE` off a
+    /// dirty-tracked page not written yet, so that its first write still
+    /// faults. A page mapped to a shared frame (the [`zero_frame`], or a
+    /// pre-allocated page shared copy-on-write) is unmapped instead, to fault
+    /// again with the new permissions, and so is a page of a custom provider,
+    /// which chooses the flags of its pages.
+    ///
+    /// Returns [`AxError::NoMemory`] if the range is not fully covered by
+    /// regions, and [`AxError::InvalidInput`] if it is not page-aligned or
+    /// cuts a pre-allocated or custom region.
+    pub fn protect(
+        &mut self,
+        uspace: &mut AddrSpace,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+    ) -> AxResult {
+        let end = start.checked_add(size).ok_or(AxError::InvalidInput)?;
+        if !start.is_aligned_4k() || !size.is_multiple_of(PAGE_SIZE_4K) {
+            return Err(AxError::InvalidInput);
+        }
+        let covered: usize = self
+            .regions
+            .iter()
+            .filter(|r| r.start < end && r.end > start)
+            .map(|r| r.end.min(end) - r.start.max(start))
+            .sum();
+        if covered != size {
+            return Err(AxError::NoMemory);
+        }
+        self.regions.protect(start, end, flags)?;
+        let Some(pages) = PageIter4K::new(start, end) else {
+            return Ok(());
+        };
+        for page in pages {
+            let Ok((paddr, _, _)) = uspace.page_table().query(page) else {
+                continue;
+            };
+            let backend = self.regions.find(page).map(|r| &r.backend);
+            let prealloc = matches!(backend, Some(LazyBackend::Prealloc(_)));
+            let custom = matches!(backend, Some(LazyBackend::Custom(_)));
+            let shared = paddr.align_down_4k() == zero_frame()
+                || (prealloc && self.cow && !self.frames.contains_key(&page))
+                || custom;
+            if shared {
+                uspace.unmap(page, PAGE_SIZE_4K)?;
+                continue;
+            }
+            let clean = self
+                .dirty
+                .as_ref()
+                .is_some_and(|dirty| dirty.contains(page) && !dirty.is_dirty(page));
+            let page_flags = if clean {
+                flags - MappingFlags::WRITE
+            } else {
+                flags
+            };
+            uspace.protect(page, PAGE_SIZE_4K, page_flags)?;
+        }
+        Ok(())
+    }
+}
+
+/// The program break of a user address space, moved by `SYS_BRK`.
+///
+/// The heap spans `[base, brk)`. Growing it only registers the new whole
+/// pages in the address space with `populate=false`, so frames are allocated
+/// by the page fault handler on first touch, exactly like the lazy stack.
+///
+/// The pages of the heap window past the break may be a guard, where a fault
+/// is an overrun of the heap rather than a page to map (see
+/// [`is_overrun`](Self::is_overrun)).
+pub struct ProgramBreak {
+    base: VirtAddr,
+    brk: VirtAddr,
+    limit: VirtAddr,
+    guard: bool,
+}
+
+impl ProgramBreak {
+    /// Creates an empty heap starting at `base` that may grow up to
+    /// `max_size` bytes, with no guard past the break.
+    pub fn new(base: VirtAddr, max_size: usize) -> Self {
+        Self {
+            base,
+            brk: base,
+            limit: base + max_size,
+            guard: false,
+        }
+    }
+
+    /// Sets whether the pages of the heap window past the break are a guard.
+    pub const fn with_guard(mut self, guard: bool) -> Self {
+        self.guard = guard;
+        self
+    }
+
+    /// Returns the start of the heap.
+    pub const fn base(&self) -> VirtAddr {
+        self.base
+    }
+
+    /// Returns the current program break.
+    pub const fn current(&self) -> VirtAddr {
+        self.brk
+    }
+
+    /// Returns whether `vaddr` overruns the heap: whether it is in a page of
+    /// the heap window past the one the break is in, the heap being guarded.
+    pub fn is_overrun(&self, vaddr: VirtAddr) -> bool {
+        self.guard && (self.brk.align_up_4k()..self.limit).contains(&vaddr)
+    }
+
+    /// Moves the program break to `new_brk`.
+    ///
+    /// Returns the new break on success. If `new_brk` is out of the heap
+    /// limits or the address space cannot be updated, the break is left
+    /// untouched and the old value is returned, as Linux does.
+    pub fn set(&mut self, uspace: &mut AddrSpace, new_brk: VirtAddr) -> VirtAddr {
+        if new_brk < self.base || new_brk > self.limit {
+            return self.brk;
+        }
+        let old_end = self.brk.align_up_4k();
+        let new_end = new_brk.align_up_4k();
+        let res = if new_end > old_end {
+            uspace.map_alloc(old_end, new_end - old_end, HEAP_FLAGS, false)
+        } else if new_end < old_end {
+            uspace.unmap(new_end, old_end - new_end)
+        } else {
+            Ok(())
+        };
+        if res.is_ok() {
+            self.brk = new_brk;
+        }
+        self.brk
+    }
+}
+
+/// The window of a user address space where `SYS_MMAP` places anonymous
+/// mappings.
+///
+/// Mappings are registered with `populate=false`, so like the heap they are
+/// demand-paged by `AddrSpace`'s fault handler, with the permissions given
+/// at map time. With the `zero-page` feature, they are
+/// [`LazyBackend::ZeroPage`] regions instead, whose pages the fault handler
+/// maps itself.
+///
+/// Each mapping may be followed by a guard page, which a later mapping placed
+/// by the kernel does not take (see [`LazyRegion::guard_page`]).
+pub struct MmapArea {
+    range: VirtAddrRange,
+    guard_pages: bool,
+}
+
+impl MmapArea {
+    /// Creates an mmap window spanning `[base, base + size)`, whose mappings
+    /// have no guard page.
+    pub fn new(base: VirtAddr, size: usize) -> Self {
+        Self {
+            range: VirtAddrRange::from_start_size(base, size),
+            guard_pages: false,
+        }
+    }
+
+    /// Sets whether each mapping is followed by a guard page.
+    pub const fn with_guard_pages(mut self, guard_pages: bool) -> Self {
+        self.guard_pages = guard_pages;
+        self
+    }
+
+    /// Registers a lazy anonymous mapping of `len` bytes with permissions
+    /// `flags`, at `hint` if possible or else at the first free address of the
+    /// window, and adds it to `regions`.
+    ///
+    /// Returns the start of the mapping, or `None` if no free range is large
+    /// enough.
+    pub fn map(
+        &self,
+        uspace: &mut AddrSpace,
+        regions: &mut LazyRegions,
+        hint: VirtAddr,
+        len: usize,
+        flags: MappingFlags,
+    ) -> Option<VirtAddr> {
+        let size = len.checked_add(PAGE_SIZE_4K - 1)? & !(PAGE_SIZE_4K - 1);
+        if size == 0 {
+            return None;
+        }
+        // The guard page is left free along with the mapping.
+        let span = if self.guard_pages {
+            size.checked_add(PAGE_SIZE_4K)?
+        } else {
+            size
+        };
+        let mut hint = hint.align_down_4k().max(self.range.start);
+        // A zero-page region is only known to `uspace` by the pages mapped in
+        // it so far, so the free range must also miss every region, and the
+        // guard pages.
+        let start = loop {
+            let start = uspace.find_free_area(hint, span, self.range)?;
+            let end = start.checked_add(span)?;
+            match regions
+                .iter()
+                .filter(|r| r.start < end && start < r.guard_end())
+                .map(|r| r.guard_end())
+                .max()
+            {
+                Some(past) => hint = past,
+                None => break start,
+            }
+        };
+        let backend = LazyBackend::anonymous();
+        backend.register(uspace, start, size, flags).ok()?;
+        regions.insert(
+            LazyRegion::new(start, start + size, flags, backend).with_guard_page(self.guard_pages),
+        );
+        Some(start)
+    }
+
+    /// Registers a lazy anonymous mapping of `len` bytes with permissions
+    /// `flags` at exactly `start`, and adds it to `regions`.
+    ///
+    /// The mappings already in the range are removed first, as with
+    /// [`unmap`](Self::unmap), so their contents are lost. A fixed mapping
+    /// gets no guard page, as the page after it may be taken. Returns the
+    /// start of the mapping, or `None` if `start` is not page-aligned or the
+    /// range is not inside the window.
+    pub fn map_fixed(
+        &self,
+        uspace: &mut AddrSpace,
+        regions: &mut LazyRegions,
+        frames: &mut BTreeMap<VirtAddr, PhysFrame>,
+        start: VirtAddr,
+        len: usize,
+        flags: MappingFlags,
+    ) -> Option<VirtAddr> {
+        self.unmap(uspace, regions, frames, start, len).ok()?;
+        let size = len.next_multiple_of(PAGE_SIZE_4K);
+        let backend = LazyBackend::anonymous();
+        backend.register(uspace, start, size, flags).ok()?;
+        regions.insert(LazyRegion::new(start, start + size, flags, backend));
+        Some(start)
+    }
+
+    /// Removes the mappings in `[start, start + len)` from `uspace` and
+    /// `regions`, freeing the frames that were faulted in, `frames` holding
+    /// those of zero-page regions.
+    ///
+    /// The range must be page-aligned at `start` and inside the window, and
+    /// may not cut a pre-allocated or custom region there (see
+    /// [`LazyRegions::cuts_indexed`]): [`AxError::InvalidInput`] otherwise.
+    pub fn unmap(
+        &self,
+        uspace: &mut AddrSpace,
+        regions: &mut LazyRegions,
+        frames: &mut BTreeMap<VirtAddr, PhysFrame>,
+        start: VirtAddr,
+        len: usize,
+    ) -> AxResult {
+        let size = len
+            .checked_add(PAGE_SIZE_4K - 1)
+            .ok_or(AxError::InvalidInput)?
+            & !(PAGE_SIZE_4K - 1);
+        let end = start.checked_add(size).ok_or(AxError::InvalidInput)?;
+        if size == 0
+            || !start.is_aligned_4k()
+            || !self.range.contains_range(VirtAddrRange::new(start, end))
+        {
+            return Err(AxError::InvalidInput);
+        }
+        // Checked before anything is unmapped, so that a failed call changes
+        // nothing.
+        if regions.cuts_indexed(start, end) {
+            return Err(AxError::InvalidInput);
+        }
+        uspace.unmap(start, size)?;
+        regions.remove(start, end)?;
+        frames.retain(|&page, _| !(start..end).contains(&page));
+        Ok(())
+    }
+}
+
+/// The mapping of a page just faulted in, as logged by the fault handler:
+/// the faulting address, the start of its page, the frame the page is mapped
+/// to, and the index of the page in its region.
+///
+/// With a pre-allocated region, the index is that of the frame in its
+/// [`SharedPages`], so consecutive pages must show consecutive indices, each
+/// with its own frame.
+pub struct PageMapping {
+    vaddr: VirtAddr,
+    page: VirtAddr,
+    paddr: Option<PhysAddr>,
+    index: Option<usize>,
+}
+
+impl PageMapping {
+    /// Looks up the mapping of the page of `vaddr` in `uspace`, and its index
+    /// in the region of `regions` containing it, in pages of the region page
+    /// size (see [`LazyRegion::page_size`]).
+    pub fn new(uspace: &AddrSpace, regions: &LazyRegions, vaddr: VirtAddr) -> Self {
+        let region = regions.find(vaddr);
+        let page_size = region.map_or(PAGE_SIZE_4K, LazyRegion::page_size);
+        let page = vaddr.align_down(page_size);
+        Self {
+            vaddr,
+            page,
+            paddr: uspace
+                .page_table()
+                .query(page)
+                .ok()
+                .map(|(paddr, ..)| paddr),
+            index: region.and_then(|r| Some(page.checked_sub_addr(r.start)? / page_size)),
+        }
+    }
+}
+
+impl fmt::Display for PageMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mapped va={:#x} page={:#x} -> ", self.vaddr, self.page)?;
+        match self.paddr {
+            Some(paddr) => write!(f, "pa={:#x}", paddr)?,
+            None => write!(f, "pa=none")?,
+        }
+        match self.index {
+            Some(index) => write!(f, " (idx {})", index),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Prints the regions of `mem`, each followed by the pages of it currently
+/// mapped in `uspace`, as found by walking the page table.
+///
+/// Runs of pages contiguous both in virtual and physical memory, and mapped
+/// with the same flags, are printed as one range.
+pub fn dump_regions(uspace: &AddrSpace, mem: &UserMemory) {
+    ax_println!("lazy regions:");
+    for region in mem.lazy_regions() {
+        ax_println!("    {}", region);
+        // The current run: its virtual range, first frame and flags.
+        let mut run: Option<(VirtAddr, VirtAddr, PhysAddr, MappingFlags)> = None;
+        let pages = PageIter4K::new(region.start, region.end.align_up_4k()).into_iter();
+        for vaddr in pages.flatten() {
+            let Ok((paddr, flags, _)) = uspace.page_table().query(vaddr) else {
+                continue;
+            };
+            match &mut run {
+                Some((start, end, pstart, run_flags))
+                    if *end == vaddr
+                        && *pstart + (vaddr - *start) == paddr
+                        && *run_flags == flags =>
+                {
+                    *end = vaddr + PAGE_SIZE_4K;
+                }
+                _ => {
+                    if let Some(run) = run.replace((vaddr, vaddr + PAGE_SIZE_4K, paddr, flags)) {
+                        print_run(run);
+                    }
+                }
+            }
+        }
+        if let Some(run) = run {
+            print_run(run);
+        }
+        ax_println!(
+            "        {} of {} pages faulted in",
+            region.mapped_pages(uspace),
+            region.total_pages()
+        );
+    }
+}
+
+fn print_run((start, end, paddr, flags): (VirtAddr, VirtAddr, PhysAddr, MappingFlags)) {
+    ax_println!(
+        "        [{:#x}, {:#x}) -> {:#x}, {} pages, {:?}",
+        start,
+        end,
+        paddr,
+        (end - start) / PAGE_SIZE_4K,
+        flags
+    );
+}
+
+/// Returns the number of bytes of user memory frames currently allocated,
+/// by `AddrSpace` or by the fault handler.
+pub fn user_memory_in_use() -> usize {
+    global_allocator().usages().get(UsageKind::VirtMem)
+}
+
+fn alloc_pages(page_size: PageSize) -> Option<PhysAddr> {
+    let size = page_size as usize;
+    let vaddr = global_allocator()
+        .alloc_pages(size / PAGE_SIZE_4K, size, UsageKind::VirtMem)
+        .ok()?;
+    Some(virt_to_phys(vaddr.into()))
+}
+
+fn alloc_zeroed_pages(page_size: PageSize) -> Option<PhysAddr> {
+    let frame = alloc_pages(page_size)?;
+    unsafe { core::ptr::write_bytes(phys_to_virt(frame).as_mut_ptr(), 0, page_size as usize) };
+    Some(frame)
+}
+
+fn dealloc_pages(frame: PhysAddr, page_size: PageSize) {
+    global_allocator().dealloc_pages(
+        phys_to_virt(frame).as_usize(),
+        page_size as usize / PAGE_SIZE_4K,
+        UsageKind::VirtMem,
+    );
+}
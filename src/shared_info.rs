@@ -0,0 +1,17 @@
+//! Layout of the shared info page, which the kernel maps read-only into a
+//! user task with feature `info-page` and passes the address of in arg0.
+//!
+//! This file is built into the payloads as well, so it uses `core` only.
+
+// The kernel writes every field, but a payload may not read them all.
+#![allow(dead_code)]
+
+/// Contents of the shared info page, at its start.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct SharedInfo {
+    /// Id of the task, as returned to its leader thread by `SYS_GETPID`.
+    pub pid: u64,
+    /// Monotonic time, in nanoseconds, when the page was last faulted in.
+    pub clock_ns: u64,
+}
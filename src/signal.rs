@@ -0,0 +1,136 @@
+use axhal::uspace::UserContext;
+use axmm::AddrSpace;
+use memory_addr::{MemoryAddr, VirtAddr};
+
+use crate::mm::UserMemory;
+use crate::syscall::copy_to_user;
+
+/// Signal number of a segmentation fault.
+pub const SIGSEGV: usize = 11;
+/// Signal number of an illegal instruction.
+pub const SIGILL: usize = 4;
+/// Signal number of a kill that cannot be caught.
+pub const SIGKILL: usize = 9;
+
+/// `si_code` of a fault on an address with no mapping.
+pub const SEGV_MAPERR: i32 = 1;
+/// `si_code` of a fault denied by the permissions of the mapping.
+pub const SEGV_ACCERR: i32 = 2;
+
+/// `sa_handler` values selecting the default action.
+const SIG_DFL: usize = 0;
+const SIG_IGN: usize = 1;
+
+/// Size of a `siginfo_t`.
+const SIGINFO_SIZE: usize = 128;
+
+/// The signal state of a user task.
+///
+/// Only `SIGSEGV` can be caught. While its handler runs, the interrupted user
+/// context is kept here, and `SYS_RT_SIGRETURN` resumes it. A fault taken
+/// while the handler runs is not delivered again: it terminates the task.
+#[derive(Default)]
+pub struct SignalState {
+    segv_handler: usize,
+    interrupted: Option<UserContext>,
+}
+
+impl SignalState {
+    /// Returns the signal state of a thread created by the current one: it
+    /// has the same handler, but is not handling a signal.
+    pub const fn for_new_thread(&self) -> Self {
+        Self {
+            segv_handler: self.segv_handler,
+            interrupted: None,
+        }
+    }
+
+    /// Returns the `SIGSEGV` handler.
+    pub const fn segv_handler(&self) -> usize {
+        self.segv_handler
+    }
+
+    /// Installs `handler` as the `SIGSEGV` handler, and returns the previous
+    /// one. `SIG_DFL` (0) restores the default action, terminating the task.
+    pub fn set_segv_handler(&mut self, handler: usize) -> usize {
+        core::mem::replace(&mut self.segv_handler, handler)
+    }
+
+    /// Delivers `SIGSEGV` for a fault at `fault_addr` to the user handler, if
+    /// one is installed and not already running.
+    ///
+    /// A `siginfo_t` carrying `code` and `fault_addr` is pushed onto the user
+    /// stack, and `uctx` is redirected to `handler(SIGSEGV, &siginfo, 0)`.
+    /// The handler must not return: it either exits or calls
+    /// `SYS_RT_SIGRETURN`. Returns whether the signal was delivered; if not,
+    /// the task must be terminated.
+    pub fn deliver_segv(
+        &mut self,
+        uctx: &mut UserContext,
+        uspace: &mut AddrSpace,
+        mem: &mut UserMemory,
+        fault_addr: VirtAddr,
+        code: i32,
+    ) -> bool {
+        if matches!(self.segv_handler, SIG_DFL | SIG_IGN) || self.interrupted.is_some() {
+            return false;
+        }
+        // struct siginfo { int si_signo; int si_errno; int si_code; void *si_addr; ... }
+        let mut siginfo = [0u8; SIGINFO_SIZE];
+        siginfo[0..4].copy_from_slice(&(SIGSEGV as i32).to_ne_bytes());
+        siginfo[8..12].copy_from_slice(&code.to_ne_bytes());
+        siginfo[16..16 + size_of::<usize>()].copy_from_slice(&fault_addr.as_usize().to_ne_bytes());
+
+        let Some(frame) = uctx
+            .sp()
+            .checked_sub(SIGINFO_SIZE)
+            .map(|sp| VirtAddr::from(sp).align_down(16usize))
+        else {
+            return false;
+        };
+        if copy_to_user(uspace, mem, frame, &siginfo).is_err() {
+            return false;
+        }
+
+        let interrupted = *uctx;
+        // The handler must not return, so its return address is 0. On x86_64
+        // it is pushed on the stack, which is 8 mod 16 on function entry.
+        #[cfg(target_arch = "x86_64")]
+        let handler_sp = {
+            let Some(ret_addr) = frame.checked_sub(size_of::<usize>()) else {
+                return false;
+            };
+            if copy_to_user(uspace, mem, ret_addr, &0usize.to_ne_bytes()).is_err() {
+                return false;
+            }
+            ret_addr
+        };
+        #[cfg(not(target_arch = "x86_64"))]
+        let handler_sp = {
+            uctx.set_ra(0);
+            frame
+        };
+
+        self.interrupted = Some(interrupted);
+        uctx.set_ip(self.segv_handler);
+        uctx.set_sp(handler_sp.as_usize());
+        uctx.set_arg0(SIGSEGV);
+        uctx.set_arg1(frame.as_usize());
+        uctx.set_arg2(0);
+        true
+    }
+
+    /// Resumes the context interrupted by the signal being handled.
+    ///
+    /// Returns `false`, leaving `uctx` untouched, if no signal is being
+    /// handled.
+    pub fn sigreturn(&mut self, uctx: &mut UserContext) -> bool {
+        match self.interrupted.take() {
+            Some(interrupted) => {
+                *uctx = interrupted;
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Layout of the `struct stat` written by `SYS_FSTAT`.
+//!
+//! The `fstat` payload includes this file by path to read the structure back,
+//! so it must not depend on anything outside `core`.
+
+// The kernel never uses `S_IFMT`, nor the payload most fields.
+#![allow(dead_code)]
+
+/// Mask of the file type bits of `st_mode`.
+pub const S_IFMT: u32 = 0o170000;
+/// File type of a character device.
+pub const S_IFCHR: u32 = 0o020000;
+
+/// `struct stat` of the generic syscall ABI, which the kernel follows on
+/// every architecture, as for the syscall numbers: 128 bytes on 64-bit
+/// targets, with no implicit padding.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct Stat {
+    pub st_dev: usize,
+    pub st_ino: usize,
+    pub st_mode: u32,
+    pub st_nlink: u32,
+    pub st_uid: u32,
+    pub st_gid: u32,
+    pub st_rdev: usize,
+    pub __pad1: usize,
+    pub st_size: isize,
+    pub st_blksize: i32,
+    pub __pad2: i32,
+    pub st_blocks: isize,
+    pub st_atime: isize,
+    pub st_atime_nsec: usize,
+    pub st_mtime: isize,
+    pub st_mtime_nsec: usize,
+    pub st_ctime: isize,
+    pub st_ctime_nsec: usize,
+    pub __unused4: u32,
+    pub __unused5: u32,
+}
+
+// Every byte of the structure belongs to a field: 12 words, and 8 fields of
+// 4 bytes.
+const _: () = assert!(size_of::<Stat>() == 12 * size_of::<usize>() + 8 * 4);
+
+impl Stat {
+    /// Returns the bytes of the structure, as user space reads them.
+    pub fn as_bytes(&self) -> &[u8] {
+        // The structure has no padding, so every byte of it is initialized.
+        unsafe {
+            core::slice::from_raw_parts((self as *const Self).cast::<u8>(), size_of::<Self>())
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use axhal::mem::PAGE_SIZE_4K;
+use axsync::Mutex;
+use axtask::AxTaskRef;
+use memory_addr::{MemoryAddr, VirtAddr};
+
+use crate::mm;
+
+/// A snapshot of the page fault counters of a user task.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PageFaultStats {
+    /// Number of page faults taken by the task.
+    pub faults: usize,
+    /// Number of distinct pages mapped by the fault handler.
+    pub pages_mapped: usize,
+    /// Number of faults that terminated the task.
+    pub segfaults: usize,
+    /// Number of interrupts (timer ticks with the `preempt` feature) taken
+    /// while the task ran in user space.
+    pub interrupts: usize,
+    /// Number of pages mapped to the shared zero frame, and so backed by no
+    /// frame of their own (see [`LazyBackend::ZeroPage`]).
+    ///
+    /// [`LazyBackend::ZeroPage`]: crate::mm::LazyBackend::ZeroPage
+    pub zero_pages: usize,
+    /// Lowest stack page mapped by the fault handler, if any: the task
+    /// touched the stack from this address up to the stack top.
+    pub stack_watermark: Option<VirtAddr>,
+    /// Total time spent mapping pages in the fault handler. Only measured
+    /// with the `fault_timing` feature.
+    pub fault_time: Duration,
+    /// Longest time spent mapping the page of a single fault. Only measured
+    /// with the `fault_timing` feature.
+    pub max_fault_time: Duration,
+}
+
+/// A snapshot of the page fault counters of every user task spawned, see
+/// [`global_fault_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GlobalFaultStats {
+    /// Number of user tasks spawned, not counting their threads.
+    pub tasks: usize,
+    /// Number of page faults taken by all threads of all tasks.
+    pub faults: usize,
+    /// Number of pages mapped by the fault handler.
+    pub pages_mapped: usize,
+    /// Number of those pages mapped to a frame of their own (a fresh frame,
+    /// a private copy or a pre-allocated frame), not to the shared zero
+    /// frame.
+    pub frames_allocated: usize,
+    /// Number of faults that terminated a task.
+    pub segfaults: usize,
+    /// Highest number of 4K frames of user memory in use (see
+    /// [`mm::user_memory_in_use`]) right after a page was mapped.
+    pub peak_resident_pages: usize,
+}
+
+/// Kernel-wide counters, updated along with the counters of each thread, so
+/// that each of them is the sum of the matching counter of every thread.
+struct GlobalCounters {
+    tasks: AtomicUsize,
+    faults: AtomicUsize,
+    pages_mapped: AtomicUsize,
+    zero_pages: AtomicUsize,
+    segfaults: AtomicUsize,
+    peak_resident_pages: AtomicUsize,
+}
+
+static GLOBAL: GlobalCounters = GlobalCounters {
+    tasks: AtomicUsize::new(0),
+    faults: AtomicUsize::new(0),
+    pages_mapped: AtomicUsize::new(0),
+    zero_pages: AtomicUsize::new(0),
+    segfaults: AtomicUsize::new(0),
+    peak_resident_pages: AtomicUsize::new(0),
+};
+
+/// Live page fault counters of a user task, updated by its fault handler.
+#[derive(Default)]
+pub struct FaultCounters {
+    faults: AtomicUsize,
+    pages_mapped: AtomicUsize,
+    segfaults: AtomicUsize,
+    interrupts: AtomicUsize,
+    zero_pages: AtomicUsize,
+    /// Lowest stack page mapped so far, or 0 if none is.
+    stack_watermark: AtomicUsize,
+    fault_nanos: AtomicU64,
+    max_fault_nanos: AtomicU64,
+}
+
+impl FaultCounters {
+    /// Records a page fault taken by the task, and returns the number of
+    /// faults taken so far.
+    pub fn fault(&self) -> usize {
+        GLOBAL.faults.fetch_add(1, Ordering::Relaxed);
+        self.faults.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Records a page mapped by the fault handler.
+    pub fn page_mapped(&self) {
+        GLOBAL.pages_mapped.fetch_add(1, Ordering::Relaxed);
+        let resident = mm::user_memory_in_use() / PAGE_SIZE_4K;
+        GLOBAL
+            .peak_resident_pages
+            .fetch_max(resident, Ordering::Relaxed);
+        self.pages_mapped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a fault that could not be handled.
+    pub fn segfault(&self) {
+        GLOBAL.segfaults.fetch_add(1, Ordering::Relaxed);
+        self.segfaults.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records an interrupt taken in user space.
+    pub fn interrupt(&self) {
+        self.interrupts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a page mapped to the shared zero frame.
+    pub fn zero_page_mapped(&self) {
+        GLOBAL.zero_pages.fetch_add(1, Ordering::Relaxed);
+        self.zero_pages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a stack page mapped by the fault handler for a fault at
+    /// `vaddr`, lowering the stack watermark if needed.
+    pub fn stack_fault(&self, vaddr: VirtAddr) {
+        let page = vaddr.align_down_4k().as_usize();
+        // The user stack never reaches the page at address 0, so 0 is free
+        // to mean "no stack page yet".
+        let _ = self
+            .stack_watermark
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |low| {
+                (low == 0 || page < low).then_some(page)
+            });
+    }
+
+    /// Records `time` spent handling a single fault.
+    pub fn fault_time(&self, time: Duration) {
+        let nanos = time.as_nanos() as u64;
+        self.fault_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_fault_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    /// Returns the current value of all counters.
+    pub fn snapshot(&self) -> PageFaultStats {
+        PageFaultStats {
+            faults: self.faults.load(Ordering::Relaxed),
+            pages_mapped: self.pages_mapped.load(Ordering::Relaxed),
+            segfaults: self.segfaults.load(Ordering::Relaxed),
+            interrupts: self.interrupts.load(Ordering::Relaxed),
+            zero_pages: self.zero_pages.load(Ordering::Relaxed),
+            stack_watermark: match self.stack_watermark.load(Ordering::Relaxed) {
+                0 => None,
+                page => Some(page.into()),
+            },
+            fault_time: Duration::from_nanos(self.fault_nanos.load(Ordering::Relaxed)),
+            max_fault_time: Duration::from_nanos(self.max_fault_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Counters of every spawned user task, keyed by task id.
+///
+/// Entries outlive the tasks, so the counters can still be read after the
+/// task has exited.
+static TASK_STATS: Mutex<BTreeMap<u64, Arc<FaultCounters>>> = Mutex::new(BTreeMap::new());
+
+/// Attaches `counters` to `task`.
+pub fn register(task: &AxTaskRef, counters: Arc<FaultCounters>) {
+    TASK_STATS.lock().insert(task.id().as_u64(), counters);
+}
+
+/// Returns the page fault counters of the user task `task`.
+///
+/// Returns all zeros if `task` is not a user task.
+pub fn page_fault_stats(task: &AxTaskRef) -> PageFaultStats {
+    TASK_STATS
+        .lock()
+        .get(&task.id().as_u64())
+        .map(|counters| counters.snapshot())
+        .unwrap_or_default()
+}
+
+/// Records a user task spawned, once for its leader thread.
+pub fn task_spawned() {
+    GLOBAL.tasks.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns the page fault counters summed over every user task spawned so
+/// far, exited ones and threads included.
+///
+/// Each counter is read on its own, so a snapshot taken while tasks run may
+/// mix values from before and after a fault.
+pub fn global_fault_stats() -> GlobalFaultStats {
+    let pages_mapped = GLOBAL.pages_mapped.load(Ordering::Relaxed);
+    GlobalFaultStats {
+        tasks: GLOBAL.tasks.load(Ordering::Relaxed),
+        faults: GLOBAL.faults.load(Ordering::Relaxed),
+        pages_mapped,
+        frames_allocated: pages_mapped.saturating_sub(GLOBAL.zero_pages.load(Ordering::Relaxed)),
+        segfaults: GLOBAL.segfaults.load(Ordering::Relaxed),
+        peak_resident_pages: GLOBAL.peak_resident_pages.load(Ordering::Relaxed),
+    }
+}
@@ -0,0 +1,107 @@
+//! Single-stepping of user code, for debugging.
+//!
+//! Where the architecture has a user-level single-step mechanism, each user
+//! instruction traps back to the kernel once it has run, as an exception
+//! that [`is_step_trap`] recognizes: the trap flag of `RFLAGS` on x86_64, and
+//! the software step of the debug architecture on aarch64. Neither riscv64
+//! nor loongarch64 offers one to the supervisor without help from firmware
+//! or a debugger, so stepping is not supported there.
+
+use axhal::mem::{PAGE_SIZE_4K, phys_to_virt};
+use axhal::uspace::{ReturnReason, UserContext};
+use axmm::AddrSpace;
+use memory_addr::{MemoryAddr, VirtAddr};
+
+/// Number of bytes shown at the pc of each step: an instruction of any
+/// architecture but x86_64, where at least its start.
+const STEP_BYTES: usize = 8;
+
+/// Returns whether single-stepping is supported on this architecture.
+pub const fn is_supported() -> bool {
+    cfg!(any(target_arch = "x86_64", target_arch = "aarch64"))
+}
+
+/// Arms `uctx` to trap after its next user instruction. It must be armed
+/// again before each return to user space, as the aarch64 step state is
+/// cleared by every step.
+///
+/// Does nothing where stepping is not supported (see [`is_supported`]).
+pub fn arm(uctx: &mut UserContext) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        // RFLAGS.TF: a debug exception follows each instruction. SYSCALL
+        // masks it in the kernel, and SYSRET and IRETQ restore it.
+        const TRAP_FLAG: u64 = 1 << 8;
+        uctx.rflags |= TRAP_FLAG;
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        // SPSR_EL1.SS: PSTATE.SS is set on the return to EL0, so that one
+        // instruction runs before the software step exception.
+        const SPSR_SS: u64 = 1 << 21;
+        enable_software_step();
+        uctx.spsr |= SPSR_SS;
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let _ = uctx;
+}
+
+/// Enables software step exceptions from EL0: clears the OS lock, which
+/// otherwise disables them, and sets `MDSCR_EL1.SS`. Kernel debug exceptions
+/// stay disabled (`MDSCR_EL1.KDE` is clear), so the kernel is not stepped.
+#[cfg(target_arch = "aarch64")]
+fn enable_software_step() {
+    const MDSCR_SS: u64 = 1;
+    unsafe {
+        core::arch::asm!(
+            "msr oslar_el1, xzr",
+            "mrs {tmp}, mdscr_el1",
+            "orr {tmp}, {tmp}, {ss}",
+            "msr mdscr_el1, {tmp}",
+            "isb",
+            tmp = out(reg) _,
+            ss = const MDSCR_SS,
+        )
+    };
+}
+
+/// Returns whether `reason` is the trap that follows a stepped instruction.
+pub fn is_step_trap(reason: &ReturnReason) -> bool {
+    let ReturnReason::Exception(info) = reason else {
+        return false;
+    };
+    #[cfg(target_arch = "x86_64")]
+    {
+        // #DB, the debug exception.
+        info.vector == 1
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        // ESR_EL1.EC: software step from a lower exception level.
+        const EC_SOFTWARE_STEP_LOWER_EL: u64 = 0x32;
+        (info.esr.get() >> 26) & 0x3f == EC_SOFTWARE_STEP_LOWER_EL
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        let _ = info;
+        false
+    }
+}
+
+/// Logs a step of the thread `name` at user address `pc`, with the bytes of
+/// code there.
+///
+/// The bytes are read through the page table of `uspace` without faulting
+/// anything in, and only up to the end of the page of `pc`, so that logging
+/// never changes which faults the task takes.
+pub fn log_step(uspace: &AddrSpace, name: &str, pc: usize) {
+    let pc = VirtAddr::from(pc);
+    let len = STEP_BYTES.min(PAGE_SIZE_4K - pc.align_offset_4k());
+    match uspace.page_table().query(pc) {
+        Ok((paddr, _, _)) => {
+            let bytes = unsafe { core::slice::from_raw_parts(phys_to_virt(paddr).as_ptr(), len) };
+            ax_println!("{}: step at pc {:#x}: {:02x?}", name, pc, bytes);
+        }
+        Err(_) => ax_println!("{}: step at pc {:#x}: not mapped", name, pc),
+    }
+}
@@ -1,9 +1,157 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+use axerrno::AxError;
+use axhal::mem::{PAGE_SIZE_4K, phys_to_virt};
+use axhal::paging::MappingFlags;
+use axhal::trap::PageFaultFlags;
 use axhal::uspace::UserContext;
+use axmm::AddrSpace;
+use memory_addr::{MemoryAddr, PageIter4K, VirtAddr};
+
+use crate::errno;
+use crate::fault::{FaultOutcome, handle_fault};
+use crate::mm::UserMemory;
+use crate::signal::{SIGSEGV, SignalState};
+use crate::stat::{S_IFCHR, Stat};
+use crate::stats;
 
+const SYS_READ: usize = 63;
+const SYS_WRITE: usize = 64;
+const SYS_FSTAT: usize = 80;
 const SYS_EXIT: usize = 93;
+const SYS_EXIT_GROUP: usize = 94;
+const SYS_SET_TID_ADDRESS: usize = 96;
+const SYS_NANOSLEEP: usize = 101;
+const SYS_CLOCK_GETTIME: usize = 113;
+const SYS_SCHED_YIELD: usize = 124;
+const SYS_RT_SIGACTION: usize = 134;
+const SYS_RT_SIGRETURN: usize = 139;
+const SYS_UNAME: usize = 160;
+const SYS_GETRUSAGE: usize = 165;
+const SYS_GETPID: usize = 172;
+const SYS_GETTID: usize = 178;
+const SYS_BRK: usize = 214;
+const SYS_MUNMAP: usize = 215;
+const SYS_CLONE: usize = 220;
+const SYS_MMAP: usize = 222;
+const SYS_MPROTECT: usize = 226;
+const SYS_MADVISE: usize = 233;
+const SYS_GETRANDOM: usize = 278;
+
+/// Errors reported to user space by syscalls, as Linux errno values (see
+/// [`errno`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyscallError {
+    /// `EBADF`: bad file descriptor.
+    BadFd = errno::EBADF as isize,
+    /// `ENOMEM`: out of memory, or address range not mapped.
+    NoMem = errno::ENOMEM as isize,
+    /// `EFAULT`: bad user address.
+    Fault = errno::EFAULT as isize,
+    /// `EINVAL`: invalid argument.
+    Invalid = errno::EINVAL as isize,
+    /// `ENOSYS`: syscall not implemented.
+    NoSys = errno::ENOSYS as isize,
+}
+
+impl SyscallError {
+    /// Returns the errno value of the error.
+    pub const fn errno(self) -> i32 {
+        self as i32
+    }
+}
+
+type SyscallResult = Result<usize, SyscallError>;
+
+/// Encodes `res` for the user result register: a negative errno on error.
+fn retval(res: SyscallResult) -> usize {
+    match res {
+        Ok(val) => val,
+        Err(e) => errno::err(e.errno()),
+    }
+}
+
+const PROT_READ: usize = 0x1;
+const PROT_WRITE: usize = 0x2;
+const PROT_EXEC: usize = 0x4;
+
+const MAP_PRIVATE: usize = 0x02;
+const MAP_FIXED: usize = 0x10;
+const MAP_ANONYMOUS: usize = 0x20;
+
+const MAP_FAILED: usize = usize::MAX;
+
+const CLONE_VM: usize = 0x100;
+const CLONE_SIGHAND: usize = 0x800;
+const CLONE_THREAD: usize = 0x10000;
+
+const MADV_WILLNEED: usize = 3;
+const MADV_DONTNEED: usize = 4;
+
+const GRND_NONBLOCK: usize = 0x1;
+const GRND_RANDOM: usize = 0x2;
+
+/// Number of bytes of stdout a [`ConsoleBuffer`] holds at most before it is
+/// flushed, even with no newline.
+const CONSOLE_BUFFER_SIZE: usize = 1024;
+
+/// Bits of the `SYS_EXIT` code that make up the exit status, as on Linux,
+/// where a waiter only ever sees the low 8 bits.
+const EXIT_STATUS_MASK: usize = 0xff;
+
+const RUSAGE_SELF: isize = 0;
+const RUSAGE_CHILDREN: isize = -1;
+const RUSAGE_THREAD: isize = 1;
+
+/// Size of `struct rusage`: two `struct timeval`, then 14 `long` counters.
+const RUSAGE_SIZE: usize = 18 * size_of::<usize>();
+
+/// Index of `ru_minflt` among the words of `struct rusage`, followed by
+/// `ru_majflt`.
+const RUSAGE_MINFLT: usize = 8;
+
+/// Permission bits of the console in `st_mode`: read and write for the
+/// owner.
+const CONSOLE_MODE: u32 = 0o600;
+
+/// Device number of the console in `st_rdev`, as Linux numbers
+/// `/dev/console`: major 5, minor 1.
+const CONSOLE_RDEV: usize = (5 << 8) | 1;
+
+const CLOCK_REALTIME: usize = 0;
+const CLOCK_MONOTONIC: usize = 1;
+
+/// Size of each field of `struct utsname`, its null terminator included.
+const UTSNAME_FIELD_LEN: usize = 65;
+
+/// The fields of the `struct utsname` written by `SYS_UNAME`: `sysname`,
+/// `nodename`, `release`, `version`, `machine` and `domainname`.
+const UTSNAME: [&str; 6] = [
+    "ArceOS",
+    "arceos",
+    env!("CARGO_PKG_VERSION"),
+    "#1 lazymapping",
+    UTSNAME_MACHINE,
+    "(none)",
+];
+
+/// The `machine` field of [`UTSNAME`], as Linux names the architecture.
+#[cfg(target_arch = "riscv64")]
+const UTSNAME_MACHINE: &str = "riscv64";
+#[cfg(target_arch = "riscv32")]
+const UTSNAME_MACHINE: &str = "riscv32";
+#[cfg(target_arch = "aarch64")]
+const UTSNAME_MACHINE: &str = "aarch64";
+#[cfg(target_arch = "x86_64")]
+const UTSNAME_MACHINE: &str = "x86_64";
+#[cfg(target_arch = "loongarch64")]
+const UTSNAME_MACHINE: &str = "loongarch64";
 
 /// Get the syscall number from the UserContext (architecture-specific register).
-fn syscall_num(uctx: &UserContext) -> usize {
+fn syscall_number(uctx: &UserContext) -> usize {
     #[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
     {
         uctx.regs.a7
@@ -22,23 +170,885 @@ fn syscall_num(uctx: &UserContext) -> usize {
     }
 }
 
+/// Get the `i`-th syscall argument from the UserContext
+/// (architecture-specific register).
+///
+/// Syscalls take at most 6 arguments; panics if `i >= 6`.
+fn syscall_arg(uctx: &UserContext, i: usize) -> usize {
+    #[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+    {
+        let r = &uctx.regs;
+        [r.a0, r.a1, r.a2, r.a3, r.a4, r.a5][i]
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        uctx.x[..6][i] as usize
+    }
+    #[cfg(target_arch = "x86_64")]
+    {
+        [uctx.rdi, uctx.rsi, uctx.rdx, uctx.r10, uctx.r8, uctx.r9][i] as usize
+    }
+    #[cfg(target_arch = "loongarch64")]
+    {
+        let r = &uctx.regs;
+        [r.a0, r.a1, r.a2, r.a3, r.a4, r.a5][i]
+    }
+}
+
+/// Set the syscall result in the UserContext (architecture-specific register).
+pub fn set_syscall_return(uctx: &mut UserContext, val: usize) {
+    #[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+    {
+        uctx.regs.a0 = val;
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        uctx.x[0] = val as u64;
+    }
+    #[cfg(target_arch = "x86_64")]
+    {
+        uctx.rax = val as u64;
+    }
+    #[cfg(target_arch = "loongarch64")]
+    {
+        uctx.regs.a0 = val;
+    }
+}
+
+/// Output of a user task to stdout, line-buffered with feature
+/// `buffered-write` to save console calls.
+///
+/// Each complete line reaches the console at once, and the bytes after the
+/// last newline are held until the next one, until [`CONSOLE_BUFFER_SIZE`]
+/// bytes are pending, or until [`flush`](Self::flush), which the task calls
+/// before it waits for console input and when it exits, however it exits.
+#[derive(Default)]
+pub struct ConsoleBuffer {
+    pending: Vec<u8>,
+}
+
+impl ConsoleBuffer {
+    /// Writes `bytes` to the console, through the buffer with feature
+    /// `buffered-write`.
+    fn write(&mut self, bytes: &[u8]) {
+        if !cfg!(feature = "buffered-write") {
+            axhal::console::write_bytes(bytes);
+            return;
+        }
+        self.pending.extend_from_slice(bytes);
+        if let Some(end) = self.pending.iter().rposition(|&b| b == b'\n') {
+            axhal::console::write_bytes(&self.pending[..=end]);
+            self.pending.drain(..=end);
+        }
+        if self.pending.len() >= CONSOLE_BUFFER_SIZE {
+            self.flush();
+        }
+    }
+
+    /// Writes the pending bytes, if any, to the console.
+    pub fn flush(&mut self) {
+        if !self.pending.is_empty() {
+            axhal::console::write_bytes(&self.pending);
+            self.pending.clear();
+        }
+    }
+}
+
+/// What the calling thread must do to complete a syscall that
+/// [`handle_syscall`] cannot complete on its own.
+pub enum TaskRequest {
+    /// Exit with the given code, the low 8 bits of the code passed to
+    /// `SYS_EXIT`, so always in `0..=255`.
+    Exit(i32),
+    /// Exit the whole thread group with the given code, as [`Exit`] does:
+    /// every other thread of the task exits with it as well.
+    ///
+    /// [`Exit`]: TaskRequest::Exit
+    ExitGroup(i32),
+    /// Spawn a thread sharing the address space, which resumes from the
+    /// syscall like the caller, but on the user stack `stack` and with a
+    /// result of 0. The caller gets the thread id of the new thread as result,
+    /// with [`set_syscall_return`].
+    Clone { stack: VirtAddr },
+    /// Sleep for the given time, or only yield the CPU if it is zero, then
+    /// return 0. The caller must not hold the state of the task meanwhile,
+    /// so that its other threads keep running.
+    Sleep(Duration),
+    /// Clear the 32-bit word at the given user address when the thread
+    /// exits, or nothing if it is null, then return the thread id.
+    SetTidAddress(VirtAddr),
+    /// Wait for console input with [`read_console`], then copy at most `len`
+    /// bytes of it to the user buffer `buf` with [`complete_read`]. The
+    /// caller must not hold the state of the task while it waits.
+    ReadConsole { buf: VirtAddr, len: usize },
+}
+
 /// Handle a syscall from user space.
-/// Returns `Some(exit_code)` if the user process wants to exit,
-/// or `None` to continue running.
-pub fn handle_syscall(uctx: &mut UserContext) -> Option<i32> {
+/// Returns `Some(request)` if the calling thread wants to exit, alone or with
+/// its whole thread group, to create a thread, to sleep, to set its tid
+/// address or to wait for console input, or `None` to continue running.
+///
+/// The result of every other syscall is written back to the user result
+/// register with [`set_syscall_return`], except for `SYS_RT_SIGRETURN`,
+/// which replaces the whole context. `pid` is the id of the leader thread of
+/// the task, which `SYS_GETPID` returns. Writes to stdout go through
+/// `console`, the buffer of the task, which is flushed before a read.
+pub fn handle_syscall(
+    uctx: &mut UserContext,
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    pid: u64,
+    signals: &mut SignalState,
+    console: &mut ConsoleBuffer,
+) -> Option<TaskRequest> {
     ax_println!("handle_syscall ...");
 
-    let num = syscall_num(uctx);
-    match num {
-        SYS_EXIT => {
+    let num = syscall_number(uctx);
+    let ret = match num {
+        SYS_READ => match sys_read(
+            uspace,
+            mem,
+            syscall_arg(uctx, 0),
+            syscall_arg(uctx, 1).into(),
+            syscall_arg(uctx, 2),
+        ) {
+            Ok(0) => 0,
+            Ok(len) => {
+                // As by a C library, a prompt is shown before the wait.
+                console.flush();
+                let buf = syscall_arg(uctx, 1).into();
+                return Some(TaskRequest::ReadConsole { buf, len });
+            }
+            Err(e) => retval(Err(e)),
+        },
+        SYS_WRITE => retval(sys_write(
+            uspace,
+            mem,
+            console,
+            syscall_arg(uctx, 0),
+            syscall_arg(uctx, 1).into(),
+            syscall_arg(uctx, 2),
+        )),
+        // Exiting the whole thread group also exits the other threads of
+        // the task, each as it next returns from user space.
+        SYS_EXIT | SYS_EXIT_GROUP => {
             ax_println!("[SYS_EXIT]: system is exiting ..");
-            let exit_code = uctx.arg0() as i32;
-            Some(exit_code)
+            // Masked the same on every architecture, whatever the width of
+            // the register: 0x142 exits with 0x42, and 256 with 0.
+            let exit_code = (syscall_arg(uctx, 0) & EXIT_STATUS_MASK) as i32;
+            return Some(if num == SYS_EXIT_GROUP {
+                TaskRequest::ExitGroup(exit_code)
+            } else {
+                TaskRequest::Exit(exit_code)
+            });
+        }
+        SYS_SET_TID_ADDRESS => {
+            return Some(TaskRequest::SetTidAddress(syscall_arg(uctx, 0).into()));
         }
+        // No signal interrupts the sleep, so the remaining time is never
+        // written back.
+        SYS_NANOSLEEP => match read_timespec(uspace, mem, syscall_arg(uctx, 0).into()) {
+            Ok(duration) => return Some(TaskRequest::Sleep(duration)),
+            Err(e) => retval(Err(e)),
+        },
+        SYS_CLOCK_GETTIME => retval(sys_clock_gettime(
+            uspace,
+            mem,
+            syscall_arg(uctx, 0),
+            syscall_arg(uctx, 1).into(),
+        )),
+        SYS_RT_SIGACTION => retval(sys_rt_sigaction(
+            uspace,
+            mem,
+            signals,
+            syscall_arg(uctx, 0),
+            syscall_arg(uctx, 1).into(),
+            syscall_arg(uctx, 2).into(),
+        )),
+        SYS_RT_SIGRETURN => {
+            // The restored context carries its own result register.
+            if signals.sigreturn(uctx) {
+                return None;
+            }
+            retval(Err(SyscallError::Invalid))
+        }
+        SYS_UNAME => retval(sys_uname(uspace, mem, syscall_arg(uctx, 0).into())),
+        SYS_FSTAT => retval(sys_fstat(
+            uspace,
+            mem,
+            syscall_arg(uctx, 0),
+            syscall_arg(uctx, 1).into(),
+        )),
+        SYS_SCHED_YIELD => {
+            // The scheduler reinstalls this task's page table root when it
+            // switches back, so the pages faulted in so far stay mapped.
+            axtask::yield_now();
+            0
+        }
+        // Every thread of a task shares the id of its leader as the process
+        // id, while the thread id is that of the calling thread.
+        SYS_GETPID => pid as usize,
+        SYS_GETTID => axtask::current().id().as_u64() as usize,
+        SYS_BRK => {
+            // brk(0) queries the current break without moving it.
+            let brk = match syscall_arg(uctx, 0) {
+                0 => mem.brk.current(),
+                addr => mem.brk.set(uspace, addr.into()),
+            };
+            brk.as_usize()
+        }
+        SYS_MMAP => sys_mmap(
+            uspace,
+            mem,
+            syscall_arg(uctx, 0).into(),
+            syscall_arg(uctx, 1),
+            syscall_arg(uctx, 2),
+            syscall_arg(uctx, 3),
+        ),
+        SYS_MUNMAP => retval(
+            mem.mmap
+                .unmap(
+                    uspace,
+                    &mut mem.regions,
+                    &mut mem.frames,
+                    syscall_arg(uctx, 0).into(),
+                    syscall_arg(uctx, 1),
+                )
+                .map(|_| 0)
+                .map_err(|_| SyscallError::Invalid),
+        ),
+        SYS_CLONE => match sys_clone(syscall_arg(uctx, 0), syscall_arg(uctx, 1).into()) {
+            Ok(stack) => return Some(TaskRequest::Clone { stack }),
+            Err(e) => retval(Err(e)),
+        },
+        SYS_MPROTECT => retval(sys_mprotect(
+            uspace,
+            mem,
+            syscall_arg(uctx, 0).into(),
+            syscall_arg(uctx, 1),
+            syscall_arg(uctx, 2),
+        )),
+        SYS_MADVISE => retval(sys_madvise(
+            uspace,
+            mem,
+            syscall_arg(uctx, 0).into(),
+            syscall_arg(uctx, 1),
+            syscall_arg(uctx, 2),
+        )),
+        SYS_GETRUSAGE => retval(sys_getrusage(
+            uspace,
+            mem,
+            syscall_arg(uctx, 0) as isize,
+            syscall_arg(uctx, 1).into(),
+        )),
+        SYS_GETRANDOM => retval(sys_getrandom(
+            uspace,
+            mem,
+            syscall_arg(uctx, 0).into(),
+            syscall_arg(uctx, 1),
+            syscall_arg(uctx, 2),
+        )),
+        // The user may probe for syscalls, so an unknown one only fails.
         _ => {
-            ax_println!("Unimplemented syscall: {}", num);
-            uctx.set_retval(usize::MAX); // -ENOSYS equivalent
-            None
+            debug!("Unimplemented syscall: {}", num);
+            retval(Err(SyscallError::NoSys))
+        }
+    };
+    set_syscall_return(uctx, ret);
+    None
+}
+
+/// Checks the arguments of a thread creation with `flags`, whose new thread
+/// starts on the user stack `stack`, and returns that stack.
+///
+/// Only threads are supported: `flags` must be `CLONE_VM | CLONE_THREAD`,
+/// optionally with `CLONE_SIGHAND` (the signal handler is copied to the new
+/// thread in any case), and `stack` must not be null.
+fn sys_clone(flags: usize, stack: VirtAddr) -> Result<VirtAddr, SyscallError> {
+    if flags & !CLONE_SIGHAND != CLONE_VM | CLONE_THREAD || stack.as_usize() == 0 {
+        return Err(SyscallError::Invalid);
+    }
+    Ok(stack)
+}
+
+/// Checks a read of `len` bytes from the console into user address `buf`,
+/// and returns the number of bytes to wait for, at most.
+///
+/// Only stdin (0) is supported. The pages of the buffer are faulted in for
+/// writing up front, as by [`fault_in_user`], so that a buffer outside every
+/// writable region yields `-EFAULT` before the thread waits for input. A
+/// zero-length read returns 0 at once.
+fn sys_read(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    fd: usize,
+    buf: VirtAddr,
+    len: usize,
+) -> SyscallResult {
+    if fd != 0 {
+        return Err(SyscallError::BadFd);
+    }
+    if len > 0 {
+        fault_in_user(uspace, mem, buf, len, PageFaultFlags::WRITE)?;
+    }
+    Ok(len)
+}
+
+/// Waits for input on the console, yielding the CPU until a byte is
+/// available, and returns at most `len` bytes of it.
+///
+/// The input ends with a newline, which is kept, with the bytes available at
+/// once, or with an end of transmission (`^D`), which is dropped: a `^D`
+/// first thus reads as end of file, with no byte. The state of the task must
+/// not be locked meanwhile.
+///
+/// The wait is abandoned, with the bytes read so far, once `cancelled`
+/// returns true, as it is asked before each yield.
+pub fn read_console(len: usize, cancelled: impl Fn() -> bool) -> Vec<u8> {
+    const EOT: u8 = 0x04;
+    let mut input = Vec::with_capacity(len.min(PAGE_SIZE_4K));
+    let mut byte = [0];
+    while input.len() < len {
+        if axhal::console::read_bytes(&mut byte) == 0 {
+            if !input.is_empty() || cancelled() {
+                break;
+            }
+            axtask::yield_now();
+            continue;
+        }
+        if byte[0] == EOT {
+            break;
+        }
+        input.push(byte[0]);
+        if byte[0] == b'\n' {
+            break;
+        }
+    }
+    input
+}
+
+/// Completes a `SYS_READ` by copying the console `input` to user address
+/// `buf`, and returns the result of the syscall: the number of bytes read, 0
+/// at end of file, or `-EFAULT` if the buffer was unmapped while the thread
+/// waited.
+pub fn complete_read(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    buf: VirtAddr,
+    input: &[u8],
+) -> usize {
+    retval(copy_to_user(uspace, mem, buf, input).map(|()| input.len()))
+}
+
+/// Writes `len` bytes at user address `buf` to the console.
+///
+/// Only stdout (1) and stderr (2) are supported. Stdout goes through
+/// `console`, and stderr is never buffered. The bytes are copied out of
+/// `uspace` page by page with [`walk_user_pages`], so a buffer straddling an
+/// untouched lazy page has it faulted in, and a pointer outside every
+/// readable region yields `-EFAULT` instead of being dereferenced. Returns
+/// the number of bytes written, which is less than `len` if a page past the
+/// first one cannot be read.
+fn sys_write(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    console: &mut ConsoleBuffer,
+    fd: usize,
+    buf: VirtAddr,
+    len: usize,
+) -> SyscallResult {
+    if fd != 1 && fd != 2 {
+        return Err(SyscallError::BadFd);
+    }
+    if buf.checked_add(len).is_none() {
+        return Err(SyscallError::Fault);
+    }
+
+    let written = walk_user_pages(uspace, mem, buf, len, PageFaultFlags::READ, |src, _, n| {
+        let bytes = unsafe { core::slice::from_raw_parts(src.as_ptr(), n) };
+        if fd == 1 {
+            console.write(bytes);
+        } else {
+            axhal::console::write_bytes(bytes);
+        }
+    });
+    // Report a partial write if some bytes already reached the console.
+    match written {
+        0 if len > 0 => Err(SyscallError::Fault),
+        written => Ok(written),
+    }
+}
+
+/// Maps `len` bytes of anonymous private memory with protection `prot`.
+///
+/// Only `MAP_PRIVATE | MAP_ANONYMOUS` mappings are supported. `addr` is only a
+/// hint, unless `MAP_FIXED` is given: the mapping is then made exactly at
+/// `addr`, which must be page-aligned (or `-EINVAL` is returned), replacing the
+/// mappings already there. No frame is allocated here: the pages are
+/// demand-paged on first touch. Returns the start of the mapping, or
+/// `MAP_FAILED` if the request is not supported or no room is left.
+fn sys_mmap(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    addr: VirtAddr,
+    len: usize,
+    prot: usize,
+    flags: usize,
+) -> usize {
+    if flags & !MAP_FIXED != MAP_PRIVATE | MAP_ANONYMOUS {
+        return MAP_FAILED;
+    }
+    let fixed = flags & MAP_FIXED != 0;
+    if fixed && !addr.is_aligned_4k() {
+        return retval(Err(SyscallError::Invalid));
+    }
+    let map_flags = prot_flags(prot);
+    if fixed {
+        mem.mmap.map_fixed(
+            uspace,
+            &mut mem.regions,
+            &mut mem.frames,
+            addr,
+            len,
+            map_flags,
+        )
+    } else {
+        mem.mmap.map(uspace, &mut mem.regions, addr, len, map_flags)
+    }
+    .map_or(MAP_FAILED, VirtAddr::as_usize)
+}
+
+/// Returns the mapping flags of user pages with protection `prot`.
+fn prot_flags(prot: usize) -> MappingFlags {
+    let mut flags = MappingFlags::USER;
+    if prot & PROT_READ != 0 {
+        flags |= MappingFlags::READ;
+    }
+    if prot & PROT_WRITE != 0 {
+        flags |= MappingFlags::WRITE;
+    }
+    if prot & PROT_EXEC != 0 {
+        flags |= MappingFlags::EXECUTE;
+    }
+    flags
+}
+
+/// Changes the protection of the pages of `[addr, addr + len)` to `prot`
+/// (see [`UserMemory::protect`]).
+///
+/// As on Linux, write access implies read access. `addr` must be
+/// page-aligned, and `prot` must allow some access: `PROT_NONE` is not
+/// supported. `EINVAL` is returned otherwise, or if the range cuts a
+/// pre-allocated region, and `ENOMEM` if it is not fully covered by regions.
+fn sys_mprotect(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    addr: VirtAddr,
+    len: usize,
+    prot: usize,
+) -> SyscallResult {
+    let size = len
+        .checked_next_multiple_of(PAGE_SIZE_4K)
+        .ok_or(SyscallError::Invalid)?;
+    let all = PROT_READ | PROT_WRITE | PROT_EXEC;
+    if !addr.is_aligned_4k() || prot & !all != 0 || prot == 0 {
+        return Err(SyscallError::Invalid);
+    }
+    let mut flags = prot_flags(prot);
+    if flags.contains(MappingFlags::WRITE) {
+        flags |= MappingFlags::READ;
+    }
+    match mem.protect(uspace, addr, size, flags) {
+        Ok(()) => Ok(0),
+        Err(AxError::NoMemory) => Err(SyscallError::NoMem),
+        Err(_) => Err(SyscallError::Invalid),
+    }
+}
+
+/// Applies `advice` to the pages of `[addr, addr + len)`.
+///
+/// `MADV_WILLNEED` faults every page of the range in up front, through the
+/// regular fault handler, as a write if its region is writable and as a read
+/// otherwise, so later accesses to it take no fault. Only the pages of the
+/// dirty-tracked stack are faulted in as a read, so as not to mark them dirty
+/// unwritten: the first write to one of them still faults.
+/// `MADV_DONTNEED` unmaps the pages and frees their frames, so that they are
+/// demand-paged again (see [`UserMemory::discard`]). `addr` must be
+/// page-aligned and the range fully covered by regions; `EINVAL` is returned
+/// otherwise, and for any other advice.
+fn sys_madvise(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    addr: VirtAddr,
+    len: usize,
+    advice: usize,
+) -> SyscallResult {
+    let size = len
+        .checked_next_multiple_of(PAGE_SIZE_4K)
+        .ok_or(SyscallError::Invalid)?;
+    if !addr.is_aligned_4k() {
+        return Err(SyscallError::Invalid);
+    }
+    match advice {
+        MADV_WILLNEED => {
+            let end = addr.checked_add(size).ok_or(SyscallError::Invalid)?;
+            for page in PageIter4K::new(addr, end).ok_or(SyscallError::Invalid)? {
+                let dirty = mem.dirty.as_ref().is_some_and(|dirty| dirty.contains(page));
+                let access = match mem.regions.find(page) {
+                    Some(region) if region.flags.contains(MappingFlags::WRITE) && !dirty => {
+                        PageFaultFlags::WRITE
+                    }
+                    Some(_) => PageFaultFlags::READ,
+                    None => return Err(SyscallError::Invalid),
+                };
+                fault_in_user(uspace, mem, page, PAGE_SIZE_4K, access)
+                    .map_err(|_| SyscallError::Invalid)?;
+            }
+        }
+        MADV_DONTNEED => mem
+            .discard(uspace, addr, size)
+            .map_err(|_| SyscallError::Invalid)?,
+        _ => return Err(SyscallError::Invalid),
+    }
+    Ok(0)
+}
+
+/// Installs the `struct sigaction` at user address `act`, if not null, as
+/// the action of signal `signum`, and stores the previous action at `oldact`,
+/// if not null.
+///
+/// Only `SIGSEGV` can be caught, and only `sa_handler` is honored: the flags,
+/// mask and restorer of the action are ignored, and read back as zeros.
+fn sys_rt_sigaction(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    signals: &mut SignalState,
+    signum: usize,
+    act: VirtAddr,
+    oldact: VirtAddr,
+) -> SyscallResult {
+    if signum != SIGSEGV {
+        return Err(SyscallError::Invalid);
+    }
+    // struct sigaction { void *sa_handler; unsigned long sa_flags; ... }
+    const SIGACTION_SIZE: usize = 4 * size_of::<usize>();
+    let old = if act.as_usize() != 0 {
+        let handler = copy_from_user(uspace, mem, act, size_of::<usize>())?;
+        signals.set_segv_handler(word_at(&handler, 0))
+    } else {
+        signals.segv_handler()
+    };
+    if oldact.as_usize() != 0 {
+        let mut old_action = [0u8; SIGACTION_SIZE];
+        old_action[..size_of::<usize>()].copy_from_slice(&old.to_ne_bytes());
+        copy_to_user(uspace, mem, oldact, &old_action)?;
+    }
+    Ok(0)
+}
+
+/// Writes the current time of clock `clock_id` as a `timespec` at user
+/// address `tp`.
+///
+/// `CLOCK_REALTIME` and `CLOCK_MONOTONIC` are supported.
+fn sys_clock_gettime(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    clock_id: usize,
+    tp: VirtAddr,
+) -> SyscallResult {
+    let now = match clock_id {
+        CLOCK_REALTIME => axhal::time::wall_time(),
+        CLOCK_MONOTONIC => axhal::time::monotonic_time(),
+        _ => return Err(SyscallError::Invalid),
+    };
+    // struct timespec { time_t tv_sec; long tv_nsec; }
+    let mut ts = [0u8; 2 * size_of::<usize>()];
+    let (sec, nsec) = ts.split_at_mut(size_of::<usize>());
+    sec.copy_from_slice(&(now.as_secs() as usize).to_ne_bytes());
+    nsec.copy_from_slice(&(now.subsec_nanos() as usize).to_ne_bytes());
+    copy_to_user(uspace, mem, tp, &ts)?;
+    Ok(0)
+}
+
+/// Writes the resource usage of `who` as a `struct rusage` at user address
+/// `usage`.
+///
+/// Only the page fault counts are filled, all other fields are zero. Each
+/// thread has its own fault counters, so `RUSAGE_SELF` reports those of the
+/// calling thread, as `RUSAGE_THREAD` does. Every fault is minor, as no page
+/// is read from a device on a fault, so `ru_majflt` is always 0. A task has
+/// no child, so `RUSAGE_CHILDREN` reports zeros. The faults the kernel
+/// handles itself meanwhile, such as on this very copy, are not counted.
+fn sys_getrusage(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    who: isize,
+    usage: VirtAddr,
+) -> SyscallResult {
+    let minflt = match who {
+        RUSAGE_SELF | RUSAGE_THREAD => stats::page_fault_stats(&axtask::current().clone()).faults,
+        RUSAGE_CHILDREN => 0,
+        _ => return Err(SyscallError::Invalid),
+    };
+    const WORD: usize = size_of::<usize>();
+    let mut rusage = [0u8; RUSAGE_SIZE];
+    rusage[RUSAGE_MINFLT * WORD..(RUSAGE_MINFLT + 1) * WORD].copy_from_slice(&minflt.to_ne_bytes());
+    copy_to_user(uspace, mem, usage, &rusage)?;
+    Ok(0)
+}
+
+/// Writes the fixed identification of the system, [`UTSNAME`], as a
+/// `struct utsname` at user address `buf`.
+///
+/// The structure is copied with [`copy_to_user`], so untouched lazy pages of
+/// it are faulted in.
+fn sys_uname(uspace: &mut AddrSpace, mem: &mut UserMemory, buf: VirtAddr) -> SyscallResult {
+    let mut uts = [0u8; UTSNAME.len() * UTSNAME_FIELD_LEN];
+    for (field, value) in uts.chunks_mut(UTSNAME_FIELD_LEN).zip(UTSNAME) {
+        field[..value.len()].copy_from_slice(value.as_bytes());
+    }
+    copy_to_user(uspace, mem, buf, &uts)?;
+    Ok(0)
+}
+
+/// Writes the status of file descriptor `fd` as a `struct stat` (see
+/// [`Stat`]) at user address `buf`.
+///
+/// Only the standard streams, 0 to 2, are open, all of them on the console:
+/// each is reported as a character device, `/dev/console`, readable and
+/// writable by its owner, with every other field zero. Any other `fd` fails
+/// with `EBADF`. The structure is copied with [`copy_to_user`], so an
+/// untouched lazy page of it is faulted in.
+fn sys_fstat(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    fd: usize,
+    buf: VirtAddr,
+) -> SyscallResult {
+    if fd > 2 {
+        return Err(SyscallError::BadFd);
+    }
+    let stat = Stat {
+        st_mode: S_IFCHR | CONSOLE_MODE,
+        st_nlink: 1,
+        st_rdev: CONSOLE_RDEV,
+        ..Stat::default()
+    };
+    copy_to_user(uspace, mem, buf, stat.as_bytes())?;
+    Ok(0)
+}
+
+/// Fills the `len` bytes at user address `buf` with random bytes.
+///
+/// `GRND_NONBLOCK` and `GRND_RANDOM` are accepted, and make no difference:
+/// the bytes come from [`random_u64`], which never blocks. The bytes are
+/// copied with [`copy_to_user`], page by page, so untouched lazy pages of the
+/// buffer are faulted in. Returns the number of bytes written, which is less
+/// than `len` if a page past the first one cannot be written, or `EFAULT` if
+/// the first one cannot.
+fn sys_getrandom(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    buf: VirtAddr,
+    len: usize,
+    flags: usize,
+) -> SyscallResult {
+    if flags & !(GRND_NONBLOCK | GRND_RANDOM) != 0 {
+        return Err(SyscallError::Invalid);
+    }
+    if buf.checked_add(len).is_none() {
+        return Err(SyscallError::Fault);
+    }
+
+    let mut written = 0;
+    let mut chunk = [0u8; PAGE_SIZE_4K];
+    while written < len {
+        // Stop at page boundaries, so that a partial write covers whole pages.
+        let n = (len - written).min(PAGE_SIZE_4K - (buf + written).align_offset_4k());
+        for bytes in chunk[..n].chunks_mut(size_of::<u64>()) {
+            bytes.copy_from_slice(&random_u64().to_ne_bytes()[..bytes.len()]);
+        }
+        match copy_to_user(uspace, mem, buf + written, &chunk[..n]) {
+            Ok(()) => written += n,
+            Err(_) if written > 0 => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(written)
+}
+
+/// Returns the next value of a splitmix64 generator, mixed with the current
+/// time.
+///
+/// The values are unpredictable enough for a demo payload, but are not fit
+/// for cryptography.
+pub fn random_u64() -> u64 {
+    static STATE: AtomicU64 = AtomicU64::new(0);
+    let mut z = STATE
+        .fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed)
+        .wrapping_add(axhal::time::monotonic_time_nanos());
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Reads the `timespec` at user address `tp` as a duration, for
+/// `SYS_NANOSLEEP`.
+///
+/// Returns `EINVAL` if the time is negative or its nanoseconds are not below
+/// one second.
+fn read_timespec(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    tp: VirtAddr,
+) -> Result<Duration, SyscallError> {
+    // struct timespec { time_t tv_sec; long tv_nsec; }
+    let ts = copy_from_user(uspace, mem, tp, 2 * size_of::<usize>())?;
+    let sec = word_at(&ts, 0) as isize;
+    let nsec = word_at(&ts, 1) as isize;
+    if sec < 0 || !(0..1_000_000_000).contains(&nsec) {
+        return Err(SyscallError::Invalid);
+    }
+    Ok(Duration::new(sec as u64, nsec as u32))
+}
+
+/// Decodes the `i`-th word of `bytes`, or returns 0 if `bytes` is too short.
+fn word_at(bytes: &[u8], i: usize) -> usize {
+    bytes
+        .get(i * size_of::<usize>()..)
+        .and_then(|word| word.first_chunk())
+        .map_or(0, |word| usize::from_ne_bytes(*word))
+}
+
+/// Copies `len` bytes at user address `uaddr` out of `uspace`.
+///
+/// See [`walk_user_pages`] for how lazy pages of the source are handled.
+fn copy_from_user(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    uaddr: VirtAddr,
+    len: usize,
+) -> Result<Vec<u8>, SyscallError> {
+    let mut buf = vec![0u8; len];
+    let dst = buf.as_mut_ptr();
+    let copied = walk_user_pages(
+        uspace,
+        mem,
+        uaddr,
+        len,
+        PageFaultFlags::READ,
+        |src, offset, n| unsafe {
+            core::ptr::copy_nonoverlapping(src.as_ptr(), dst.add(offset), n);
+        },
+    );
+    if copied < len {
+        return Err(SyscallError::Fault);
+    }
+    Ok(buf)
+}
+
+/// Copies `data` to user address `uaddr` in `uspace`.
+///
+/// See [`walk_user_pages`] for how lazy pages of the destination are
+/// handled. On `EFAULT`, the pages before the faulting one may have been
+/// written.
+///
+/// The user address is never dereferenced: each page is faulted in by
+/// calling the fault handler on the region list directly, with the state of
+/// the task the caller already holds, and is written through the kernel
+/// mapping of its frame. The copy thus takes no hardware fault, and cannot
+/// re-enter the trap path nor wait for a lock it holds.
+pub fn copy_to_user(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    uaddr: VirtAddr,
+    data: &[u8],
+) -> Result<(), SyscallError> {
+    let copied = walk_user_pages(
+        uspace,
+        mem,
+        uaddr,
+        data.len(),
+        PageFaultFlags::WRITE,
+        |dst, offset, n| unsafe {
+            core::ptr::copy_nonoverlapping(data.as_ptr().add(offset), dst.as_mut_ptr(), n);
+        },
+    );
+    if copied < data.len() {
+        return Err(SyscallError::Fault);
+    }
+    Ok(())
+}
+
+/// Visits the user range `[uaddr, uaddr + len)` for `access`, one page
+/// at a time, and returns the number of bytes visited.
+///
+/// Each page is made accessible as by [`fault_in_user`], then translated to
+/// its frame through the page table of `uspace` (whichever backend mapped
+/// it, a huge page of [`SharedPages`](crate::mm::SharedPages) included), and
+/// `f` is called at once with the kernel address of the part of the range in
+/// that frame, its offset in the range and its length. Faulting in the next
+/// page thus cannot remap one not visited yet. The walk stops at the first
+/// page that cannot be made accessible.
+fn walk_user_pages(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    uaddr: VirtAddr,
+    len: usize,
+    access: PageFaultFlags,
+    mut f: impl FnMut(VirtAddr, usize, usize),
+) -> usize {
+    let mut done = 0;
+    while done < len {
+        // Every page before this one is in `uspace`, so this cannot wrap.
+        let vaddr = uaddr + done;
+        let n = (len - done).min(PAGE_SIZE_4K - vaddr.align_offset_4k());
+        if fault_in_user(uspace, mem, vaddr, n, access).is_err() {
+            break;
+        }
+        let Ok((paddr, _, _)) = uspace.page_table().query(vaddr) else {
+            break;
+        };
+        f(phys_to_virt(paddr), done, n);
+        done += n;
+    }
+    done
+}
+
+/// Makes the user range `[uaddr, uaddr + len)` accessible for `access`.
+///
+/// The pages are checked one by one, so a range straddling a mapped and an
+/// unmapped page is handled. A page that is not mapped with the permission
+/// needed by `access` yet (an untouched lazy page, or a copy-on-write or
+/// dirty-tracked page for a write) is faulted in through the regular fault
+/// handler, exactly as if the user had accessed it. Returns `EFAULT` if part of
+/// the range is not in a region allowing `access`.
+fn fault_in_user(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    uaddr: VirtAddr,
+    len: usize,
+    access: PageFaultFlags,
+) -> Result<(), SyscallError> {
+    let end = uaddr.checked_add(len).ok_or(SyscallError::Fault)?;
+    if !uspace.contains_range(uaddr, len) {
+        return Err(SyscallError::Fault);
+    }
+    let pages =
+        PageIter4K::new(uaddr.align_down_4k(), end.align_up_4k()).ok_or(SyscallError::Fault)?;
+    for page in pages {
+        let accessible = matches!(
+            uspace.page_table().query(page),
+            Ok((_, flags, _)) if flags.contains(access)
+        );
+        if !accessible
+            && !matches!(
+                handle_fault(uspace, mem, page, access),
+                FaultOutcome::Mapped | FaultOutcome::AlreadyMapped | FaultOutcome::Dirtied
+            )
+        {
+            return Err(SyscallError::Fault);
         }
     }
+    Ok(())
 }
@@ -1,11 +1,161 @@
-use axhal::paging::MappingFlags;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+use axerrno::{AxError, AxResult};
+use axhal::context::TrapFrame;
+use axhal::mem::{PAGE_SIZE_4K, phys_to_virt};
+use axhal::paging::{MappingFlags, PageSize};
 use axhal::trap::PageFaultFlags;
 use axhal::uspace::{ReturnReason, UserContext};
 use axmm::AddrSpace;
+use axsync::Mutex;
 use axtask::{AxTaskRef, TaskInner};
-use memory_addr::VirtAddr;
+use memory_addr::{MemoryAddr, PageIter4K, VirtAddr, VirtAddrRange};
+
+use crate::fault::{FaultOutcome, handle_fault};
+use crate::loader::{self, SymbolTable};
+use crate::mm::{
+    DirtyPages, FlagsPolicy, HEAP_FLAGS, INFO_FLAGS, InfoPage, LazyBackend, LazyRegion,
+    LazyRegions, MmapArea, PageMapping, ProgramBreak, STACK_ALIGN, STACK_FLAGS, SharedPages,
+    UserMemory, dump_regions, is_zero_page,
+};
+use crate::signal::{SEGV_ACCERR, SEGV_MAPERR, SIGILL, SIGKILL, SIGSEGV, SignalState};
+use crate::stats::{self, FaultCounters};
+use crate::step;
+use crate::syscall::{self, ConsoleBuffer, TaskRequest};
 
-use crate::syscall;
+/// Logs a successfully handled page fault.
+///
+/// The message is printed to the console with the `verbose_faults` feature,
+/// and only logged at debug level otherwise, so that payloads touching many
+/// pages do not flood the console.
+macro_rules! fault_log {
+    ($($arg:tt)*) => {
+        if cfg!(feature = "verbose_faults") {
+            ax_println!($($arg)*);
+        } else {
+            debug!($($arg)*);
+        }
+    };
+}
+
+/// How far the stack of a user task may grow, and how far it has grown.
+struct StackGrowth {
+    /// Top of the stack.
+    top: VirtAddr,
+    /// Lowest address the stack may grow down to.
+    limit: VirtAddr,
+    /// Start of the guard page below `limit`, or `limit` if there is none.
+    guard_page: VirtAddr,
+    /// End of the guard page above `top`, or `top` if there is none.
+    guard_top: VirtAddr,
+    /// Current lowest address of the stack.
+    bottom: VirtAddr,
+    /// Permissions of the stack pages.
+    flags: MappingFlags,
+    /// Number of pages below a faulting stack page to map along with it.
+    readahead: usize,
+    /// Whether the next stack fault maps the whole stack region it hits:
+    /// set until the first stack fault of the task if the stack is mapped in
+    /// full on first touch.
+    first_touch_full: bool,
+}
+
+/// The state shared by the threads of a user task: its address space and
+/// everything needed to handle its faults and syscalls.
+struct UserProcess {
+    uspace: AddrSpace,
+    mem: UserMemory,
+    stack: StackGrowth,
+    /// Size of the kernel stack of each thread.
+    kernel_stack_size: usize,
+    /// Exit codes of the threads when a fault or trap terminates them.
+    exit_codes: ExitCodes,
+    /// Number of page faults each thread may take before it is terminated.
+    max_faults: Option<usize>,
+    /// Function symbols of the app, to name the code a trap stopped in.
+    symbols: Option<SymbolTable>,
+    /// Callback run when the task exits with `SYS_EXIT`, until it is.
+    on_exit: Option<ExitHook>,
+    /// The page of the dirty-tracked stack watched with
+    /// [`watch_first_write`], if any, and the callback told of its first
+    /// write.
+    write_watch: Option<(VirtAddr, WriteWatchHook)>,
+    /// Id of the leader thread, which is the process id of every thread of
+    /// the task, set once the leader is created.
+    pid: u64,
+    /// Threads created with `SYS_CLONE`, not joined by the leader yet.
+    threads: Vec<AxTaskRef>,
+    /// Exit code of the task once a thread called `SYS_EXIT_GROUP`: every
+    /// other thread then exits with it as it next returns from user space,
+    /// or gives up waiting for console input.
+    group_exit: Option<i32>,
+    /// Shared info page of the task, told the task id once the leader
+    /// thread is created.
+    info_page: Option<Arc<InfoPage>>,
+    /// Output of the task to stdout not written to the console yet.
+    console: ConsoleBuffer,
+    /// Whether the threads of the task are single-stepped.
+    single_step: bool,
+}
+
+/// The state of a user task, shared by its threads behind a single lock.
+///
+/// The lock is the lock of the address space of the task: the page table is
+/// only reached through it, and the fault handler holds it from the check
+/// of the faulting page to its mapping (see [`handle_fault`]). Two threads
+/// faulting on the same page thus map it once, the second one finding it
+/// already mapped, and faults on different pages never interleave their
+/// updates of the page table.
+///
+/// As to the scheduler, the lock is a blocking [`Mutex`], only ever taken in
+/// task context, never by an interrupt handler: a thread waiting for it
+/// sleeps, and the thread holding it may be preempted, or switched out by
+/// the timer tick, without deadlocking. It is never held while user code
+/// runs, nor while a thread sleeps, waits for input or joins another
+/// thread, and the fault and exit hooks run without it. [`RUNNING_THREADS`]
+/// and [`EXITED_TASKS`] may be locked before it, to find a task, but never
+/// while it is held.
+///
+/// The lock is held while a trap of a thread of the task is handled, so the
+/// functions that take it from outside the task, such as
+/// [`add_lazy_region`], must not be called from the handling of a trap of
+/// that task: the syscalls work on the locked state directly.
+type SharedProcess = Arc<Mutex<UserProcess>>;
+
+/// How a user thread exited.
+#[derive(Clone, Copy)]
+enum ThreadExit {
+    /// With `SYS_EXIT` or `SYS_EXIT_GROUP`, and the code the user passed.
+    Requested(i32),
+    /// Terminated by the kernel after a fault or trap, with the matching
+    /// code of the task's [`ExitCodes`].
+    Killed(i32),
+}
+
+impl ThreadExit {
+    /// Returns the exit code of the thread.
+    const fn code(self) -> i32 {
+        match self {
+            Self::Requested(code) | Self::Killed(code) => code,
+        }
+    }
+}
+
+/// State of the user task of every thread still running user code, keyed by
+/// the task id of the thread.
+static RUNNING_THREADS: Mutex<BTreeMap<u64, SharedProcess>> = Mutex::new(BTreeMap::new());
+
+/// State of every exited user task not reaped yet, keyed by the task id of
+/// its leader thread.
+static EXITED_TASKS: Mutex<BTreeMap<u64, SharedProcess>> = Mutex::new(BTreeMap::new());
 
 /// Wrapper to ensure `UserContext` is 16-byte aligned on the stack.
 ///
@@ -15,85 +165,1674 @@ use crate::syscall;
 /// the CPU adjusts RSP, causing all pushed values to be offset by 8 bytes
 /// and the kernel-RSP restore to read the wrong value (triple fault).
 ///
-/// `sizeof(TrapFrame) == 176 == 11 × 16`, so TSS.RSP0 inherits the
-/// alignment of `&uctx`.  Forcing 16-byte alignment here guarantees
-/// TSS.RSP0 is also 16-byte aligned on every architecture.
+/// `sizeof(TrapFrame)` is a multiple of [`TRAP_FRAME_ALIGN`] (176 == 11 × 16
+/// on x86_64), so TSS.RSP0 inherits the alignment of `&uctx`.  Forcing
+/// 16-byte alignment here guarantees TSS.RSP0 is also 16-byte aligned on
+/// every architecture.  Both are checked at compile time, so a layout change
+/// of `TrapFrame` fails the build instead of triple faulting.
+///
+/// This only concerns the kernel stack: the alignment of the initial user
+/// stack pointer is checked against [`STACK_ALIGN`] when the task is spawned.
 #[repr(C, align(16))]
 struct AlignedUserContext(UserContext);
 
-/// Spawn a user task that enters user space and handles traps.
+/// Alignment the kernel stack pointer must keep across a trap from user
+/// space, which [`AlignedUserContext`] relies on.
+const TRAP_FRAME_ALIGN: usize = 16;
+
+const _: () = assert!(
+    size_of::<TrapFrame>().is_multiple_of(TRAP_FRAME_ALIGN),
+    "TrapFrame size would misalign TSS.RSP0"
+);
+const _: () = assert!(align_of::<AlignedUserContext>() == TRAP_FRAME_ALIGN);
+
+/// Errors that can occur while spawning a user task or while lazily mapping
+/// its pages.
+#[derive(Debug)]
+pub enum SpawnError {
+    /// Failed to install a page table entry for the given page.
+    MapFailed(VirtAddr),
+    /// No pre-allocated physical page exists at the given page index.
+    NoPhysPage(usize),
+    /// No free physical frame is left for a private page copy.
+    NoMemory,
+    /// The address space does not match the requested user stack layout.
+    AddrSpaceSetup,
+    /// The top of the user stack, where the stack pointer starts, is not
+    /// aligned to [`STACK_ALIGN`].
+    MisalignedStack(VirtAddr),
+    /// The arguments and environment do not fit in the initial user stack.
+    ArgsTooLarge,
+    /// The user app is not a valid executable, or does not fit in the
+    /// address space.
+    BadApp,
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MapFailed(vaddr) => write!(f, "failed to map page {:#x}", vaddr),
+            Self::NoPhysPage(idx) => write!(f, "no physical page at index {}", idx),
+            Self::NoMemory => write!(f, "out of physical memory"),
+            Self::AddrSpaceSetup => write!(f, "invalid user stack layout"),
+            Self::MisalignedStack(top) => write!(
+                f,
+                "user stack top {:#x} is not aligned to {} bytes",
+                top, STACK_ALIGN
+            ),
+            Self::ArgsTooLarge => write!(f, "arguments do not fit in the user stack"),
+            Self::BadApp => write!(f, "cannot load the user app"),
+        }
+    }
+}
+
+/// Layout and backing of the lazy user stack of a task.
+pub struct UserStack {
+    /// Top of the stack, where the user stack pointer starts.
+    pub top: VirtAddr,
+    /// Initial lowest address of the stack.
+    pub bottom: VirtAddr,
+    /// Size the stack may grow to, measured down from `top`.
+    pub max_size: usize,
+    /// Permissions of every stack page.
+    pub flags: MappingFlags,
+    /// Where the frames behind the stack come from.
+    pub backend: LazyBackend,
+    /// Whether pre-allocated frames are shared copy-on-write.
+    pub cow: bool,
+    /// Whether the page below the growth limit, and the page above the top
+    /// if it is in the address space, are guard pages.
+    pub guard_page: bool,
+    /// Whether the first write to each stack page is detected, to track the
+    /// dirty pages.
+    pub track_dirty: bool,
+    /// Whether the initial stack pages are mapped on first touch. If not,
+    /// they are all mapped before the task starts.
+    pub lazy: bool,
+    /// Number of pages below a faulting stack page that the fault handler
+    /// maps along with it (see [`read_ahead`]).
+    pub readahead: usize,
+    /// Whether the first stack fault of the task maps the whole initial
+    /// stack at once (see [`map_rest_of_region`]), so that it takes no other
+    /// stack fault until it grows.
+    pub first_touch_full: bool,
+    /// Arguments of the program. If there are arguments or environment
+    /// variables, they are pushed on the stack before the task starts.
+    pub args: Vec<String>,
+    /// Environment variables of the program, as `NAME=value`.
+    pub env: Vec<String>,
+    /// Ranges of 4K pages, as offsets from `bottom`, written before the task
+    /// starts (see [`StackLayout::with_page_data`]). They are mapped then, so
+    /// that their first touch takes no fault.
+    pub populated: Vec<Range<usize>>,
+}
+
+impl UserStack {
+    /// Registers the initial stack range in `uspace`, without mapping any
+    /// page (see [`LazyBackend::register`]). The stack thus starts with no
+    /// page table entry, and spawning the task has none to unmap, whatever
+    /// the stack size.
+    pub fn register(&self, uspace: &mut AddrSpace) -> AxResult {
+        self.backend
+            .register(uspace, self.bottom, self.top - self.bottom, self.flags)
+    }
+}
+
+/// Builder of a [`UserStack`] spanning `[base, base + size)`.
+///
+/// The stack is mapped with [`STACK_FLAGS`], and its frames are not shared.
+/// By default it is backed on demand, may not grow, has guard pages, and
+/// holds no arguments.
+pub struct StackLayout {
+    base: VirtAddr,
+    size: usize,
+    max_size: usize,
+    prealloc: Option<PageSize>,
+    guard_page: bool,
+    track_dirty: bool,
+    lazy: bool,
+    readahead: usize,
+    first_touch_full: bool,
+    args: Vec<String>,
+    env: Vec<String>,
+    page_data: Vec<(usize, Vec<u8>)>,
+    random_window: usize,
+}
+
+impl StackLayout {
+    /// Starts the layout of a stack whose lowest address is `base` and whose
+    /// initial size is `size` bytes.
+    pub const fn new(base: VirtAddr, size: usize) -> Self {
+        Self {
+            base,
+            size,
+            max_size: size,
+            prealloc: None,
+            guard_page: true,
+            track_dirty: false,
+            lazy: true,
+            readahead: 0,
+            first_touch_full: false,
+            args: Vec::new(),
+            env: Vec::new(),
+            page_data: Vec::new(),
+            random_window: 0,
+        }
+    }
+
+    /// Sets whether the stack has guard pages: the page below the growth
+    /// limit, where a fault is reported as a stack overflow, and the page
+    /// above the top, where a fault is reported as a stack underflow.
+    ///
+    /// No region may take a guard page, so neither is ever mapped. The page
+    /// above the top is only a guard page if it is in the address space:
+    /// past its end, no page can be mapped anyway.
+    #[allow(dead_code)] // The demo stacks keep the default guard page.
+    pub const fn with_guard_page(mut self, guard_page: bool) -> Self {
+        self.guard_page = guard_page;
+        self
+    }
+
+    /// Lets the stack grow down to `max_size` bytes below its top.
+    pub const fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Backs the initial stack with frames of `page_size` committed up
+    /// front, instead of allocating a frame on each first touch.
+    pub const fn with_prealloc(mut self, page_size: PageSize) -> Self {
+        self.prealloc = Some(page_size);
+        self
+    }
+
+    /// Tracks the dirty pages of the stack: each page is mapped read-only
+    /// until the first write to it.
+    pub const fn with_dirty_tracking(mut self) -> Self {
+        self.track_dirty = true;
+        self
+    }
+
+    /// Sets whether the initial stack pages are mapped on first touch, or
+    /// all before the task starts, e.g. to measure the cost of demand paging.
+    /// Pages the stack grows by are always mapped on first touch.
+    pub const fn with_lazy(mut self, lazy: bool) -> Self {
+        self.lazy = lazy;
+        self
+    }
+
+    /// Maps the `pages` pages below each faulting stack page along with it,
+    /// to save faults on a stack used sequentially downward. Defaults to 0.
+    pub const fn with_readahead(mut self, pages: usize) -> Self {
+        self.readahead = pages;
+        self
+    }
+
+    /// Sets whether the first stack fault maps the whole initial stack, a
+    /// middle ground between mapping each page on first touch and all of them
+    /// before the task starts: the cost is still deferred until the stack is
+    /// used, but paid in a single fault. Defaults to `false`.
+    pub const fn with_first_touch_full(mut self, first_touch_full: bool) -> Self {
+        self.first_touch_full = first_touch_full;
+        self
+    }
+
+    /// Pushes the arguments `args` and the environment `env` on the stack
+    /// before the task starts, as by the System V ABI (see [`push_args`]).
+    pub fn with_args(mut self, args: &[&str], env: &[&str]) -> Self {
+        self.args = args.iter().map(|&arg| arg.into()).collect();
+        self.env = env.iter().map(|&var| var.into()).collect();
+        self
+    }
+
+    /// Moves the whole stack down by a random number of pages, of at most
+    /// `window` bytes, drawn from the kernel RNG when it is built, so that
+    /// repeated runs place it at different addresses. The stack top stays
+    /// aligned to the page size (4K, or the pre-allocated page size), and so
+    /// to [`STACK_ALIGN`]. Defaults to 0, for a fixed base.
+    pub const fn with_random_base(mut self, window: usize) -> Self {
+        self.random_window = window;
+        self
+    }
+
+    /// Writes `data` at `offset` bytes above the stack base before the task
+    /// starts, e.g. for ABI setup, and maps the pages holding it then, so
+    /// that the fault handler does not map them again on their first touch.
+    /// The stack must be pre-allocated (see [`populate_stack_page`]).
+    pub fn with_page_data(mut self, offset: usize, data: &[u8]) -> Self {
+        self.page_data.push((offset, data.into()));
+        self
+    }
+
+    /// Checks the layout and builds the stack, allocating the pre-allocated
+    /// frames if any.
+    ///
+    /// Returns [`SpawnError::AddrSpaceSetup`] if the stack is empty, not
+    /// aligned to 4K (or to the pre-allocated page size), wraps around, or
+    /// may not hold its initial size, [`SpawnError::MisalignedStack`] if its
+    /// top is not aligned to [`STACK_ALIGN`], and [`SpawnError::NoMemory`] if
+    /// the frames cannot be allocated. The errors of writing the page data
+    /// are returned as well. A random base below address 0 is reported as
+    /// [`SpawnError::AddrSpaceSetup`] too.
+    pub fn build(mut self) -> Result<UserStack, SpawnError> {
+        let page_size = self.prealloc.unwrap_or(PageSize::Size4K);
+        if self.random_window > 0 {
+            let slots = (self.random_window / page_size as usize) as u64 + 1;
+            let offset = (syscall::random_u64() % slots) as usize * page_size as usize;
+            self.base = self
+                .base
+                .checked_sub(offset)
+                .ok_or(SpawnError::AddrSpaceSetup)?;
+        }
+        let top = self
+            .base
+            .checked_add(self.size)
+            .ok_or(SpawnError::AddrSpaceSetup)?;
+        if !top.is_aligned(STACK_ALIGN) {
+            return Err(SpawnError::MisalignedStack(top));
+        }
+        if self.size == 0
+            || self.max_size < self.size
+            || !page_size.is_aligned(self.base.as_usize())
+            || !page_size.is_aligned(self.size)
+            || !self.max_size.is_multiple_of(PAGE_SIZE_4K)
+        {
+            return Err(SpawnError::AddrSpaceSetup);
+        }
+        let backend = match self.prealloc {
+            Some(page_size) => {
+                let pages = SharedPages::new(self.size, page_size).map_err(|e| match e {
+                    AxError::NoMemory => SpawnError::NoMemory,
+                    _ => SpawnError::AddrSpaceSetup,
+                })?;
+                for (offset, data) in &self.page_data {
+                    populate_stack_page(&self, &pages, *offset, data)?;
+                }
+                LazyBackend::Prealloc(Arc::new(pages))
+            }
+            None if !self.page_data.is_empty() => return Err(SpawnError::AddrSpaceSetup),
+            None => LazyBackend::OnDemand,
+        };
+        let populated = self
+            .page_data
+            .iter()
+            .filter(|(_, data)| !data.is_empty())
+            .map(|(offset, data)| offset.align_down_4k()..(offset + data.len()).align_up_4k())
+            .collect();
+        Ok(UserStack {
+            top,
+            bottom: self.base,
+            max_size: self.max_size,
+            flags: STACK_FLAGS,
+            backend,
+            cow: false,
+            guard_page: self.guard_page,
+            track_dirty: self.track_dirty,
+            lazy: self.lazy,
+            readahead: self.readahead,
+            first_touch_full: self.first_touch_full,
+            args: self.args,
+            env: self.env,
+            populated,
+        })
+    }
+}
+
+/// Callback observing the page faults of a user task.
+///
+/// It is called with the faulting address and the access flags reported by
+/// the trap, before the fault is handled. It runs in kernel context with the
+/// task's page table active, and must not fault itself: it may not touch user
+/// memory, nor any page of the task that is not mapped yet.
+pub type FaultHook = Box<dyn Fn(VirtAddr, MappingFlags) + Send>;
+
+/// Callback told the exit code a user task passed to `SYS_EXIT` or
+/// `SYS_EXIT_GROUP`.
+///
+/// It is called once by the leader thread, after all of its threads have
+/// exited and before the task itself exits, in kernel context. It is not
+/// called if a fault or trap terminates the task.
+pub type ExitHook = Box<dyn FnOnce(i32) + Send>;
+
+/// Callback told of the first write to the page watched with
+/// [`watch_first_write`].
+///
+/// It is called with the user pc of the writing instruction and the faulting
+/// address, before the page is made writable. It runs in kernel context with
+/// the state of the task locked, and must not fault, as a [`FaultHook`].
+pub type WriteWatchHook = Box<dyn Fn(usize, VirtAddr) + Send>;
+
+/// A user app ready to run: its address space with the app loaded, and its
+/// lazy stack.
+pub struct UserApp {
+    /// Address space of the app.
+    pub uspace: AddrSpace,
+    /// Regions of the loaded app, such as its segments.
+    pub regions: LazyRegions,
+    /// User entry point.
+    pub entry: usize,
+    /// Lazy user stack.
+    pub stack: UserStack,
+    /// Window the program break moves in, demand-paged from its start.
+    pub heap: VirtAddrRange,
+    /// Callback run on every page fault of the task.
+    pub on_fault: Option<FaultHook>,
+    /// Callback run when the task exits with `SYS_EXIT`.
+    pub on_exit: Option<ExitHook>,
+    /// Exit codes of the task when a fault or trap terminates it.
+    pub exit_codes: ExitCodes,
+    /// Number of page faults each thread of the task may take, or `None` for
+    /// no limit.
+    pub max_faults: Option<usize>,
+    /// Function symbols of the app, as collected by the loader.
+    pub symbols: Option<SymbolTable>,
+    /// Where to map the read-only shared info page of the task, whose
+    /// address the entry gets in arg0. Without one, arg0 is 0.
+    pub info_page: Option<VirtAddr>,
+    /// Policy choosing the flags of each page faulted in, or `None` to map
+    /// each page with the flags of its region.
+    pub flags_policy: Option<FlagsPolicy>,
+    /// Whether every thread of the task traps after each user instruction,
+    /// which is logged with its pc and code bytes (see [`step`](crate::step)).
+    /// Ignored, with a warning, where stepping is not supported.
+    pub single_step: bool,
+    /// Whether the heap window past the program break, the page past the
+    /// window and the page past each `SYS_MMAP` mapping placed by the kernel
+    /// are guard pages (see [`ProgramBreak::is_overrun`] and
+    /// [`LazyRegion::guard_page`]).
+    pub heap_guard: bool,
+}
+
+/// Exit codes of a user task terminated by the kernel, by cause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitCodes {
+    /// Exit code after a fault that cannot be handled: a fault outside every
+    /// region, a permission fault, a stack overflow, or a failure to map the
+    /// page.
+    pub segfault: i32,
+    /// Exit code after a trap from user space that is neither a syscall nor
+    /// a page fault.
+    pub trap: i32,
+    /// Exit code after a fault past the fault budget of the task.
+    pub fault_storm: i32,
+    /// Exit code after a fault in a guard page of the stack: a stack
+    /// overflow, or underflow.
+    pub stack_overflow: i32,
+    /// Exit code after a fault past the program break in a guarded heap, or
+    /// in the guard page of a `SYS_MMAP` mapping: an overrun past its end.
+    pub overrun: i32,
+}
+
+impl ExitCodes {
+    /// The exit statuses of a shell for a process killed by `SIGSEGV` (139,
+    /// stack overflows and overruns included), for any other trap, `SIGILL`
+    /// (132), or, past its fault budget, `SIGKILL` (137).
+    pub const POSIX: Self = Self {
+        segfault: 128 + SIGSEGV as i32,
+        trap: 128 + SIGILL as i32,
+        fault_storm: 128 + SIGKILL as i32,
+        stack_overflow: 128 + SIGSEGV as i32,
+        overrun: 128 + SIGSEGV as i32,
+    };
+}
+
+impl Default for ExitCodes {
+    /// Faults and traps exit with -1, a fault past the budget with -2, a
+    /// fault in a stack guard page with -3, and one in the guard page of the
+    /// heap or of a mapping with -4.
+    fn default() -> Self {
+        Self {
+            segfault: -1,
+            trap: -1,
+            fault_storm: -2,
+            stack_overflow: -3,
+            overrun: -4,
+        }
+    }
+}
+
+/// Spawn a user task named `name` that enters user space at `app.entry` and
+/// handles traps, on a kernel stack of `kernel_stack_size` bytes, which must
+/// be a non-zero multiple of 4K; each thread it creates gets one as large.
+/// With feature `kstack-guard`, the bottom of each such stack is checked for
+/// an overflow after every trap (see [`KernelStackGuard`]).
+///
+/// `app.regions` holds the regions already set up in `app.uspace` by the
+/// loader; the stack region and the `app.heap` region are added to them.
 ///
 /// This task implements **lazy (demand) paging** for the user stack:
-/// - The stack area is registered with map_alloc(populate=false)
+/// - The stack area is registered without mapping any page
+///   (see [`LazyBackend::register`]).
 /// - When the user first touches the stack, a page fault occurs.
-/// - The handler uses AddrSpace::handle_page_fault to allocate
-///   and map the page on demand, then resumes execution.
-pub fn spawn_user_task(
-    mut uspace: AddrSpace,
-    ustack_top: VirtAddr,
-    _ustack_vaddr: VirtAddr,
-) -> AxTaskRef {
-    let page_table_root = uspace.page_table_root();
+/// - With [`LazyBackend::OnDemand`], the handler uses
+///   AddrSpace::handle_page_fault to allocate a zeroed frame and map it.
+/// - With [`LazyBackend::Prealloc`], the handler maps the matching page of
+///   the [`SharedPages`](crate::mm::SharedPages), which may be a 2M huge page
+///   to reduce the number of faults.
+///
+/// If `app.stack.lazy` is cleared, the initial stack pages are all mapped
+/// before the task starts instead, so touching them takes no fault. If
+/// `app.stack.first_touch_full` is set, they are all mapped by the first stack
+/// fault of the task, whichever thread takes it.
+///
+/// The stack grows downward on demand: a fault below the current stack bottom
+/// but within `app.stack.max_size` bytes of `app.stack.top` extends the stack
+/// down to the faulting page, which is then demand-paged with a fresh frame.
+/// Unless `app.stack.guard_page` is cleared, the page just below that limit is
+/// a guard page, and a fault there is reported as a stack overflow; so is the
+/// page just above `app.stack.top`, if it is in `app.uspace`, where a fault is
+/// a stack underflow. Both exit with `app.exit_codes.stack_overflow`.
+///
+/// If `app.heap_guard` is set, the pages of the heap window past the program
+/// break, the page just past the window, and the page just past each
+/// `SYS_MMAP` mapping placed by the kernel, are guard pages as well: a fault
+/// there is reported as a heap overrun, and exits with
+/// `app.exit_codes.overrun`.
+///
+/// The task keeps the list of its demand-paged regions in [`LazyRegions`]:
+/// the stack, the stack extensions, the heap and the `SYS_MMAP` mappings. A
+/// fault is dispatched to the backend of the region containing it, and a
+/// fault that hits no region is a segmentation fault.
+///
+/// In both cases execution resumes once the page is mapped. The heap grown by
+/// `SYS_BRK` is demand-paged the same way as the on-demand stack, and so are
+/// the anonymous mappings made by `SYS_MMAP`, with the permissions requested
+/// by the user. A fault that cannot be satisfied only terminates this task,
+/// never the kernel.
+///
+/// Every stack page is mapped with exactly `app.stack.flags`. A fault whose
+/// access type is not allowed by the region it hits (e.g. a write to a
+/// read-only page, or an instruction fetch from a non-executable one) is
+/// reported as a permission fault and terminates the task.
+///
+/// If `app.stack.cow` is set, the frames of a [`LazyBackend::Prealloc`] stack
+/// are shared copy-on-write: several tasks may be spawned with the same
+/// [`SharedPages`](crate::mm::SharedPages), and each gets a private copy of a
+/// page on its first write to it.
+///
+/// If `app.stack.track_dirty` is set, the stack pages are first mapped without
+/// `WRITE`, and the first write to each of them only makes it writable, and
+/// marks it dirty. The dirty pages can be read with [`dirty_stack_pages`]
+/// once the task has exited.
+///
+/// Every page fault is accounted in the task's [`FaultCounters`], which can be
+/// read with [`stats::page_fault_stats`] even after the task has exited.
+/// If `app.on_fault` is given, it is called on every page fault before the
+/// fault is handled; see [`FaultHook`].
+///
+/// A fault or trap that terminates the task makes it exit with the matching
+/// code of `app.exit_codes`. If `app.on_exit` is given, it is told the code
+/// the task passed to `SYS_EXIT` instead, when it does; see [`ExitHook`].
+/// An unexpected trap is reported with the user pc, and the function it is
+/// in if `app.symbols` has it.
+///
+/// If `app.max_faults` is given, a thread that takes more page faults than
+/// that, handled or not, is terminated with `app.exit_codes.fault_storm`
+/// after a "fault storm" message: a payload faulting in a loop cannot keep
+/// the kernel busy forever.
+///
+/// If `app.stack` holds arguments or environment variables, they are pushed
+/// on the stack, and the task starts with its stack pointer at `argc` (see
+/// [`push_args`]). Otherwise it starts at the stack top.
+///
+/// The task may create threads with `SYS_CLONE`. They share its address
+/// space and regions, so a page faulted in by one thread is mapped for all of
+/// them, but have their own fault counters and no fault hook. The task
+/// exits once all of its threads have exited. A thread calling
+/// `SYS_EXIT_GROUP` has every other thread exit with the same code as soon
+/// as it returns from user space, or from a wait for console input: a
+/// thread spinning in user space only returns on an interrupt, with feature
+/// `preempt`.
+///
+/// With feature `fp-simd`, the FP/SIMD registers of the task are saved and
+/// restored on each switch to another task, and enabled for user space, so
+/// an FP value survives the page faults and syscalls that block in between.
+///
+/// Returns [`SpawnError::AddrSpaceSetup`] if the stack range, its growth
+/// window or its lower guard page is not inside `app.uspace`, if the bottom or
+/// the top of the stack is not aligned to 4K, if a region of
+/// `app.regions` takes a guard page or the heap window, if the stack is not
+/// fully backed by the pre-allocated pages, or if it tracks dirty pages and
+/// is shared copy-on-write or backed by huge pages, and
+/// [`SpawnError::MisalignedStack`] if `app.stack.top` is not aligned to
+/// [`STACK_ALIGN`]. The errors of mapping an eager stack and of pushing the
+/// arguments are returned as well.
+pub fn spawn_user_task_at(
+    app: UserApp,
+    name: String,
+    kernel_stack_size: usize,
+) -> Result<AxTaskRef, SpawnError> {
+    assert!(
+        kernel_stack_size > 0 && kernel_stack_size.is_multiple_of(PAGE_SIZE_4K),
+        "kernel stack size {:#x} is not a non-zero multiple of 4K",
+        kernel_stack_size
+    );
+    let UserApp {
+        mut uspace,
+        mut regions,
+        entry,
+        stack,
+        heap,
+        on_fault,
+        on_exit,
+        exit_codes,
+        max_faults,
+        symbols,
+        info_page: info_addr,
+        flags_policy,
+        single_step,
+        heap_guard,
+    } = app;
+    let UserStack {
+        top: ustack_top,
+        bottom: ustack_vaddr,
+        max_size: max_stack_size,
+        flags: stack_flags,
+        backend: stack_backend,
+        cow,
+        guard_page: has_guard_page,
+        track_dirty,
+        lazy,
+        readahead,
+        first_touch_full,
+        args,
+        env,
+        populated,
+    } = stack;
+    if !ustack_top.is_aligned(STACK_ALIGN) {
+        return Err(SpawnError::MisalignedStack(ustack_top));
+    }
+    // The fault handler indexes the stack pages from the bottom, by whole
+    // pages: a bound cutting a page would leave a last page with no frame.
+    if !ustack_vaddr.is_aligned_4k() || !ustack_top.is_aligned_4k() {
+        return Err(SpawnError::AddrSpaceSetup);
+    }
+    let ustack_size = ustack_top
+        .checked_sub_addr(ustack_vaddr)
+        .ok_or(SpawnError::AddrSpaceSetup)?;
+    if !uspace.contains_range(ustack_vaddr, ustack_size) || max_stack_size < ustack_size {
+        return Err(SpawnError::AddrSpaceSetup);
+    }
+    // Lowest address the stack may grow down to, and the guard page below it.
+    // Without a guard page, `guard_page..ustack_limit` is empty.
+    let ustack_limit = ustack_top
+        .checked_sub(max_stack_size)
+        .filter(|limit| limit.is_aligned_4k())
+        .ok_or(SpawnError::AddrSpaceSetup)?;
+    let guard_page = if has_guard_page {
+        ustack_limit
+            .checked_sub(PAGE_SIZE_4K)
+            .filter(|&guard| guard >= uspace.base())
+            .ok_or(SpawnError::AddrSpaceSetup)?
+    } else {
+        ustack_limit
+    };
+    let guard_top = match ustack_top.checked_add(PAGE_SIZE_4K) {
+        Some(end) if has_guard_page && end <= uspace.end() => end,
+        _ => ustack_top,
+    };
+    if regions.overlaps(guard_page, ustack_limit) || regions.overlaps(ustack_top, guard_top) {
+        return Err(SpawnError::AddrSpaceSetup);
+    }
+    if let LazyBackend::Prealloc(pages) = &stack_backend {
+        // Each fault maps a whole page of `pages.page_size`, which must not
+        // cross the stack bounds. Private copies are only made of 4K pages.
+        let page_size = pages.page_size;
+        if pages.size() < ustack_size
+            || !page_size.is_aligned(ustack_vaddr.as_usize())
+            || !page_size.is_aligned(ustack_top.as_usize())
+            || ((cow || track_dirty) && page_size.is_huge())
+            || (cow && track_dirty)
+        {
+            return Err(SpawnError::AddrSpaceSetup);
+        }
+    }
 
-    // Create the user context: entry point, stack top, arg0=0
-    let entry = crate::APP_ENTRY;
-    let sp = ustack_top;
+    regions.insert(LazyRegion::new(
+        ustack_vaddr,
+        ustack_top,
+        stack_flags,
+        stack_backend,
+    ));
+    let heap_region = LazyRegion::new(heap.start, heap.end, HEAP_FLAGS, LazyBackend::OnDemand)
+        .with_guard_page(heap_guard);
+    if regions.overlaps(heap_region.start, heap_region.guard_end()) {
+        return Err(SpawnError::AddrSpaceSetup);
+    }
+    regions.insert(heap_region);
+    let info_page = match info_addr {
+        Some(addr) => {
+            let end = addr.checked_add(PAGE_SIZE_4K);
+            if !addr.is_aligned_4k()
+                || !uspace.contains_range(addr, PAGE_SIZE_4K)
+                || end.is_none_or(|end| regions.overlaps(addr, end))
+            {
+                return Err(SpawnError::AddrSpaceSetup);
+            }
+            let page = Arc::new(InfoPage::new().map_err(|_| SpawnError::NoMemory)?);
+            regions.insert(LazyRegion::new(
+                addr,
+                addr + PAGE_SIZE_4K,
+                INFO_FLAGS,
+                LazyBackend::Custom(page.clone()),
+            ));
+            Some(page)
+        }
+        None => None,
+    };
+    let mut mem = UserMemory {
+        regions,
+        brk: ProgramBreak::new(heap.start, heap.size()).with_guard(heap_guard),
+        mmap: MmapArea::new(crate::USER_MMAP_BASE.into(), crate::USER_MMAP_SIZE)
+            .with_guard_pages(heap_guard),
+        cow,
+        frames: BTreeMap::new(),
+        dirty: track_dirty.then(|| DirtyPages::new(ustack_top, ustack_limit)),
+        flags_policy,
+    };
+    if !lazy {
+        map_eagerly(&mut uspace, &mut mem, ustack_vaddr, ustack_top)?;
+    }
+    for pages in populated {
+        map_eagerly(
+            &mut uspace,
+            &mut mem,
+            ustack_vaddr + pages.start,
+            ustack_vaddr + pages.end,
+        )?;
+    }
+    let sp = if args.is_empty() && env.is_empty() {
+        ustack_top
+    } else {
+        push_args(&mut uspace, &mut mem, ustack_vaddr, ustack_top, &args, &env)?
+    };
+    let single_step = single_step && {
+        if !step::is_supported() {
+            warn!("single-stepping is not supported on this architecture");
+        }
+        step::is_supported()
+    };
+    let process = Arc::new(Mutex::new(UserProcess {
+        uspace,
+        mem,
+        stack: StackGrowth {
+            top: ustack_top,
+            limit: ustack_limit,
+            guard_page,
+            guard_top,
+            bottom: ustack_vaddr,
+            flags: stack_flags,
+            readahead,
+            first_touch_full: lazy && first_touch_full,
+        },
+        kernel_stack_size,
+        exit_codes,
+        max_faults,
+        symbols,
+        on_exit,
+        write_watch: None,
+        pid: 0,
+        threads: Vec::new(),
+        group_exit: None,
+        info_page,
+        console: ConsoleBuffer::default(),
+        single_step,
+    }));
+    // Create the user context: entry point, stack pointer, and in arg0 the
+    // address of the shared info page, or 0.
+    debug_assert!(sp.is_aligned(STACK_ALIGN));
+    let arg0 = info_addr.map_or(0, VirtAddr::as_usize);
+    let uctx = UserContext::new(entry, sp, arg0);
+    let task = spawn_thread(process, uctx, SignalState::default(), on_fault, name, true);
+    Ok(task)
+}
+
+/// Spawns a task running the user context `uctx` in the address space of
+/// `process`, with the signal state `signals`.
+///
+/// The leader thread of a user task, once it has exited, waits for the
+/// threads created with `SYS_CLONE` to exit, then parks `process` in
+/// [`EXITED_TASKS`] (see [`exit_user_task`]). Any other thread merely drops
+/// its reference: the leader still holds one until it has joined the thread,
+/// so the address space is never freed on a thread's own page table.
+fn spawn_thread(
+    process: SharedProcess,
+    uctx: UserContext,
+    signals: SignalState,
+    on_fault: Option<FaultHook>,
+    name: String,
+    leader: bool,
+) -> AxTaskRef {
+    let (page_table_root, kernel_stack_size) = {
+        let process = process.lock();
+        (process.uspace.page_table_root(), process.kernel_stack_size)
+    };
+    let counters = Arc::new(FaultCounters::default());
+    let task_counters = counters.clone();
+    let process_ref = process.clone();
 
     let mut task = TaskInner::new(
         move || {
-            let mut aligned_uctx = AlignedUserContext(UserContext::new(entry, sp, 0));
-
             ax_println!(
                 "Enter user space: entry={:#x}, ustack={:#x}, kstack={:#x}",
-                entry,
-                sp,
-                axtask::current().kernel_stack_top().unwrap(),
+                uctx.ip(),
+                uctx.sp(),
+                axtask::current().kernel_stack_top().unwrap_or_default(),
             );
-
+            let kstack_guard = cfg!(feature = "kstack-guard")
+                .then(|| KernelStackGuard::paint(kernel_stack_size))
+                .flatten();
+            let exit = run_user_thread(
+                &process,
+                uctx,
+                signals,
+                on_fault,
+                &task_counters,
+                kstack_guard.as_ref(),
+            );
+            RUNNING_THREADS
+                .lock()
+                .remove(&axtask::current().id().as_u64());
+            print_fault_summary(&task_counters);
+            if !leader {
+                drop(process);
+                axtask::exit(exit.code());
+            }
             loop {
-                let reason = aligned_uctx.0.run();
-                match reason {
-                    ReturnReason::Syscall => {
-                        if let Some(exit_code) = syscall::handle_syscall(&mut aligned_uctx.0) {
-                            axtask::exit(exit_code as _);
-                        }
+                let Some(thread) = process.lock().threads.pop() else {
+                    break;
+                };
+                thread.join();
+            }
+            // No thread is left to write: the last partial line goes out
+            // before anything else is printed for the task.
+            process.lock().console.flush();
+            if let ThreadExit::Requested(code) = exit {
+                // Taken out first: the hook may not run under the lock.
+                let on_exit = process.lock().on_exit.take();
+                if let Some(hook) = on_exit {
+                    hook(code);
+                }
+            }
+            exit_user_task(process, exit.code())
+        },
+        name,
+        kernel_stack_size,
+    );
+
+    // The task id is known once the task is created, before it may run.
+    if leader {
+        let mut process = process_ref.lock();
+        process.pid = task.id().as_u64();
+        if let Some(info_page) = &process.info_page {
+            info_page.set_pid(process.pid);
+        }
+    }
+
+    // Set the page table root so the scheduler switches to user space
+    // page table when this task is scheduled.
+    task.ctx_mut().set_page_table_root(page_table_root);
+
+    RUNNING_THREADS
+        .lock()
+        .insert(task.id().as_u64(), process_ref);
+    let task = axtask::spawn_task(task);
+    stats::register(&task, counters);
+    if leader {
+        stats::task_spawned();
+    }
+    task
+}
+
+/// Canary painted over the lowest bytes of the kernel stack of a user
+/// thread, with feature `kstack-guard`.
+///
+/// Kernel stacks are allocated from the kernel heap, in the linear mapping,
+/// so no guard page can be unmapped below them. Instead, an overflow that
+/// reached the canary is caught after the trap that caused it, and panics
+/// with the overwritten address rather than corrupting the neighbours of the
+/// stack unnoticed. An overflow that jumps over the canary is not caught.
+struct KernelStackGuard {
+    bottom: VirtAddr,
+}
+
+impl KernelStackGuard {
+    /// Number of words of the canary.
+    const WORDS: usize = 64;
+    const CANARY: usize = 0x5a5a_a5a5_c3c3_3c3c;
+
+    /// Paints the canary at the bottom of the kernel stack of the current
+    /// task, `size` bytes below its top. Returns `None` if the task has no
+    /// kernel stack of its own.
+    fn paint(size: usize) -> Option<Self> {
+        let bottom = axtask::current().kernel_stack_top()?.checked_sub(size)?;
+        let words: *mut usize = bottom.as_mut_ptr().cast();
+        for i in 0..Self::WORDS {
+            // Far below the frames of the task, which just started.
+            unsafe { words.add(i).write_volatile(Self::CANARY) };
+        }
+        Some(Self { bottom })
+    }
+
+    /// Panics if the canary was overwritten.
+    fn check(&self) {
+        let words: *const usize = self.bottom.as_ptr().cast();
+        for i in 0..Self::WORDS {
+            let word = unsafe { words.add(i) };
+            if unsafe { word.read_volatile() } != Self::CANARY {
+                panic!(
+                    "kernel stack overflow in {}: canary at {:#x} overwritten",
+                    axtask::current().id_name(),
+                    word as usize
+                );
+            }
+        }
+    }
+}
+
+/// Runs the user context `uctx` of a thread of `process` until the thread
+/// exits, handling its syscalls and page faults, and returns how it exited.
+///
+/// However the thread exits, the user word it set with
+/// `SYS_SET_TID_ADDRESS`, if any, is then cleared. Once another thread has
+/// exited the group, the thread exits with the code of the group before it
+/// enters user space again.
+///
+/// The state of `process` is locked while a trap is handled, never while
+/// user code runs. With `kstack_guard`, the kernel stack is checked for an
+/// overflow after each trap.
+fn run_user_thread(
+    process: &SharedProcess,
+    uctx: UserContext,
+    mut signals: SignalState,
+    on_fault: Option<FaultHook>,
+    task_counters: &FaultCounters,
+    kstack_guard: Option<&KernelStackGuard>,
+) -> ThreadExit {
+    let mut aligned_uctx = AlignedUserContext(uctx);
+    // The user word to clear on exit, set with `SYS_SET_TID_ADDRESS`.
+    let mut clear_tid = None;
+    let single_step = process.lock().single_step;
+    let exit = loop {
+        // Another thread exited the whole group: this one follows before it
+        // runs any more user code, so that the leader can join it.
+        if let Some(code) = process.lock().group_exit {
+            break ThreadExit::Requested(code);
+        }
+        if let Some(guard) = kstack_guard {
+            guard.check();
+        }
+        if single_step {
+            step::arm(&mut aligned_uctx.0);
+        }
+        let reason = aligned_uctx.0.run();
+        match reason {
+            ReturnReason::Syscall => {
+                let request = {
+                    let mut process = process.lock();
+                    let UserProcess {
+                        uspace,
+                        mem,
+                        pid,
+                        console,
+                        ..
+                    } = &mut *process;
+                    syscall::handle_syscall(
+                        &mut aligned_uctx.0,
+                        uspace,
+                        mem,
+                        *pid,
+                        &mut signals,
+                        console,
+                    )
+                };
+                match request {
+                    None => {}
+                    Some(TaskRequest::Exit(exit_code)) => break ThreadExit::Requested(exit_code),
+                    Some(TaskRequest::ExitGroup(exit_code)) => {
+                        // The first thread to exit the group sets its code.
+                        let code = *process.lock().group_exit.get_or_insert(exit_code);
+                        break ThreadExit::Requested(code);
                     }
-                    ReturnReason::PageFault(vaddr, flags) => {
-                        // Convert flags to PageFaultFlags for handle_page_fault
-                        let access_flags = if flags.contains(MappingFlags::WRITE) {
-                            PageFaultFlags::WRITE
-                        } else if flags.contains(MappingFlags::EXECUTE) {
-                            PageFaultFlags::EXECUTE
+                    Some(TaskRequest::Clone { stack }) => {
+                        let mut child_uctx = aligned_uctx.0;
+                        child_uctx.set_sp(stack.as_usize());
+                        syscall::set_syscall_return(&mut child_uctx, 0);
+                        let name = format!("{}-thread", axtask::current().name());
+                        let child = spawn_thread(
+                            process.clone(),
+                            child_uctx,
+                            signals.for_new_thread(),
+                            None,
+                            name,
+                            false,
+                        );
+                        let tid = child.id().as_u64() as usize;
+                        process.lock().threads.push(child);
+                        syscall::set_syscall_return(&mut aligned_uctx.0, tid);
+                    }
+                    // The page table root is reinstalled when the thread is
+                    // switched back in, so its mapped pages survive the sleep.
+                    Some(TaskRequest::Sleep(duration)) => {
+                        if duration.is_zero() {
+                            axtask::yield_now();
                         } else {
-                            PageFaultFlags::READ
-                        };
+                            axtask::sleep(duration);
+                        }
+                        syscall::set_syscall_return(&mut aligned_uctx.0, 0);
+                    }
+                    Some(TaskRequest::ReadConsole { buf, len }) => {
+                        let input =
+                            syscall::read_console(len, || process.lock().group_exit.is_some());
+                        let mut process = process.lock();
+                        let UserProcess { uspace, mem, .. } = &mut *process;
+                        let ret = syscall::complete_read(uspace, mem, buf, &input);
+                        syscall::set_syscall_return(&mut aligned_uctx.0, ret);
+                    }
+                    Some(TaskRequest::SetTidAddress(tid_addr)) => {
+                        clear_tid = (tid_addr.as_usize() != 0).then_some(tid_addr);
+                        let tid = axtask::current().id().as_u64() as usize;
+                        syscall::set_syscall_return(&mut aligned_uctx.0, tid);
+                    }
+                }
+            }
+            ReturnReason::PageFault(vaddr, flags) => {
+                if let Some(hook) = &on_fault {
+                    hook(vaddr, flags);
+                }
+                let faults = task_counters.fault();
+                let mut process = process.lock();
+                let UserProcess {
+                    uspace,
+                    mem,
+                    stack,
+                    exit_codes,
+                    max_faults,
+                    write_watch,
+                    ..
+                } = &mut *process;
+                if max_faults.is_some_and(|max| faults > max) {
+                    ax_println!(
+                        "{}: fault storm: {} page faults, last at {:#x}, exit!",
+                        axtask::current().id_name(),
+                        faults,
+                        vaddr
+                    );
+                    break ThreadExit::Killed(exit_codes.fault_storm);
+                }
+                // Convert flags to PageFaultFlags for handle_page_fault
+                let access_flags = if flags.contains(MappingFlags::WRITE) {
+                    PageFaultFlags::WRITE
+                } else if flags.contains(MappingFlags::EXECUTE) {
+                    PageFaultFlags::EXECUTE
+                } else {
+                    PageFaultFlags::READ
+                };
 
-                        // Try to handle page fault using AddrSpace's handler
-                        if uspace.handle_page_fault(vaddr, access_flags) {
-                            ax_println!("handle page fault OK! addr={:#x}", vaddr);
-                        } else {
+                let guard = if (stack.guard_page..stack.limit).contains(&vaddr) {
+                    Some("overflow")
+                } else if (stack.top..stack.guard_top).contains(&vaddr) {
+                    Some("underflow")
+                } else {
+                    None
+                };
+                if let Some(guard) = guard {
+                    ax_println!(
+                        "{}: stack {} at {:#x}, exit!",
+                        axtask::current().id_name(),
+                        guard,
+                        vaddr
+                    );
+                    task_counters.segfault();
+                    break ThreadExit::Killed(exit_codes.stack_overflow);
+                }
+                if mem.brk.is_overrun(vaddr) {
+                    ax_println!(
+                        "{}: heap overrun at {:#x}, past the break [{:#x}, {:#x}), exit!",
+                        axtask::current().id_name(),
+                        vaddr,
+                        mem.brk.base(),
+                        mem.brk.current()
+                    );
+                    task_counters.segfault();
+                    break ThreadExit::Killed(exit_codes.overrun);
+                }
+                if let Some(region) = mem.regions.find_guard(vaddr) {
+                    ax_println!(
+                        "{}: heap overrun at {:#x}, past [{:#x}, {:#x}), exit!",
+                        axtask::current().id_name(),
+                        vaddr,
+                        region.start,
+                        region.end
+                    );
+                    task_counters.segfault();
+                    break ThreadExit::Killed(exit_codes.overrun);
+                }
+                // The first write to the watched page, which is still clean,
+                // is reported before the page is made writable.
+                if let Some((page, hook)) = write_watch
+                    && access_flags.contains(PageFaultFlags::WRITE)
+                    && vaddr.align_down_4k() == *page
+                    && mem
+                        .dirty
+                        .as_ref()
+                        .is_some_and(|dirty| !dirty.is_dirty(vaddr))
+                {
+                    hook(aligned_uctx.0.ip(), vaddr);
+                }
+                // Only the mapping of the page is timed, not the bookkeeping
+                // and logging around it.
+                let start = cfg!(feature = "fault_timing").then(axhal::time::monotonic_time);
+                // A fault below the stack bottom, within the growth
+                // window, first extends the stack down to the page.
+                let grown = if mem.regions.find(vaddr).is_none()
+                    && (stack.limit..stack.bottom).contains(&vaddr)
+                {
+                    grow_stack(
+                        uspace,
+                        &mut mem.regions,
+                        &mut stack.bottom,
+                        vaddr,
+                        stack.flags,
+                    )
+                } else {
+                    Ok(())
+                };
+                let outcome = match grown {
+                    Ok(()) => handle_fault(uspace, mem, vaddr, access_flags),
+                    Err(e) => FaultOutcome::Segfault(Some(e)),
+                };
+                if let Some(start) = start {
+                    task_counters.fault_time(axhal::time::monotonic_time() - start);
+                }
+                match outcome {
+                    FaultOutcome::Mapped => {
+                        task_counters.page_mapped();
+                        if is_zero_page(uspace, vaddr) {
+                            task_counters.zero_page_mapped();
+                        }
+                        if (stack.limit..stack.top).contains(&vaddr) {
+                            task_counters.stack_fault(vaddr);
+                            let more = if stack.first_touch_full {
+                                stack.first_touch_full = false;
+                                map_rest_of_region(uspace, mem, vaddr)
+                            } else {
+                                read_ahead(uspace, mem, vaddr, stack.readahead)
+                            };
+                            for _ in 0..more {
+                                task_counters.page_mapped();
+                            }
+                        }
+                        // Only looked up if logged.
+                        fault_log!(
+                            "handle page fault OK! addr={:#x}: {}",
+                            vaddr,
+                            PageMapping::new(uspace, &mem.regions, vaddr)
+                        );
+                    }
+                    FaultOutcome::AlreadyMapped => {
+                        fault_log!("spurious page fault at {:#x}", vaddr);
+                    }
+                    FaultOutcome::Dirtied => {
+                        fault_log!("first write to stack page at {:#x}", vaddr);
+                    }
+                    FaultOutcome::Permission(denied) => {
+                        if signals.deliver_segv(
+                            &mut aligned_uctx.0,
+                            uspace,
+                            mem,
+                            vaddr,
+                            SEGV_ACCERR,
+                        ) {
                             ax_println!(
-                                "{}: segmentation fault at {:#x}, exit!",
+                                "{}: {} at {:#x}, SIGSEGV delivered",
                                 axtask::current().id_name(),
+                                denied,
                                 vaddr
                             );
-                            axtask::exit(-1);
+                            continue;
+                        }
+                        ax_println!(
+                            "{}: {} at {:#x}, exit!",
+                            axtask::current().id_name(),
+                            denied,
+                            vaddr
+                        );
+                        if cfg!(feature = "debug_faults") {
+                            dump_regions(uspace, mem);
                         }
+                        task_counters.segfault();
+                        break ThreadExit::Killed(exit_codes.segfault);
                     }
-                    _ => {
-                        ax_println!("Unexpected trap from user space: {:?}", reason);
-                        axtask::exit(-1);
+                    FaultOutcome::Segfault(None) => {
+                        if signals.deliver_segv(
+                            &mut aligned_uctx.0,
+                            uspace,
+                            mem,
+                            vaddr,
+                            SEGV_MAPERR,
+                        ) {
+                            ax_println!(
+                                "{}: segmentation fault at {:#x} ({:?}), SIGSEGV delivered",
+                                axtask::current().id_name(),
+                                vaddr,
+                                flags
+                            );
+                            continue;
+                        }
+                        ax_println!(
+                            "{}: segmentation fault at {:#x} ({:?}), exit!",
+                            axtask::current().id_name(),
+                            vaddr,
+                            flags
+                        );
+                        if cfg!(feature = "debug_faults") {
+                            dump_regions(uspace, mem);
+                        }
+                        task_counters.segfault();
+                        break ThreadExit::Killed(exit_codes.segfault);
+                    }
+                    FaultOutcome::Segfault(Some(e)) => {
+                        ax_println!(
+                            "{}: cannot handle page fault at {:#x}: {}, exit!",
+                            axtask::current().id_name(),
+                            vaddr,
+                            e
+                        );
+                        if cfg!(feature = "debug_faults") {
+                            dump_regions(uspace, mem);
+                        }
+                        task_counters.segfault();
+                        break ThreadExit::Killed(exit_codes.segfault);
                     }
                 }
             }
-        },
-        "userboot".into(),
-        crate::KERNEL_STACK_SIZE,
+            // The interrupt was handled before `run` returned, and a timer
+            // tick may have switched to other tasks since: resume the thread.
+            ReturnReason::Interrupt => task_counters.interrupt(),
+            // The stepped instruction has run: log the next one and resume.
+            _ if single_step && step::is_step_trap(&reason) => {
+                let process = process.lock();
+                let current = axtask::current();
+                step::log_step(&process.uspace, &current.id_name(), aligned_uctx.0.ip());
+            }
+            ReturnReason::Exception(_) | ReturnReason::Unknown => {
+                let process = process.lock();
+                let pc = aligned_uctx.0.ip();
+                match process.symbols.as_ref().and_then(|s| s.lookup(pc)) {
+                    Some((name, offset)) => ax_println!(
+                        "Unexpected trap from user space: {:?} at pc {:#x} ({}+{:#x})",
+                        reason,
+                        pc,
+                        name,
+                        offset
+                    ),
+                    None => ax_println!(
+                        "Unexpected trap from user space: {:?} at pc {:#x}",
+                        reason,
+                        pc
+                    ),
+                }
+                break ThreadExit::Killed(process.exit_codes.trap);
+            }
+        }
+    };
+    if let Some(guard) = kstack_guard {
+        guard.check();
+    }
+    if let Some(tid_addr) = clear_tid {
+        let mut process = process.lock();
+        let UserProcess { uspace, mem, .. } = &mut *process;
+        // There is no futex to wake: a waiter must poll the word. A word that
+        // cannot be written is left alone, as on Linux.
+        let _ = syscall::copy_to_user(uspace, mem, tid_addr, &0u32.to_ne_bytes());
+    }
+    exit
+}
+
+/// Spawns `n` user tasks named `userboot-0`, `userboot-1`, ...
+///
+/// `setup(i)` builds the app run by the `i`-th task. Every task gets its own
+/// address space, and so its own page table root, which the scheduler installs
+/// whenever the task is switched in: a fault in one task only ever maps pages
+/// into that task's page table. Each task also has its own fault counters.
+///
+/// On error, the tasks spawned so far keep running.
+pub fn spawn_user_tasks(
+    n: usize,
+    mut setup: impl FnMut(usize) -> Result<UserApp, SpawnError>,
+) -> Result<Vec<AxTaskRef>, SpawnError> {
+    (0..n)
+        .map(|i| {
+            let app = setup(i)?;
+            spawn_user_task_at(app, format!("userboot-{}", i), crate::KERNEL_STACK_SIZE)
+        })
+        .collect()
+}
+
+/// Runs the user app `bytes` to completion in a new task started with the
+/// arguments `args`, and returns its exit code.
+///
+/// The app is loaded as by [`loader::load_user_image`] into a new address
+/// space, with the lazy stack, heap window and entry point of the demo, and
+/// spawned with [`spawn_user_task_at`] with no hooks nor fault budget. The
+/// call blocks until the task exits, then reaps it, so all the frames of the
+/// task are freed on return. A fault or trap that terminates the task makes
+/// it exit with the default [`ExitCodes`]. The symbols of the app are kept,
+/// with the `user-symbols` feature, to name the code of an unexpected trap.
+///
+/// Returns [`SpawnError::BadApp`] if `bytes` cannot be loaded, and the
+/// errors of setting up the stack and spawning the task.
+pub fn run_user_payload(bytes: &[u8], args: &[&str]) -> Result<i32, SpawnError> {
+    let mut uspace = axmm::new_user_aspace(VirtAddr::from(0), crate::USER_ASPACE_SIZE)
+        .map_err(|_| SpawnError::AddrSpaceSetup)?;
+    let mut regions = LazyRegions::new();
+    let image = loader::load_user_image("payload", bytes.into(), &mut uspace, &mut regions)
+        .map_err(|_| SpawnError::BadApp)?;
+    let stack = StackLayout::new(
+        uspace.end() - crate::USER_STACK_SIZE,
+        crate::USER_STACK_SIZE,
+    )
+    .with_max_size(crate::USER_STACK_MAX_SIZE)
+    .with_args(args, &[])
+    .build()?;
+    stack
+        .register(&mut uspace)
+        .map_err(|_| SpawnError::AddrSpaceSetup)?;
+    let app = UserApp {
+        uspace,
+        regions,
+        entry: image.entry,
+        stack,
+        heap: VirtAddrRange::from_start_size(crate::USER_HEAP_BASE.into(), crate::USER_HEAP_SIZE),
+        on_fault: None,
+        on_exit: None,
+        exit_codes: ExitCodes::default(),
+        max_faults: None,
+        symbols: image.symbols,
+        info_page: None,
+        flags_policy: None,
+        single_step: false,
+        heap_guard: false,
+    };
+    let task = spawn_user_task_at(app, String::from("payload"), crate::KERNEL_STACK_SIZE)?;
+    let exit_code = task.join();
+    reap_user_task(&task);
+    Ok(exit_code)
+}
+
+/// Adds `region` to the lazy regions of the user task that the thread `task`
+/// belongs to, while it runs.
+///
+/// The range of `region` is registered in the address space of the task
+/// without mapping any page, as for the regions set up at spawn time: its
+/// pages are faulted in from its backend on first touch.
+///
+/// Returns [`AxError::NotFound`] if `task` is not running user code, and
+/// [`AxError::InvalidInput`] if `region` is not inside the address space, or
+/// overlaps a region or a stack guard page of the task.
+///
+/// This must not be called from the handling of a trap of that task (see
+/// [`SharedProcess`]).
+#[allow(dead_code)] // The demo has no region to add from outside the task.
+pub fn add_lazy_region(task: &AxTaskRef, region: LazyRegion) -> AxResult {
+    let process = RUNNING_THREADS
+        .lock()
+        .get(&task.id().as_u64())
+        .cloned()
+        .ok_or(AxError::NotFound)?;
+    let mut process = process.lock();
+    let UserProcess {
+        uspace, mem, stack, ..
+    } = &mut *process;
+    let size = region.end - region.start;
+    let overlaps = |start: VirtAddr, end: VirtAddr| region.start < end && start < region.end;
+    if !uspace.contains_range(region.start, size)
+        || mem.regions.overlaps(region.start, region.end)
+        || overlaps(stack.guard_page, stack.limit)
+        || overlaps(stack.top, stack.guard_top)
+    {
+        return Err(AxError::InvalidInput);
+    }
+    region
+        .backend
+        .register(uspace, region.start, size, region.flags)?;
+    mem.regions.insert(region);
+    Ok(())
+}
+
+/// Watches the page at `page` of the stack of the user task that the thread
+/// `task` belongs to: `hook` is told of the first write to it, by the fault
+/// that makes the page writable (see [`WriteWatchHook`]).
+///
+/// The stack of the task must track its dirty pages, each of which is mapped
+/// read-only until its first write. A task has a single watchpoint, for its
+/// lifetime.
+///
+/// Returns [`AxError::NotFound`] if `task` is not running user code,
+/// [`AxError::Unsupported`] if its stack does not track dirty pages,
+/// [`AxError::InvalidInput`] if `page` is not a page of the tracked stack,
+/// [`AxError::BadState`] if the page was written to already, and
+/// [`AxError::AlreadyExists`] if the task has a watchpoint already.
+///
+/// This must not be called from the handling of a trap of that task (see
+/// [`SharedProcess`]).
+pub fn watch_first_write(task: &AxTaskRef, page: VirtAddr, hook: WriteWatchHook) -> AxResult {
+    let process = RUNNING_THREADS
+        .lock()
+        .get(&task.id().as_u64())
+        .cloned()
+        .ok_or(AxError::NotFound)?;
+    let mut process = process.lock();
+    let dirty = process.mem.dirty.as_ref().ok_or(AxError::Unsupported)?;
+    if !page.is_aligned_4k() || !dirty.contains(page) {
+        return Err(AxError::InvalidInput);
+    }
+    if dirty.is_dirty(page) {
+        return Err(AxError::BadState);
+    }
+    if process.write_watch.is_some() {
+        return Err(AxError::AlreadyExists);
+    }
+    process.write_watch = Some((page, hook));
+    Ok(())
+}
+
+/// Calls `f` with the address space and the demand-paging state of the user
+/// task that the thread `task` belongs to, and returns its result.
+///
+/// `f` may enumerate the regions of the task with
+/// [`UserMemory::lazy_regions`], e.g. to check their bounds, flags, backend
+/// and pages faulted in. The task may be running, or have exited and not be
+/// reaped yet. Returns `None` otherwise.
+///
+/// The state of the task is locked while `f` runs, so this must not be
+/// called from the handling of a trap of that task (see [`SharedProcess`]).
+pub fn inspect_user_memory<R>(
+    task: &AxTaskRef,
+    f: impl FnOnce(&AddrSpace, &UserMemory) -> R,
+) -> Option<R> {
+    let id = task.id().as_u64();
+    let running = RUNNING_THREADS.lock().get(&id).cloned();
+    let process = match running {
+        Some(process) => process,
+        None => EXITED_TASKS.lock().get(&id).cloned()?,
+    };
+    let process = process.lock();
+    Some(f(&process.uspace, &process.mem))
+}
+
+/// Returns the indices of the stack pages written to by the exited user task
+/// `task`, counted down from the stack top (see [`DirtyPages::pages`]).
+///
+/// Returns `None` if `task` did not track its dirty pages, or is not an
+/// exited user task that is still to be reaped.
+pub fn dirty_stack_pages(task: &AxTaskRef) -> Option<Vec<usize>> {
+    EXITED_TASKS
+        .lock()
+        .get(&task.id().as_u64())
+        .and_then(|process| {
+            let process = process.lock();
+            let dirty = process.mem.dirty.as_ref()?;
+            Some(dirty.pages().iter().copied().collect())
+        })
+}
+
+/// Frees the address space of the exited user task `task`, with every frame
+/// mapped in it, and the frames its fault handler allocated.
+///
+/// Must only be called once `task` has exited, e.g. after joining it. Does
+/// nothing if `task` is not an exited user task, or was already reaped.
+pub fn reap_user_task(task: &AxTaskRef) {
+    let exited = EXITED_TASKS.lock().remove(&task.id().as_u64());
+    drop(exited);
+}
+
+/// Prints a summary of the page faults of the current user thread.
+fn print_fault_summary(counters: &FaultCounters) {
+    let stats = counters.snapshot();
+    ax_println!(
+        "{}: mapped {} pages via {} faults",
+        axtask::current().id_name(),
+        stats.pages_mapped,
+        stats.faults
     );
+    if stats.interrupts > 0 {
+        ax_println!(
+            "{}: interrupted {} times in user space",
+            axtask::current().id_name(),
+            stats.interrupts
+        );
+    }
+    if stats.zero_pages > 0 {
+        ax_println!(
+            "{}: {} pages mapped to the zero page",
+            axtask::current().id_name(),
+            stats.zero_pages
+        );
+    }
+    if let Some(watermark) = stats.stack_watermark {
+        ax_println!(
+            "{}: lowest stack fault at {:#x}",
+            axtask::current().id_name(),
+            watermark
+        );
+    }
+    if cfg!(feature = "fault_timing") && stats.faults > 0 {
+        ax_println!(
+            "{}: handled faults in {:?}, {:?} per fault, {:?} at most",
+            axtask::current().id_name(),
+            stats.fault_time,
+            stats.fault_time / stats.faults as u32,
+            stats.max_fault_time
+        );
+    }
+}
 
-    // Set the page table root so the scheduler switches to user space
-    // page table when this task is scheduled.
-    task.ctx_mut().set_page_table_root(page_table_root);
+/// Exits the leader thread of the current user task with `exit_code`.
+///
+/// The state of the task is parked in [`EXITED_TASKS`] until
+/// [`reap_user_task`] frees it: the page table of the task stays active until
+/// it is switched out for good, and so cannot be freed by the task itself.
+fn exit_user_task(process: SharedProcess, exit_code: i32) -> ! {
+    EXITED_TASKS
+        .lock()
+        .insert(axtask::current().id().as_u64(), process);
+    axtask::exit(exit_code)
+}
 
-    axtask::spawn_task(task)
+/// Pushes `args` and `env` on the initial stack `[bottom, top)`, and returns
+/// the stack pointer to start the task with.
+///
+/// The stack is laid out as by the System V ABI: the stack pointer points at
+/// `argc`, followed by the `argv` and `envp` arrays, each ending with a null
+/// pointer, and by an auxiliary vector holding only `AT_NULL`. The strings
+/// sit at the top of the stack. The pages they all take are faulted in now,
+/// through the regular fault handler, so they are not accounted as faults.
+///
+/// Returns [`SpawnError::ArgsTooLarge`] if they do not fit in the stack.
+fn push_args(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    bottom: VirtAddr,
+    top: VirtAddr,
+    args: &[String],
+    env: &[String],
+) -> Result<VirtAddr, SpawnError> {
+    const WORD: usize = size_of::<usize>();
+    let strings_size: usize = args.iter().chain(env).map(|s| s.len() + 1).sum();
+    // argc, argv and envp with their null pointers, and AT_NULL.
+    let words = 1 + (args.len() + 1) + (env.len() + 1) + 2;
+    let sp = top
+        .as_usize()
+        .checked_sub(strings_size + words * WORD)
+        .map(|sp| VirtAddr::from(sp).align_down(STACK_ALIGN))
+        .filter(|&sp| sp >= bottom)
+        .ok_or(SpawnError::ArgsTooLarge)?;
+
+    let mut image = vec![0u8; top - sp];
+    let mut string = top - strings_size;
+    let mut pointers = Vec::with_capacity(words);
+    pointers.push(args.len());
+    for list in [args, env] {
+        for s in list {
+            let offset = string - sp;
+            image[offset..offset + s.len()].copy_from_slice(s.as_bytes());
+            pointers.push(string.as_usize());
+            string += s.len() + 1;
+        }
+        pointers.push(0);
+    }
+    for (i, pointer) in pointers.iter().enumerate() {
+        image[i * WORD..(i + 1) * WORD].copy_from_slice(&pointer.to_ne_bytes());
+    }
+    syscall::copy_to_user(uspace, mem, sp, &image).map_err(|_| SpawnError::MapFailed(sp))?;
+    Ok(sp)
+}
+
+/// Writes `data` at `offset` bytes above the base of the stack laid out by
+/// `layout`, through the frame of `pages` that backs it, before the stack is
+/// mapped.
+///
+/// Returns [`SpawnError::AddrSpaceSetup`] if `data` does not fit in the
+/// initial stack or crosses a page of `pages`, and [`SpawnError::NoPhysPage`]
+/// if no frame backs it.
+fn populate_stack_page(
+    layout: &StackLayout,
+    pages: &SharedPages,
+    offset: usize,
+    data: &[u8],
+) -> Result<(), SpawnError> {
+    let page_size = pages.page_size as usize;
+    let end = offset
+        .checked_add(data.len())
+        .filter(|&end| end <= layout.size)
+        .ok_or(SpawnError::AddrSpaceSetup)?;
+    if data.is_empty() {
+        return Ok(());
+    }
+    let index = offset / page_size;
+    if (end - 1) / page_size != index {
+        return Err(SpawnError::AddrSpaceSetup);
+    }
+    let frame = *pages
+        .phys_pages
+        .get(index)
+        .ok_or(SpawnError::NoPhysPage(index))?;
+    // The frame is owned by `pages`, and not mapped in user space yet.
+    unsafe {
+        core::ptr::copy_nonoverlapping(
+            data.as_ptr(),
+            phys_to_virt(frame + offset % page_size).as_mut_ptr(),
+            data.len(),
+        )
+    };
+    Ok(())
+}
+
+/// Maps every page of `[start, end)` up front, as a read of it would.
+///
+/// The pages go through the regular fault handler, so they are mapped from
+/// the backend of their region, but are not accounted as faults.
+fn map_eagerly(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    start: VirtAddr,
+    end: VirtAddr,
+) -> Result<(), SpawnError> {
+    for page in PageIter4K::new(start, end).ok_or(SpawnError::AddrSpaceSetup)? {
+        match handle_fault(uspace, mem, page, PageFaultFlags::READ) {
+            FaultOutcome::Mapped | FaultOutcome::AlreadyMapped | FaultOutcome::Dirtied => {}
+            FaultOutcome::Segfault(Some(e)) => return Err(e),
+            FaultOutcome::Segfault(None) | FaultOutcome::Permission(_) => {
+                return Err(SpawnError::AddrSpaceSetup);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Maps up to `pages` pages below the page of `vaddr`, which was just
+/// faulted in, and returns how many were mapped.
+///
+/// The pages go through the regular fault handler as reads, so they are
+/// mapped from the backend of the region of `vaddr`, and never beyond it:
+/// the pages of another region, e.g. one the stack grew by, are left to
+/// fault. Pages already mapped are skipped.
+fn read_ahead(
+    uspace: &mut AddrSpace,
+    mem: &mut UserMemory,
+    vaddr: VirtAddr,
+    pages: usize,
+) -> usize {
+    let Some(start) = mem.regions.find(vaddr).map(|region| region.start) else {
+        return 0;
+    };
+    let mut mapped = 0;
+    let mut page = vaddr.align_down_4k();
+    for _ in 0..pages {
+        match page
+            .checked_sub(PAGE_SIZE_4K)
+            .filter(|&below| below >= start)
+        {
+            Some(below) => page = below,
+            None => break,
+        }
+        if matches!(
+            handle_fault(uspace, mem, page, PageFaultFlags::READ),
+            FaultOutcome::Mapped
+        ) {
+            mapped += 1;
+        }
+    }
+    mapped
+}
+
+/// Maps every page of the region of `vaddr`, which was just faulted in, and
+/// returns how many were mapped.
+///
+/// The pages go through the regular fault handler as reads, like those of
+/// [`read_ahead`], so a page of a dirty-tracked stack, or one shared
+/// copy-on-write, still faults on its first write. Pages already mapped are
+/// skipped.
+fn map_rest_of_region(uspace: &mut AddrSpace, mem: &mut UserMemory, vaddr: VirtAddr) -> usize {
+    let Some((start, end)) = mem.regions.find(vaddr).map(|r| (r.start, r.end)) else {
+        return 0;
+    };
+    PageIter4K::new(start, end)
+        .into_iter()
+        .flatten()
+        .filter(|&page| {
+            matches!(
+                handle_fault(uspace, mem, page, PageFaultFlags::READ),
+                FaultOutcome::Mapped
+            )
+        })
+        .count()
+}
+
+/// Extends the stack downward from `*bottom` so that it covers `vaddr`.
+///
+/// The new pages are registered lazily with `flags` as a new on-demand region
+/// of `regions`, and `*bottom` is updated to the new lowest stack address.
+fn grow_stack(
+    uspace: &mut AddrSpace,
+    regions: &mut LazyRegions,
+    bottom: &mut VirtAddr,
+    vaddr: VirtAddr,
+    flags: MappingFlags,
+) -> Result<(), SpawnError> {
+    let new_bottom = vaddr.align_down_4k();
+    uspace
+        .map_alloc(new_bottom, *bottom - new_bottom, flags, false)
+        .map_err(|_| SpawnError::MapFailed(new_bottom))?;
+    regions.insert(LazyRegion::new(
+        new_bottom,
+        *bottom,
+        flags,
+        LazyBackend::OnDemand,
+    ));
+    ax_println!("grow user stack: {:#x} -> {:#x}", *bottom, new_bottom);
+    *bottom = new_bottom;
+    Ok(())
 }
@@ -1,7 +1,8 @@
 use clap::{Parser, Subcommand};
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::{self, Command};
+use std::process::{self, Command, Stdio};
+use std::time::{Duration, Instant};
 
 /// ArceOS lazymapping multi-architecture build & run tool
 #[derive(Parser)]
@@ -20,14 +21,61 @@ enum Cmd {
     Build {
         #[arg(long, default_value = "riscv64")]
         arch: String,
+        /// Payload binary to build (`origin`, `heap`, `segfault`, `rodata`,
+        /// `nosys`, `sigsegv`, `stack`, `mmapfixed`, `thread`, `bench`,
+        /// `getrandom`, `zeroed`, `args`, `sleep`, `stacktop`, `adversarial`,
+        /// `linear`, `zeropage`, `mprotect`, `fp`, `storm`, `straddle`,
+        /// `illegal`, `overflow`, `pattern`, `uname`, `pie`, `spin`,
+        /// `exitcode`, `preload`, `echo`, `info`, `unterminated`, `rusage`,
+        /// `race`, `scramble`, `stackexec`, `overrun`, `fstat`, `exitgroup`,
+        /// `willneed` or `brkoverrun`)
+        #[arg(long, default_value = "origin")]
+        payload: String,
+        /// Link the payload as a position-independent executable, which the
+        /// kernel relocates at load time
+        #[arg(long)]
+        pie: bool,
+        /// Extra kernel features, comma-separated (e.g. `user-args`)
+        #[arg(long, default_value = "")]
+        features: String,
     },
     /// Build and run the kernel in QEMU
     Run {
         #[arg(long, default_value = "riscv64")]
         arch: String,
+        /// Payload binary to install as `/sbin/origin` (`origin`, `heap`,
+        /// `segfault`, `rodata`, `nosys`, `sigsegv`, `stack`, `mmapfixed`,
+        /// `thread`, `bench`, `getrandom`, `zeroed`, `args`, `sleep`,
+        /// `stacktop`, `adversarial`, `linear`, `zeropage`, `mprotect`, `fp`,
+        /// `storm`, `straddle`, `illegal`, `overflow`, `pattern`, `uname`,
+        /// `pie`, `spin`, `exitcode`, `preload`, `echo`, `info`,
+        /// `unterminated`, `rusage`, `race`, `scramble`, `stackexec`,
+        /// `overrun`, `fstat`, `exitgroup`, `willneed` or `brkoverrun`)
+        #[arg(long, default_value = "origin")]
+        payload: String,
+        /// Link the payload as a position-independent executable, which the
+        /// kernel relocates at load time
+        #[arg(long)]
+        pie: bool,
+        /// Extra kernel features, comma-separated (e.g. `user-args`)
+        #[arg(long, default_value = "")]
+        features: String,
+    },
+    /// Boot the kernel in QEMU and check that the payload's page faults are
+    /// handled and its tasks exit cleanly
+    Test {
+        /// Architecture to test (all supported architectures if omitted)
+        #[arg(long)]
+        arch: Option<String>,
     },
 }
 
+/// Architectures supported by the kernel.
+const ARCHS: [&str; 4] = ["riscv64", "aarch64", "x86_64", "loongarch64"];
+
+/// How long a test run may take before QEMU is killed.
+const TEST_TIMEOUT: Duration = Duration::from_secs(120);
+
 #[allow(dead_code)]
 struct ArchInfo {
     target: &'static str,
@@ -86,18 +134,30 @@ fn install_config(root: &Path, arch: &str) {
     println!("Installed config: {} -> .axconfig.toml", src.display());
 }
 
-/// Build the user-space payload binary for the target architecture.
-/// Equivalent to `make payload` in the original workflow.
-fn build_payload(root: &Path, info: &ArchInfo) -> PathBuf {
-    println!("Building payload for {} ...", info.target);
-    let status = Command::new("cargo")
+/// Build the user-space payload ELF `name` for the target architecture, as
+/// a static PIE if `pie`. Equivalent to `make payload` in the original
+/// workflow.
+fn build_payload(root: &Path, info: &ArchInfo, name: &str, pie: bool) -> PathBuf {
+    println!("Building payload {} for {} ...", name, info.target);
+    let mut cmd = Command::new("cargo");
+    if pie {
+        // Read by build.rs, to link with payload/pie.ld.
+        let rustflags = std::env::var("RUSTFLAGS").unwrap_or_default();
+        cmd.env("LAZYMAPPING_PIE", "1").env(
+            "RUSTFLAGS",
+            format!("{rustflags} -C relocation-model=pie").trim_start(),
+        );
+    } else {
+        cmd.env_remove("LAZYMAPPING_PIE");
+    }
+    let status = cmd
         .args([
             "build",
             "--release",
             "--target",
             info.target,
             "--bin",
-            "origin",
+            name,
             "--features",
             "payload",
             "--manifest-path",
@@ -110,32 +170,14 @@ fn build_payload(root: &Path, info: &ArchInfo) -> PathBuf {
         process::exit(status.code().unwrap_or(1));
     }
 
-    // Objcopy to flat binary
+    // The kernel loads the payload ELF directly, one region per segment.
     let elf = root
         .join("target")
         .join(info.target)
         .join("release")
-        .join("origin");
-    let bin = elf.with_extension("bin");
-
-    let status = Command::new("rust-objcopy")
-        .args([
-            &format!("--binary-architecture={}", info.objcopy_arch),
-            elf.to_str().unwrap(),
-            "--strip-all",
-            "-O",
-            "binary",
-            bin.to_str().unwrap(),
-        ])
-        .status()
-        .expect("failed to execute rust-objcopy for payload");
-    if !status.success() {
-        eprintln!("Error: payload objcopy failed");
-        process::exit(status.code().unwrap_or(1));
-    }
-
-    println!("Payload built: {}", bin.display());
-    bin
+        .join(name);
+    println!("Payload built: {}", elf.display());
+    elf
 }
 
 /// Create a 64MB FAT32 disk image containing `/sbin/origin`.
@@ -204,8 +246,19 @@ fn create_fat_disk_image(path: &Path, payload_bin: &Path) {
     );
 }
 
-/// Build the kernel.
-fn do_build(root: &Path, info: &ArchInfo) {
+/// Returns the kernel features: `axstd`, and the comma-separated `extra`
+/// ones if any.
+fn kernel_features(extra: &str) -> String {
+    if extra.is_empty() {
+        "axstd".into()
+    } else {
+        format!("axstd,{extra}")
+    }
+}
+
+/// Build the kernel with `features` (which must include `axstd`), embedding
+/// `payload` with the `embed-payload` feature.
+fn do_build(root: &Path, info: &ArchInfo, features: &str, payload: &Path) {
     let manifest = root.join("Cargo.toml");
     let ax_config = root.join(".axconfig.toml");
     let status = Command::new("cargo")
@@ -215,12 +268,13 @@ fn do_build(root: &Path, info: &ArchInfo) {
             "--target",
             info.target,
             "--features",
-            "axstd",
+            features,
             "--manifest-path",
             manifest.to_str().unwrap(),
         ])
         // Ensure dependencies read the intended config regardless of subprocess cwd.
         .env("AX_CONFIG_PATH", ax_config.to_str().unwrap())
+        .env("LAZYMAPPING_PAYLOAD", payload)
         .status()
         .expect("failed to execute cargo build");
     if !status.success() {
@@ -248,8 +302,56 @@ fn do_objcopy(elf: &Path, bin: &Path, objcopy_arch: &str) {
     }
 }
 
+/// Build the payload (as a PIE if `pie`), the disk image and the kernel (with
+/// `features`), ready to boot. Returns the kernel ELF, the kernel raw binary
+/// and the disk image.
+fn prepare_run(
+    root: &Path,
+    arch: &str,
+    payload: &str,
+    pie: bool,
+    features: &str,
+) -> (PathBuf, PathBuf, PathBuf) {
+    let info = arch_info(arch);
+    install_config(root, arch);
+
+    // 1. Build payload (equivalent to `make payload`)
+    let payload_bin = build_payload(root, &info, payload, pie);
+
+    // 2. Create disk image with payload (equivalent to `./update_disk.sh`)
+    let disk = root.join("target").join(format!("disk-{arch}.img"));
+    create_fat_disk_image(&disk, &payload_bin);
+
+    // 3. Build kernel (equivalent to `make run A=tour/m_2_0 BLK=y`)
+    do_build(root, &info, features, &payload_bin);
+
+    let elf = root
+        .join("target")
+        .join(info.target)
+        .join("release")
+        .join("arceos-lazymapping");
+    let bin = elf.with_extension("bin");
+
+    if arch != "x86_64" {
+        do_objcopy(&elf, &bin, info.objcopy_arch);
+    }
+    (elf, bin, disk)
+}
+
 /// Run QEMU with VirtIO block device.
 fn do_run_qemu(arch: &str, elf: &Path, bin: &Path, disk: &Path) {
+    let mut qemu = qemu_command(arch, elf, bin, disk);
+    let status = qemu.status().unwrap_or_else(|e| {
+        eprintln!("Error: failed to run {:?}: {}", qemu.get_program(), e);
+        process::exit(1);
+    });
+    if !status.success() {
+        process::exit(status.code().unwrap_or(1));
+    }
+}
+
+/// Build the QEMU command line booting the kernel with the disk image.
+fn qemu_command(arch: &str, elf: &Path, bin: &Path, disk: &Path) -> Command {
     let mem = "128M";
     let smp = "1";
     let qemu = format!("qemu-system-{arch}");
@@ -311,16 +413,66 @@ fn do_run_qemu(arch: &str, elf: &Path, bin: &Path, disk: &Path) {
     ]);
 
     println!("Running: {} {}", qemu, args.join(" "));
-    let status = Command::new(&qemu)
-        .args(&args)
-        .status()
-        .unwrap_or_else(|e| {
-            eprintln!("Error: failed to run {}: {}", qemu, e);
-            process::exit(1);
-        });
-    if !status.success() {
-        process::exit(status.code().unwrap_or(1));
+    let mut cmd = Command::new(&qemu);
+    cmd.args(&args);
+    cmd
+}
+
+/// Boot the kernel with the default payload on `arch` and check its console
+/// output (see [`check_output`]).
+///
+/// The kernel is built with `verbose_faults`, so that every handled fault is
+/// printed. The console output is kept in `target/test-<arch>.log`.
+fn do_test(root: &Path, arch: &str) -> Result<(), String> {
+    let (elf, bin, disk) = prepare_run(root, arch, "origin", false, "axstd,verbose_faults");
+
+    let log_path = root.join("target").join(format!("test-{arch}.log"));
+    let log = std::fs::File::create(&log_path)
+        .map_err(|e| format!("failed to create {}: {}", log_path.display(), e))?;
+    let mut child = qemu_command(arch, &elf, &bin, &disk)
+        .stdin(Stdio::null())
+        .stdout(log)
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| format!("failed to run qemu-system-{arch}: {e}"))?;
+
+    // The kernel powers the machine off once all tasks have exited.
+    let deadline = Instant::now() + TEST_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(100)),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {}s", TEST_TIMEOUT.as_secs()));
+            }
+            Err(e) => return Err(format!("failed to wait for QEMU: {e}")),
+        }
+    }
+
+    let output = std::fs::read(&log_path)
+        .map_err(|e| format!("failed to read {}: {}", log_path.display(), e))?;
+    check_output(&String::from_utf8_lossy(&output))
+}
+
+/// Check the console output of a run of the default payload: a page fault is
+/// handled, then every task exits with 0 and the kernel exits normally.
+fn check_output(output: &str) -> Result<(), String> {
+    let fault = output
+        .find("handle page fault OK!")
+        .ok_or("no page fault was handled")?;
+    let rest = &output[fault..];
+    if !rest.contains("exit [0]") {
+        return Err("no task exited with 0 after the page fault".into());
+    }
+    if output.contains("exit [-") {
+        return Err("a task exited with an error".into());
+    }
+    if !rest.contains("monolithic kernel exit normally!") {
+        return Err("the kernel did not exit normally".into());
     }
+    Ok(())
 }
 
 fn main() {
@@ -328,39 +480,46 @@ fn main() {
     let root = project_root();
 
     match cli.command {
-        Cmd::Build { ref arch } => {
+        Cmd::Build {
+            ref arch,
+            ref payload,
+            pie,
+            ref features,
+        } => {
             let info = arch_info(arch);
             install_config(&root, arch);
-            let _payload = build_payload(&root, &info);
-            do_build(&root, &info);
+            let payload = build_payload(&root, &info, payload, pie);
+            do_build(&root, &info, &kernel_features(features), &payload);
             println!("Build complete for {arch} ({})", info.target);
         }
-        Cmd::Run { ref arch } => {
-            let info = arch_info(arch);
-            install_config(&root, arch);
-
-            // 1. Build payload (equivalent to `make payload`)
-            let payload_bin = build_payload(&root, &info);
-
-            // 2. Create disk image with payload (equivalent to `./update_disk.sh`)
-            let disk = root.join("target").join(format!("disk-{arch}.img"));
-            create_fat_disk_image(&disk, &payload_bin);
-
-            // 3. Build kernel (equivalent to `make run A=tour/m_2_0 BLK=y`)
-            do_build(&root, &info);
-
-            let elf = root
-                .join("target")
-                .join(info.target)
-                .join("release")
-                .join("arceos-lazymapping");
-            let bin = elf.with_extension("bin");
-
-            if arch != "x86_64" {
-                do_objcopy(&elf, &bin, info.objcopy_arch);
-            }
-
+        Cmd::Run {
+            ref arch,
+            ref payload,
+            pie,
+            ref features,
+        } => {
+            let (elf, bin, disk) =
+                prepare_run(&root, arch, payload, pie, &kernel_features(features));
             do_run_qemu(arch, &elf, &bin, &disk);
         }
+        Cmd::Test { ref arch } => {
+            let archs = match arch {
+                Some(arch) => vec![arch.as_str()],
+                None => ARCHS.to_vec(),
+            };
+            let mut failed = false;
+            for arch in archs {
+                match do_test(&root, arch) {
+                    Ok(()) => println!("test {arch}: ok"),
+                    Err(e) => {
+                        eprintln!("test {arch}: FAILED: {e}");
+                        failed = true;
+                    }
+                }
+            }
+            if failed {
+                process::exit(1);
+            }
+        }
     }
 }